@@ -0,0 +1,22 @@
+-- Matches generated as part of a draw may not know their players yet,
+-- they are filled in as the earlier rounds are finished
+ALTER TABLE matches
+    ALTER COLUMN player_one DROP NOT NULL,
+    ALTER COLUMN player_two DROP NOT NULL,
+    ADD COLUMN IF NOT EXISTS round INTEGER; -- NULL indicates the match isn't part of a draw
+
+-- The winner of from_match advances into the given slot (player one or two) of to_match
+CREATE TABLE IF NOT EXISTS match_progression (
+    from_match_id BIGINT NOT NULL,
+    to_match_id BIGINT NOT NULL,
+    slot SMALLINT NOT NULL CHECK (slot IN (1, 2)),
+    PRIMARY KEY (from_match_id),
+    CONSTRAINT valid_from_match
+        FOREIGN KEY(from_match_id)
+            REFERENCES matches(id)
+            ON DELETE CASCADE,
+    CONSTRAINT valid_to_match
+        FOREIGN KEY(to_match_id)
+            REFERENCES matches(id)
+            ON DELETE CASCADE
+);
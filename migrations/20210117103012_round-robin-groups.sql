@@ -0,0 +1,33 @@
+CREATE TABLE IF NOT EXISTS tournament_groups (
+    id SERIAL PRIMARY KEY,
+    tournament_id INTEGER NOT NULL,
+    class TEXT NOT NULL,
+    name TEXT NOT NULL,
+    -- Applied in order when players have the same number of wins
+    tiebreak_rules TEXT[] NOT NULL,
+    UNIQUE (tournament_id, class, name),
+    CONSTRAINT valid_tournament
+        FOREIGN KEY(tournament_id)
+            REFERENCES tournaments(id)
+            ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS group_players (
+    group_id INTEGER NOT NULL,
+    player_id BIGINT NOT NULL,
+    PRIMARY KEY (group_id, player_id),
+    CONSTRAINT valid_group
+        FOREIGN KEY(group_id)
+            REFERENCES tournament_groups(id)
+            ON DELETE CASCADE,
+    CONSTRAINT valid_player
+        FOREIGN KEY(player_id)
+            REFERENCES players(id)
+            ON DELETE CASCADE
+);
+
+-- NULL indicates the match isn't part of a group
+ALTER TABLE matches
+    ADD COLUMN IF NOT EXISTS group_id INTEGER
+        REFERENCES tournament_groups(id)
+        ON DELETE CASCADE;
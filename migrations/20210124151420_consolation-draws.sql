@@ -0,0 +1,13 @@
+-- Which draw of the class the match is part of (main, losers or consolation),
+-- NULL indicates the match isn't part of a draw
+ALTER TABLE matches
+    ADD COLUMN IF NOT EXISTS draw TEXT;
+UPDATE matches SET draw = 'main' WHERE round IS NOT NULL;
+
+-- Losers can advance into a consolation or losers draw, so a match
+-- may have one progression for the winner and one for the loser
+ALTER TABLE match_progression
+    ADD COLUMN IF NOT EXISTS advancing TEXT NOT NULL DEFAULT 'winner'
+        CHECK (advancing IN ('winner', 'loser')),
+    DROP CONSTRAINT IF EXISTS match_progression_pkey,
+    ADD PRIMARY KEY (from_match_id, advancing);
@@ -0,0 +1,5 @@
+-- Match results are validated against the format of the tournament
+ALTER TABLE tournaments
+    ADD COLUMN IF NOT EXISTS best_of SMALLINT NOT NULL DEFAULT 3 CHECK (best_of IN (3, 5)),
+    -- Play a match tiebreak to 10 instead of the deciding set
+    ADD COLUMN IF NOT EXISTS match_tiebreak BOOLEAN NOT NULL DEFAULT FALSE;
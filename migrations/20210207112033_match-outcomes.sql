@@ -0,0 +1,4 @@
+-- How the match ended, the result holds the (partial) score if there is one
+ALTER TABLE match_result
+    ADD COLUMN IF NOT EXISTS outcome TEXT NOT NULL DEFAULT 'completed'
+        CHECK (outcome IN ('completed', 'walkover', 'retired', 'disqualified'));
@@ -0,0 +1,19 @@
+-- Finished matches can have their result corrected afterwards,
+-- the replaced result is kept together with who corrected it and when
+CREATE TABLE IF NOT EXISTS match_result_corrections (
+    id SERIAL8 PRIMARY KEY,
+    match_id BIGINT NOT NULL,
+    previous_result TEXT NOT NULL,
+    previous_winner BIGINT NOT NULL,
+    previous_outcome TEXT NOT NULL,
+    corrected_by UUID, -- null if the user has been removed
+    corrected_at TIMESTAMP NOT NULL,
+    CONSTRAINT valid_match
+        FOREIGN KEY(match_id)
+            REFERENCES matches(id)
+            ON DELETE CASCADE,
+    CONSTRAINT valid_user
+        FOREIGN KEY(corrected_by)
+            REFERENCES users(id)
+            ON DELETE SET NULL
+);
@@ -0,0 +1,19 @@
+-- A doubles pair. Every team also has a row in players with the same id so it can take
+-- part in matches, draws and groups just like a single player, while check-in is done
+-- by the two players of the team.
+CREATE TABLE IF NOT EXISTS teams (
+    id BIGINT PRIMARY KEY,
+    player_one BIGINT NOT NULL,
+    player_two BIGINT NOT NULL CHECK (player_one <> player_two),
+    CONSTRAINT valid_team
+        FOREIGN KEY(id)
+            REFERENCES players(id)
+            ON DELETE CASCADE,
+    CONSTRAINT valid_players
+        FOREIGN KEY(player_one)
+            REFERENCES players(id)
+            ON DELETE CASCADE,
+        FOREIGN KEY(player_two)
+            REFERENCES players(id)
+            ON DELETE CASCADE
+);
@@ -0,0 +1,8 @@
+-- The user that created the tournament, tournaments created before
+-- owners were tracked don't have one
+ALTER TABLE tournaments
+    ADD COLUMN IF NOT EXISTS owner UUID,
+    ADD CONSTRAINT valid_owner
+        FOREIGN KEY(owner)
+            REFERENCES users(id)
+            ON DELETE SET NULL;
@@ -0,0 +1,15 @@
+-- Roles the owner of a tournament has given other users in that tournament
+CREATE TABLE IF NOT EXISTS tournament_roles (
+    tournament_id INTEGER NOT NULL,
+    user_id UUID NOT NULL,
+    role TEXT NOT NULL CHECK (role IN ('organizer', 'referee', 'desk')),
+    PRIMARY KEY (tournament_id, user_id),
+    CONSTRAINT valid_tournament
+        FOREIGN KEY(tournament_id)
+            REFERENCES tournaments(id)
+            ON DELETE CASCADE,
+    CONSTRAINT valid_user
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
@@ -0,0 +1,5 @@
+-- Global role of the user, admins can manage all tournaments and users.
+-- The first admin has to be promoted directly in the database:
+-- UPDATE users SET role = 'admin' WHERE email = '...';
+ALTER TABLE users
+    ADD COLUMN role TEXT NOT NULL DEFAULT 'user' CHECK (role IN ('user', 'admin'));
@@ -0,0 +1,25 @@
+-- A session is started at login and is shared by every refresh token issued
+-- for it, revoking the session revokes the whole refresh token family
+CREATE TABLE IF NOT EXISTS sessions (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    revoked_at TIMESTAMP,
+    CONSTRAINT valid_user
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
+
+-- Only the SHA-256 hash of the refresh token is stored, a used token
+-- is kept around to be able to detect reuse of stolen tokens
+CREATE TABLE IF NOT EXISTS refresh_tokens (
+    token_hash TEXT PRIMARY KEY,
+    session_id UUID NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP,
+    CONSTRAINT valid_session
+        FOREIGN KEY(session_id)
+            REFERENCES sessions(id)
+            ON DELETE CASCADE
+);
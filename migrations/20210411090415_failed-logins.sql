@@ -0,0 +1,9 @@
+-- Failed login attempts per email and per IP address, shared by all instances
+CREATE TABLE IF NOT EXISTS failed_logins (
+    kind TEXT NOT NULL CHECK (kind IN ('email', 'ip')),
+    identifier TEXT NOT NULL,
+    failures INTEGER NOT NULL,
+    last_failure TIMESTAMP NOT NULL,
+    locked_until TIMESTAMP,
+    PRIMARY KEY (kind, identifier)
+);
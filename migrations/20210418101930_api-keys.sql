@@ -0,0 +1,20 @@
+-- Long lived keys for devices like check-in kiosks, a key only works in one
+-- tournament and for the listed actions. Only the SHA-256 hash of the key is stored.
+CREATE TABLE IF NOT EXISTS api_keys (
+    id UUID PRIMARY KEY,
+    tournament_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL,
+    created_by UUID,
+    created_at TIMESTAMP NOT NULL,
+    revoked_at TIMESTAMP,
+    CONSTRAINT valid_tournament
+        FOREIGN KEY(tournament_id)
+            REFERENCES tournaments(id)
+            ON DELETE CASCADE,
+    CONSTRAINT valid_creator
+        FOREIGN KEY(created_by)
+            REFERENCES users(id)
+            ON DELETE SET NULL
+);
@@ -0,0 +1,13 @@
+-- Registrations now record the user or api key that checked the player in.
+-- registerd_by is kept as a display name, rows from before this only have the name.
+ALTER TABLE register
+    ADD COLUMN registerd_by_user UUID,
+    ADD COLUMN registerd_by_api_key UUID,
+    ADD CONSTRAINT valid_registerd_by_user
+        FOREIGN KEY(registerd_by_user)
+            REFERENCES users(id)
+            ON DELETE SET NULL,
+    ADD CONSTRAINT valid_registerd_by_api_key
+        FOREIGN KEY(registerd_by_api_key)
+            REFERENCES api_keys(id)
+            ON DELETE SET NULL;
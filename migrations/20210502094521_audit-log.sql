@@ -0,0 +1,26 @@
+-- Every state changing request is recorded here. Rows are never updated or deleted,
+-- there are no foreign keys so entries outlive the users, tournaments and matches they refer to.
+CREATE TABLE IF NOT EXISTS audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    user_id UUID,
+    api_key_id UUID,
+    endpoint TEXT NOT NULL,
+    tournament_id INTEGER,
+    match_id BIGINT,
+    player_id BIGINT,
+    before JSONB,
+    after JSONB,
+    created_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX audit_log_tournament_idx ON audit_log (tournament_id, id);
+
+CREATE FUNCTION prevent_audit_log_change() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_log is append only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_append_only
+    BEFORE UPDATE OR DELETE ON audit_log
+    FOR EACH ROW EXECUTE FUNCTION prevent_audit_log_change();
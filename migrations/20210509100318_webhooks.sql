@@ -0,0 +1,43 @@
+-- Urls that are called when the chosen events happen in a tournament. The secret
+-- is used to sign the payloads so it has to be stored as is.
+CREATE TABLE IF NOT EXISTS webhooks (
+    id UUID PRIMARY KEY,
+    tournament_id INTEGER NOT NULL,
+    url TEXT NOT NULL,
+    event_types TEXT[] NOT NULL,
+    secret TEXT NOT NULL,
+    created_by UUID,
+    created_at TIMESTAMP NOT NULL,
+    CONSTRAINT valid_tournament
+        FOREIGN KEY(tournament_id)
+            REFERENCES tournaments(id)
+            ON DELETE CASCADE,
+    CONSTRAINT valid_creator
+        FOREIGN KEY(created_by)
+            REFERENCES users(id)
+            ON DELETE SET NULL
+);
+
+-- Outbox of the webhook calls, a delivery is retried until it succeeds or runs out of attempts.
+-- The payload is kept as text since the signature is calculated from the exact bytes.
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    id BIGSERIAL PRIMARY KEY,
+    webhook_id UUID NOT NULL,
+    event_type TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    status TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    status_code INTEGER,
+    last_error TEXT,
+    next_attempt_at TIMESTAMP NOT NULL,
+    last_attempt_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL,
+    CONSTRAINT valid_webhook
+        FOREIGN KEY(webhook_id)
+            REFERENCES webhooks(id)
+            ON DELETE CASCADE
+);
+
+CREATE INDEX webhook_deliveries_pending_idx ON webhook_deliveries (next_attempt_at)
+    WHERE status = 'pending';
+CREATE INDEX webhook_deliveries_webhook_idx ON webhook_deliveries (webhook_id, id);
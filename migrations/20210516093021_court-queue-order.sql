@@ -0,0 +1,18 @@
+-- The queue is ordered by position so matches can be moved around, place_in_queue is
+-- kept as the time the match joined the queue. Matches pulled out of the queue keep
+-- their row but are skipped until they are put back.
+ALTER TABLE court_queue ADD COLUMN position INTEGER;
+ALTER TABLE court_queue ADD COLUMN on_hold BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE court_queue SET position = ordered.position
+FROM (
+    SELECT tournament_id, match_id,
+        ROW_NUMBER() OVER (PARTITION BY tournament_id ORDER BY place_in_queue, match_id) AS position
+    FROM court_queue
+) AS ordered
+WHERE court_queue.tournament_id = ordered.tournament_id
+AND court_queue.match_id = ordered.match_id;
+
+ALTER TABLE court_queue ALTER COLUMN position SET NOT NULL;
+
+CREATE INDEX court_queue_position_idx ON court_queue (tournament_id, position);
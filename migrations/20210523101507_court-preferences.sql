@@ -0,0 +1,15 @@
+-- What the matches need from the court they are assigned and how urgent they are.
+-- Matches with a higher priority are placed ahead of lower ones when they join the court queue.
+ALTER TABLE matches
+    ADD COLUMN priority INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN preferred_court TEXT,
+    -- Only the preferred court may be used
+    ADD COLUMN court_required BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN required_surface TEXT,
+    ADD COLUMN required_indoor BOOLEAN,
+    ADD COLUMN requires_lighting BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE tournament_court_allocation
+    ADD COLUMN surface TEXT,
+    ADD COLUMN indoor BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN lighting BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,14 @@
+-- The events published to the clients following a tournament. NOTIFY only carries the
+-- id since its payload is limited to 8000 bytes, the listeners fetch the event from here.
+CREATE TABLE IF NOT EXISTS tournament_events (
+    id BIGSERIAL PRIMARY KEY,
+    tournament_id INTEGER NOT NULL,
+    payload TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    CONSTRAINT valid_tournament
+        FOREIGN KEY(tournament_id)
+            REFERENCES tournaments(id)
+            ON DELETE CASCADE
+);
+
+CREATE INDEX tournament_events_created_at_idx ON tournament_events (created_at);
@@ -1,26 +1,55 @@
-use crate::configuration::Settings;
-use crate::{stores::user_store::UserStore, ServerError};
+use crate::configuration::{get_configuration, LoginSettings, Settings, SigningKeySettings};
+use crate::mailer::{Email, Mailer};
+use crate::stores::{
+    api_key_store::{ApiKey, ApiKeyScope, ApiKeyStore},
+    email_verification_store::EmailVerificationStore,
+    failed_login_store::{FailedLoginStore, LoginAttemptKind},
+    match_store::MatchStore,
+    password_reset_store::PasswordResetStore,
+    player_registration_store::Registrar,
+    session_store::SessionStore,
+    tournament_role_store::{TournamentRole, TournamentRoleStore},
+    tournament_store::TournamentStore,
+};
+use crate::{
+    stores::user_store::{UserInfoRow, UserRole, UserStore},
+    ServerError,
+};
 use actix_web::dev::{Payload, PayloadStream};
+use actix_web::HttpMessage;
 use actix_web::{dev::ServiceRequest, Error, FromRequest, HttpRequest};
 use actix_web_httpauth::extractors::bearer::BearerAuth;
-use chrono::Local;
+use chrono::{Duration, Local};
 use futures::future::{err, ready};
 use futures::prelude::future::Ready;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
-use once_cell::sync::{Lazy, OnceCell};
+use jsonwebtoken::{decode, decode_header, encode, DecodingKey, EncodingKey, Header, Validation};
+use once_cell::sync::Lazy;
 use regex::Regex;
+use ring::{
+    digest::{digest, SHA256},
+    rand::{SecureRandom, SystemRandom},
+};
 use serde::Deserialize;
 use serde::Serialize;
 use sqlx::PgPool;
-use tracing::{error, warn};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
 const AUTH_HEADER: &str = "Authorization";
 const HEADER_PREFIX: &str = "Bearer ";
-const THREE_DAYS_SECONDS: usize = 60 * 60 * 24 * 3;
+// Lets authenticate_request tell API keys apart from JWTs
+const API_KEY_PREFIX: &str = "ttk_";
+const ACCESS_TOKEN_SECONDS: usize = 60 * 15;
+const REFRESH_TOKEN_DAYS: i64 = 30;
+const PASSWORD_RESET_MINUTES: i64 = 60;
+const EMAIL_VERIFICATION_HOURS: i64 = 24;
 const PATTERN: &str = include_str!("../email_regex.txt");
-static DECODING_KEY: OnceCell<DecodingKey> = OnceCell::new();
-static ENCODING_KEY: OnceCell<EncodingKey> = OnceCell::new();
+// Replaced as a whole when the keys are reloaded
+static SIGNING_KEYS: Lazy<RwLock<Option<Arc<SigningKeys>>>> = Lazy::new(|| RwLock::new(None));
 static EMAIL_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(PATTERN).expect("Regex is invalid"));
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -28,11 +57,34 @@ struct Claims {
     exp: usize,
     iat: usize,
     sub: String,
+    // Tokens issued before roles existed belong to regular users
+    #[serde(default)]
+    role: UserRole,
+    // The login session the token was issued for
+    sid: Uuid,
 }
 
 #[derive(Debug)]
 pub struct UserInfo {
     pub id: Uuid,
+    pub role: UserRole,
+    pub session_id: Uuid,
+}
+
+// The access token is short lived and is renewed using the refresh token,
+// every refresh token can only be used once
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthTokens {
+    pub access_token: String,
+    pub refresh_token: String,
+    // Seconds until the access token expires
+    pub expires_in: usize,
+}
+
+impl UserInfo {
+    pub fn is_admin(&self) -> bool {
+        self.role == UserRole::Admin
+    }
 }
 
 impl FromRequest for UserInfo {
@@ -64,15 +116,141 @@ impl FromRequest for UserInfo {
     }
 }
 
+// Who made the request, endpoints that accept API keys extract this instead of UserInfo
+#[derive(Debug)]
+pub enum Caller {
+    User(UserInfo),
+    ApiKey(ApiKey),
+}
+
+impl Caller {
+    pub fn user_id(&self) -> Option<Uuid> {
+        match self {
+            Caller::User(user_info) => Some(user_info.id),
+            Caller::ApiKey(_) => None,
+        }
+    }
+
+    pub fn api_key_id(&self) -> Option<Uuid> {
+        match self {
+            Caller::User(_) => None,
+            Caller::ApiKey(api_key) => Some(api_key.id),
+        }
+    }
+}
+
+impl FromRequest for Caller {
+    type Error = ServerError;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload<PayloadStream>) -> Self::Future {
+        // Inserted by authenticate_request
+        if let Some(api_key) = req.extensions().get::<ApiKey>() {
+            return ready(Ok(Caller::ApiKey(api_key.clone())));
+        }
+        match UserInfo::from_request(req, payload).into_inner() {
+            Ok(user_info) => ready(Ok(Caller::User(user_info))),
+            Err(error) => err(error),
+        }
+    }
+}
+
+// Extracting this instead of UserInfo makes the endpoint admin only
+#[derive(Debug)]
+pub struct AdminUser(pub UserInfo);
+
+impl FromRequest for AdminUser {
+    type Error = ServerError;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload<PayloadStream>) -> Self::Future {
+        match UserInfo::from_request(req, payload).into_inner() {
+            Ok(user_info) if user_info.is_admin() => ready(Ok(AdminUser(user_info))),
+            Ok(user_info) => {
+                warn!("User {} tried to use an admin endpoint", user_info.id);
+                err(ServerError::AdminRequired)
+            }
+            Err(error) => err(error),
+        }
+    }
+}
+
+struct SigningKeys {
+    active_key_id: String,
+    encoding_key: EncodingKey,
+    decoding_keys: HashMap<String, DecodingKey<'static>>,
+}
+
+// The ids of the keys that are in use, never contains the keys themselves
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct SigningKeyInfo {
+    pub active_key_id: String,
+    pub key_ids: Vec<String>,
+}
+
 pub fn set_keys(config: &Settings) {
-    let key = &config.application.private_key;
-    assert!(48 <= key.len(), "Private key is too short");
-    DECODING_KEY
-        .set(DecodingKey::from_base64_secret(key).expect("Key must be base64 encoded"))
-        .expect("Failed to set decoding key");
-    ENCODING_KEY
-        .set(EncodingKey::from_base64_secret(key).expect("Key must be base64 encoded"))
-        .expect("Failed to set encoding key");
+    set_signing_keys(&config.signing_keys).expect("Invalid signing keys");
+}
+
+// Replaces the signing keys, tokens signed with keys that are removed stop working
+pub fn set_signing_keys(settings: &SigningKeySettings) -> Result<SigningKeyInfo, String> {
+    let mut decoding_keys = HashMap::new();
+    for (id, key) in settings.keys.iter() {
+        if key.len() < 48 {
+            return Err(format!("Signing key {} is too short", id));
+        }
+        let decoding_key = DecodingKey::from_base64_secret(key)
+            .map_err(|_| format!("Signing key {} must be base64 encoded", id))?;
+        decoding_keys.insert(id.clone(), decoding_key);
+    }
+    let active_key = settings
+        .keys
+        .get(&settings.active_key_id)
+        .ok_or_else(|| format!("Active signing key {} isn't set", settings.active_key_id))?;
+    let encoding_key = EncodingKey::from_base64_secret(active_key).map_err(|_| {
+        format!(
+            "Signing key {} must be base64 encoded",
+            settings.active_key_id
+        )
+    })?;
+
+    let mut key_ids: Vec<String> = decoding_keys.keys().cloned().collect();
+    key_ids.sort();
+    *SIGNING_KEYS.write().expect("Signing keys lock poisoned") = Some(Arc::new(SigningKeys {
+        active_key_id: settings.active_key_id.clone(),
+        encoding_key,
+        decoding_keys,
+    }));
+    info!(
+        "Signing keys set, active key: {}, accepted keys: {:?}",
+        settings.active_key_id, key_ids
+    );
+    Ok(SigningKeyInfo {
+        active_key_id: settings.active_key_id.clone(),
+        key_ids,
+    })
+}
+
+// Reads the signing keys from the configuration again, the current keys are kept if they are invalid
+pub fn reload_signing_keys() -> Result<SigningKeyInfo, ServerError> {
+    let config = get_configuration().map_err(|err| {
+        error!("Failed to read configuration: {}", err);
+        ServerError::SigningKeyReloadFailed
+    })?;
+    set_signing_keys(&config.signing_keys).map_err(|err| {
+        error!("Failed to reload signing keys: {}", err);
+        ServerError::SigningKeyReloadFailed
+    })
+}
+
+fn signing_keys() -> Arc<SigningKeys> {
+    SIGNING_KEYS
+        .read()
+        .expect("Signing keys lock poisoned")
+        .clone()
+        .expect("Signing keys haven't been set")
 }
 
 fn decode_token(token: &str) -> Result<UserInfo, ServerError> {
@@ -81,12 +259,19 @@ fn decode_token(token: &str) -> Result<UserInfo, ServerError> {
         ..Validation::default()
     };
 
-    let decoded_token = decode::<Claims>(
-        token,
-        &DECODING_KEY.get().expect("Decoding key hasn't been set"),
-        &validation,
-    )
-    .map_err(|err| {
+    let header = decode_header(token).map_err(|err| {
+        warn!("Token decoding error: {}", err);
+        ServerError::InvalidToken("Invalid token".to_string())
+    })?;
+    let keys = signing_keys();
+    // Tokens issued before key ids existed were signed with the active key
+    let key_id = header.kid.as_ref().unwrap_or(&keys.active_key_id);
+    let decoding_key = keys.decoding_keys.get(key_id).ok_or_else(|| {
+        warn!("Token signed with unknown key: {}", key_id);
+        ServerError::InvalidToken("Invalid token".to_string())
+    })?;
+
+    let decoded_token = decode::<Claims>(token, decoding_key, &validation).map_err(|err| {
         warn!("Token decoding error: {}", err);
         ServerError::InvalidToken("Invalid token".to_string())
     })?;
@@ -98,7 +283,11 @@ fn decode_token(token: &str) -> Result<UserInfo, ServerError> {
         );
         ServerError::InvalidToken("Invalid token content".to_string())
     })?;
-    Ok(UserInfo { id: uuid })
+    Ok(UserInfo {
+        id: uuid,
+        role: decoded_token.claims.role,
+        session_id: decoded_token.claims.sid,
+    })
 }
 
 // Authenticate the request given an auth token
@@ -108,27 +297,250 @@ pub async fn authenticate_request(
     credentials: BearerAuth,
 ) -> Result<ServiceRequest, Error> {
     let token = credentials.token();
-    let uuid = decode_token(token)?.id;
-    if pool.get_user(uuid).await.is_some() {
-        Ok(req)
-    } else {
-        Err(ServerError::InvalidToken("User no longer exists".to_string()).into())
+    if token.starts_with(API_KEY_PREFIX) {
+        return match pool
+            .find_active_api_key(&hash_token(token))
+            .await
+            .map_err(ServerError::from)?
+        {
+            Some(api_key) => {
+                req.extensions_mut().insert(api_key);
+                Ok(req)
+            }
+            None => Err(ServerError::InvalidToken("Invalid api key".to_string()).into()),
+        };
+    }
+    let user_info = decode_token(token)?;
+    match pool.get_user(user_info.id).await {
+        // Tokens issued before a role change are rejected, the user has to log in again
+        Some(user) if user.role != user_info.role => {
+            Err(ServerError::InvalidToken("User role has changed".to_string()).into())
+        }
+        Some(_) => {
+            if pool
+                .is_session_active(user_info.session_id)
+                .await
+                .map_err(ServerError::from)?
+            {
+                Ok(req)
+            } else {
+                Err(ServerError::InvalidToken("Session has been revoked".to_string()).into())
+            }
+        }
+        None => Err(ServerError::InvalidToken("User no longer exists".to_string()).into()),
+    }
+}
+
+// Admins and the owner of the tournament are allowed to do everything,
+// other users need one of the allowed roles in the tournament
+pub async fn authorize_tournament_action(
+    storage: &PgPool,
+    tournament_id: i32,
+    user: &UserInfo,
+    allowed_roles: &[TournamentRole],
+) -> Result<(), ServerError> {
+    if storage.get_tournament(tournament_id).await?.is_none() {
+        return Err(ServerError::TournamentNotFound);
+    }
+    if user.is_admin() || storage.get_tournament_owner(tournament_id).await? == Some(user.id) {
+        return Ok(());
+    }
+    match storage.get_tournament_role(tournament_id, user.id).await? {
+        Some(role) if allowed_roles.contains(&role) => Ok(()),
+        role => {
+            warn!(
+                "User {} with role {:?} isn't allowed to change tournament {}",
+                user.id, role, tournament_id
+            );
+            Err(ServerError::MissingTournamentRole)
+        }
     }
 }
 
-// Authenticate an user and return a JWT token if the credentials are valid
+// Same as authorize_tournament_action for the tournament the match is part of
+pub async fn authorize_match_action(
+    storage: &PgPool,
+    match_id: i64,
+    user: &UserInfo,
+    allowed_roles: &[TournamentRole],
+) -> Result<(), ServerError> {
+    match storage.get_match(match_id).await? {
+        Some(match_data) => {
+            authorize_tournament_action(storage, match_data.tournament_id, user, allowed_roles)
+                .await
+        }
+        None => Err(ServerError::MatchNotFound),
+    }
+}
+
+// Users are authorized by their tournament roles, API keys must belong to the
+// tournament of the match and have the scope of the action
+pub async fn authorize_caller_match_action(
+    storage: &PgPool,
+    match_id: i64,
+    caller: &Caller,
+    allowed_roles: &[TournamentRole],
+    scope: ApiKeyScope,
+) -> Result<(), ServerError> {
+    match caller {
+        Caller::User(user_info) => {
+            authorize_match_action(storage, match_id, user_info, allowed_roles).await
+        }
+        Caller::ApiKey(api_key) => {
+            let match_data = storage
+                .get_match(match_id)
+                .await?
+                .ok_or(ServerError::MatchNotFound)?;
+            if match_data.tournament_id != api_key.tournament_id || !api_key.scopes.contains(&scope)
+            {
+                warn!(
+                    "Api key {} isn't allowed to {:?} in match {}",
+                    api_key.id, scope, match_id
+                );
+                return Err(ServerError::MissingApiKeyScope);
+            }
+            Ok(())
+        }
+    }
+}
+
+// Who is recorded as having checked in a player when the caller registers them
+pub async fn get_registrar(storage: &PgPool, caller: &Caller) -> Result<Registrar, ServerError> {
+    match caller {
+        Caller::User(user_info) => {
+            let user_row = storage
+                .get_user(user_info.id)
+                .await
+                .ok_or(ServerError::UserNotFound)?;
+            Ok(Registrar::User {
+                id: user_row.id,
+                email: user_row.email,
+            })
+        }
+        Caller::ApiKey(api_key) => Ok(Registrar::ApiKey {
+            id: api_key.id,
+            name: api_key.name.clone(),
+        }),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiKeyPayload {
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+}
+
+// The key itself is only returned here, only the hash of it is stored
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewApiKey {
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+pub async fn create_api_key(
+    storage: &PgPool,
+    tournament_id: i32,
+    user: &UserInfo,
+    payload: ApiKeyPayload,
+) -> Result<NewApiKey, ServerError> {
+    authorize_tournament_action(storage, tournament_id, user, TournamentRole::MANAGE).await?;
+    if payload.name.trim().is_empty() || payload.scopes.is_empty() {
+        return Err(ServerError::InvalidApiKey);
+    }
+    let key = generate_token().map_err(|err| {
+        error!("Failed to generate api key: {}", err);
+        ServerError::InvalidApiKey
+    })?;
+    let key = format!("{}{}", API_KEY_PREFIX, key);
+    let api_key = storage
+        .insert_api_key(
+            tournament_id,
+            payload.name.trim(),
+            &hash_token(&key),
+            &payload.scopes,
+            user.id,
+        )
+        .await?;
+    info!(
+        "Created api key {} for tournament: {}",
+        api_key.id, tournament_id
+    );
+    Ok(NewApiKey { api_key, key })
+}
+
+// Authenticate an user and start a new session if the credentials are valid.
+// Failed attempts are counted per email and per IP and lock out further attempts
+// once there are too many of them.
 pub async fn login_user(
     storage: &PgPool,
     email: &str,
     password: &str,
-) -> Result<String, ServerError> {
+    ip: &str,
+    settings: &LoginSettings,
+) -> Result<AuthTokens, ServerError> {
     // Is this really needed? Gets rid of unnecessary db call at least
     if !EMAIL_REGEX.is_match(email) {
         return Err(ServerError::InvalidEmail);
     }
+    let email_identifier = email.to_lowercase();
+    let attempt_keys = [
+        (
+            LoginAttemptKind::Email,
+            email_identifier.as_str(),
+            settings.max_failed_attempts_per_email,
+        ),
+        (
+            LoginAttemptKind::Ip,
+            ip,
+            settings.max_failed_attempts_per_ip,
+        ),
+    ];
+    for (kind, identifier, _) in attempt_keys.iter() {
+        if let Some(locked_until) = storage.get_login_lockout(*kind, identifier).await? {
+            let retry_after = (locked_until - Local::now().naive_local()).num_seconds();
+            warn!("Login attempt while locked out by {:?}", kind);
+            return Err(ServerError::TooManyLoginAttempts(retry_after.max(1) as u64));
+        }
+    }
+
+    match check_credentials(storage, email, password).await {
+        Ok(user_row) => {
+            // The failures of the user are taken back from the IP as well, so others behind
+            // the same address aren't locked out by them. The rest of the IP failures are
+            // kept, otherwise logging in to an account of your own would reset the count.
+            let failures = storage
+                .clear_failed_logins(LoginAttemptKind::Email, &email_identifier)
+                .await?;
+            if failures > 0 {
+                storage
+                    .forgive_failed_logins(LoginAttemptKind::Ip, ip, failures)
+                    .await?;
+            }
+            if !user_row.verified {
+                return Err(ServerError::EmailNotVerified);
+            }
+            let session_id = storage.insert_session(user_row.id).await?;
+            issue_tokens(storage, user_row.id, user_row.role, session_id).await
+        }
+        Err(error @ ServerError::InvalidEmail) | Err(error @ ServerError::InvalidPassword) => {
+            for (kind, identifier, max_failed_attempts) in attempt_keys.iter() {
+                record_failed_login(storage, *kind, identifier, *max_failed_attempts, settings)
+                    .await?;
+            }
+            Err(error)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+async fn check_credentials(
+    storage: &PgPool,
+    email: &str,
+    password: &str,
+) -> Result<UserInfoRow, ServerError> {
     if let Some(user_row) = storage.find_user(email).await {
         // check password
-        let is_pw_correct = bcrypt::verify(&password, &user_row.password).map_err(|err| {
+        let is_pw_correct = bcrypt::verify(password, &user_row.password).map_err(|err| {
             error!("Failed to do password verification: {}", err);
             ServerError::InvalidPassword
         })?;
@@ -136,45 +548,314 @@ pub async fn login_user(
         if !is_pw_correct {
             return Err(ServerError::InvalidPassword);
         }
-        let current_unix_time = Local::now().timestamp() as usize;
-        // create token
-        let claims = Claims {
-            exp: current_unix_time + THREE_DAYS_SECONDS,
-            iat: current_unix_time,
-            sub: user_row.id.to_string(),
-        };
-
-        let token = encode(
-            &Header::default(),
-            &claims,
-            &ENCODING_KEY.get().expect("Encoding key hasn't been set"),
-        )
-        .map_err(|err| {
-            error!("Failed to encode JWT token: {}", err);
-            ServerError::LoginFailed
-        })?;
-        Ok(token)
+        Ok(user_row)
     } else {
         Err(ServerError::InvalidEmail)
     }
 }
 
+// The lockout starts at the threshold and doubles for every failure after it
+async fn record_failed_login(
+    storage: &PgPool,
+    kind: LoginAttemptKind,
+    identifier: &str,
+    max_failed_attempts: u32,
+    settings: &LoginSettings,
+) -> Result<(), ServerError> {
+    let now = Local::now().naive_local();
+    let window_start = now - Duration::seconds(settings.failure_window_seconds as i64);
+    let failures = storage
+        .insert_failed_login(kind, identifier, window_start)
+        .await? as u32;
+    if failures >= max_failed_attempts {
+        let doublings = (failures - max_failed_attempts).min(31);
+        let lockout_seconds = settings
+            .lockout_seconds
+            .saturating_mul(1 << doublings)
+            .min(settings.max_lockout_seconds);
+        warn!(
+            "Locking out logins by {:?} for {} seconds after {} failed attempts",
+            kind, lockout_seconds, failures
+        );
+        storage
+            .lock_login(
+                kind,
+                identifier,
+                now + Duration::seconds(lockout_seconds as i64),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+// Exchanges a refresh token for new tokens in the same session. A refresh token
+// that's used twice has probably been stolen so the whole session is revoked then.
+pub async fn refresh_session(
+    storage: &PgPool,
+    refresh_token: &str,
+) -> Result<AuthTokens, ServerError> {
+    let token_hash = hash_token(refresh_token);
+    let token = storage
+        .get_refresh_token(&token_hash)
+        .await?
+        .ok_or_else(|| ServerError::InvalidToken("Unknown refresh token".to_string()))?;
+    if token.session_revoked {
+        return Err(ServerError::InvalidToken(
+            "Session has been revoked".to_string(),
+        ));
+    }
+    if token.used_at.is_some() || !storage.use_refresh_token(&token_hash).await? {
+        warn!(
+            "Refresh token reused, revoking session: {}",
+            token.session_id
+        );
+        storage.revoke_session(token.session_id).await?;
+        return Err(ServerError::InvalidToken(
+            "Refresh token has already been used".to_string(),
+        ));
+    }
+    if token.expires_at < Local::now().naive_local() {
+        return Err(ServerError::InvalidToken(
+            "Refresh token has expired".to_string(),
+        ));
+    }
+    // The role is read again so changes are picked up without logging in
+    let user = storage
+        .get_user(token.user_id)
+        .await
+        .ok_or_else(|| ServerError::InvalidToken("User no longer exists".to_string()))?;
+    issue_tokens(storage, user.id, user.role, token.session_id).await
+}
+
+// Revokes the session the refresh token belongs to
+pub async fn logout_session(storage: &PgPool, refresh_token: &str) -> Result<(), ServerError> {
+    let token = storage
+        .get_refresh_token(&hash_token(refresh_token))
+        .await?
+        .ok_or_else(|| ServerError::InvalidToken("Unknown refresh token".to_string()))?;
+    storage.revoke_session(token.session_id).await?;
+    Ok(())
+}
+
+async fn issue_tokens(
+    storage: &PgPool,
+    user_id: Uuid,
+    role: UserRole,
+    session_id: Uuid,
+) -> Result<AuthTokens, ServerError> {
+    let current_unix_time = Local::now().timestamp() as usize;
+    let claims = Claims {
+        exp: current_unix_time + ACCESS_TOKEN_SECONDS,
+        iat: current_unix_time,
+        sub: user_id.to_string(),
+        role,
+        sid: session_id,
+    };
+    let keys = signing_keys();
+    let header = Header {
+        kid: Some(keys.active_key_id.clone()),
+        ..Header::default()
+    };
+    let access_token = encode(&header, &claims, &keys.encoding_key).map_err(|err| {
+        error!("Failed to encode JWT token: {}", err);
+        ServerError::LoginFailed
+    })?;
+
+    let refresh_token = generate_token().map_err(|err| {
+        error!("Failed to generate refresh token: {}", err);
+        ServerError::LoginFailed
+    })?;
+    let expires_at = Local::now().naive_local() + Duration::days(REFRESH_TOKEN_DAYS);
+    storage
+        .insert_refresh_token(session_id, &hash_token(&refresh_token), expires_at)
+        .await?;
+
+    Ok(AuthTokens {
+        access_token,
+        refresh_token,
+        expires_in: ACCESS_TOKEN_SECONDS,
+    })
+}
+
+pub(crate) fn generate_token() -> Result<String, ring::error::Unspecified> {
+    let mut random_bytes = [0_u8; 32];
+    SystemRandom::new().fill(&mut random_bytes)?;
+    Ok(base64::encode_config(random_bytes, base64::URL_SAFE_NO_PAD))
+}
+
+// The generated tokens are random enough to not need a salted hash
+fn hash_token(token: &str) -> String {
+    base64::encode(digest(&SHA256, token.as_bytes()))
+}
+
+// The account can't be used until the email has been verified through the link
+// that's sent to it, verify_url is the address of the verification endpoint
 pub async fn create_user(
     storage: &PgPool,
+    mailer: &dyn Mailer,
     email: &str,
     password: &str,
+    verify_url: &str,
 ) -> Result<Uuid, ServerError> {
     if !EMAIL_REGEX.is_match(email) {
         return Err(ServerError::InvalidEmail);
     }
 
-    if password.len() < 8 {
-        return Err(ServerError::InvalidPassword);
-    }
+    check_valid_password(password)?;
 
     if storage.find_user(email).await.is_some() {
         return Err(ServerError::AccountAlreadyExists(email.to_string()));
     }
     let id = storage.insert_user(email, password).await?;
+    // The account exists at this point so signing up again would fail, the user
+    // can have the email resent instead
+    if let Err(err) = send_verification_email(storage, mailer, id, email, verify_url).await {
+        error!("Failed to send verification email to user {}: {}", id, err);
+    }
     Ok(id)
 }
+
+// Unknown and already verified emails are silently ignored
+pub async fn resend_verification_email(
+    storage: &PgPool,
+    mailer: &dyn Mailer,
+    email: &str,
+    verify_url: &str,
+) -> Result<(), ServerError> {
+    if !EMAIL_REGEX.is_match(email) {
+        return Err(ServerError::InvalidEmail);
+    }
+    match storage.find_user(email).await {
+        Some(user_row) if !user_row.verified => {
+            send_verification_email(storage, mailer, user_row.id, email, verify_url).await
+        }
+        _ => {
+            info!("Verification email not resent, the account is unknown or already verified");
+            Ok(())
+        }
+    }
+}
+
+pub async fn verify_email(storage: &PgPool, token: &str) -> Result<(), ServerError> {
+    let user_id = storage
+        .verify_email(&hash_token(token))
+        .await?
+        .ok_or_else(|| {
+            ServerError::InvalidToken("Invalid or expired verification token".to_string())
+        })?;
+    info!("Verified email of user: {}", user_id);
+    Ok(())
+}
+
+async fn send_verification_email(
+    storage: &PgPool,
+    mailer: &dyn Mailer,
+    user_id: Uuid,
+    email: &str,
+    verify_url: &str,
+) -> Result<(), ServerError> {
+    let token = generate_token().map_err(|err| {
+        error!("Failed to generate email verification token: {}", err);
+        ServerError::EmailDeliveryFailed
+    })?;
+    let expires_at = Local::now().naive_local() + Duration::hours(EMAIL_VERIFICATION_HOURS);
+    storage
+        .insert_email_verification_token(user_id, &hash_token(&token), expires_at)
+        .await?;
+    mailer
+        .send(Email {
+            to: email.to_string(),
+            subject: "Verify your email".to_string(),
+            body: format!(
+                "Welcome to Tournament tracker!\n\n\
+                Open this link within {} hours to verify your email: {}?token={}",
+                EMAIL_VERIFICATION_HOURS, verify_url, token
+            ),
+        })
+        .await
+}
+
+fn check_valid_password(password: &str) -> Result<(), ServerError> {
+    if password.len() < 8 {
+        Err(ServerError::InvalidPassword)
+    } else {
+        Ok(())
+    }
+}
+
+pub async fn change_password(
+    storage: &PgPool,
+    user_id: Uuid,
+    old_password: &str,
+    new_password: &str,
+) -> Result<(), ServerError> {
+    let user_row = storage
+        .get_user(user_id)
+        .await
+        .ok_or(ServerError::UserNotFound)?;
+    let is_pw_correct = bcrypt::verify(old_password, &user_row.password).map_err(|err| {
+        error!("Failed to do password verification: {}", err);
+        ServerError::InvalidPassword
+    })?;
+    if !is_pw_correct {
+        return Err(ServerError::InvalidPassword);
+    }
+    check_valid_password(new_password)?;
+    storage.update_password(user_id, new_password).await
+}
+
+// Emails a single use reset token to the user. Unknown emails are silently
+// ignored to not reveal which emails have accounts.
+pub async fn request_password_reset(
+    storage: &PgPool,
+    mailer: &dyn Mailer,
+    email: &str,
+) -> Result<(), ServerError> {
+    if !EMAIL_REGEX.is_match(email) {
+        return Err(ServerError::InvalidEmail);
+    }
+    let user_row = match storage.find_user(email).await {
+        Some(user_row) => user_row,
+        None => {
+            info!("Password reset requested for unknown email");
+            return Ok(());
+        }
+    };
+    let token = generate_token().map_err(|err| {
+        error!("Failed to generate password reset token: {}", err);
+        ServerError::EmailDeliveryFailed
+    })?;
+    let expires_at = Local::now().naive_local() + Duration::minutes(PASSWORD_RESET_MINUTES);
+    storage
+        .insert_password_reset_token(user_row.id, &hash_token(&token), expires_at)
+        .await?;
+    mailer
+        .send(Email {
+            to: user_row.email,
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Someone asked to reset the password of your account.\n\n\
+                Use this code within {} minutes to choose a new password: {}\n\n\
+                If it wasn't you, you can ignore this email.",
+                PASSWORD_RESET_MINUTES, token
+            ),
+        })
+        .await
+}
+
+// Every session of the user is revoked since the old password may have leaked
+pub async fn reset_password(
+    storage: &PgPool,
+    token: &str,
+    new_password: &str,
+) -> Result<(), ServerError> {
+    check_valid_password(new_password)?;
+    let user_id = storage
+        .use_password_reset_token(&hash_token(token))
+        .await?
+        .ok_or_else(|| ServerError::InvalidToken("Invalid or expired reset token".to_string()))?;
+    storage.update_password(user_id, new_password).await?;
+    storage.revoke_user_sessions(user_id).await?;
+    info!("Password reset for user: {}", user_id);
+    Ok(())
+}
@@ -0,0 +1,318 @@
+use crate::{
+    endpoints::DrawPayload,
+    events::{publish_event, TournamentEvent},
+    stores::{
+        bracket_store::{lock_draw, Advancing, BracketMatch, BracketStore, Draw, MatchProgression},
+        match_store::Match,
+        player_store::PlayerStore,
+        tournament_store::TournamentStore,
+    },
+    ServerError,
+};
+use chrono::{Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashSet;
+use tracing::{error, info};
+
+// The kind of draw to generate for a class
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DrawFormat {
+    #[default]
+    SingleElimination,
+    // Players are knocked out after their second loss
+    DoubleElimination,
+    // Single elimination where the first round losers play a separate draw
+    Consolation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Slot {
+    Player(i64),
+    // The winner of the node with the given index
+    WinnerOf(usize),
+    // The loser of the node with the given index
+    LoserOf(usize),
+    Bye,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BracketNode {
+    draw: Draw,
+    round: i32,
+    slots: [Slot; 2],
+}
+
+// Standard seeding order, makes sure the top seeds meet as late as possible
+// ex: size 8 -> [1, 8, 4, 5, 2, 7, 3, 6]
+fn seeding_order(size: usize) -> Vec<usize> {
+    let mut order = vec![1];
+    while order.len() < size {
+        let next_size = order.len() * 2;
+        order = order
+            .iter()
+            .flat_map(|&seed| vec![seed, next_size + 1 - seed])
+            .collect();
+    }
+    order
+}
+
+// Pairs up the slots in order into a new round of nodes and returns
+// the slots for the winners of the round. A single slot is returned as is.
+fn add_round(nodes: &mut Vec<BracketNode>, slots: &[Slot], draw: Draw, round: i32) -> Vec<Slot> {
+    if slots.len() < 2 {
+        return slots.to_vec();
+    }
+    slots
+        .chunks(2)
+        .map(|pair| {
+            nodes.push(BracketNode {
+                draw,
+                round,
+                slots: [pair[0], pair.get(1).copied().unwrap_or(Slot::Bye)],
+            });
+            Slot::WinnerOf(nodes.len() - 1)
+        })
+        .collect()
+}
+
+// Adds rounds until a single slot remains which is returned
+fn add_knockout(nodes: &mut Vec<BracketNode>, mut slots: Vec<Slot>, draw: Draw) -> Slot {
+    let mut round = 1;
+    while slots.len() > 1 {
+        slots = add_round(nodes, &slots, draw, round);
+        round += 1;
+    }
+    slots.first().copied().unwrap_or(Slot::Bye)
+}
+
+// Builds the full single elimination bracket, the draw is padded with byes
+// up to the nearest power of two. Nodes are ordered by round and position in the draw.
+fn single_elimination(seeds: &[i64]) -> Vec<BracketNode> {
+    let size = seeds.len().next_power_of_two();
+    let seed_slot = |seed: usize| {
+        seeds
+            .get(seed - 1)
+            .map_or(Slot::Bye, |&player_id| Slot::Player(player_id))
+    };
+    let mut nodes = Vec::with_capacity(size);
+    let slots = seeding_order(size).into_iter().map(seed_slot).collect();
+    add_knockout(&mut nodes, slots, Draw::Main);
+    nodes
+}
+
+fn first_round_losers(nodes: &[BracketNode]) -> Vec<Slot> {
+    nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, node)| node.draw == Draw::Main && node.round == 1)
+        .map(|(index, _)| Slot::LoserOf(index))
+        .collect()
+}
+
+// The main draw followed by a separate draw for the losers of the first round,
+// losers from neighbouring matches meet in the first round of the consolation draw
+fn consolation(seeds: &[i64]) -> Vec<BracketNode> {
+    let mut nodes = single_elimination(seeds);
+    let losers = first_round_losers(&nodes);
+    add_knockout(&mut nodes, losers, Draw::Consolation);
+    nodes
+}
+
+// The main draw is the winners bracket. Losers of the first round meet each other in
+// the losers bracket, losers of later rounds drop down and meet the survivors there.
+// The winners of both brackets meet in a final that's part of the main draw.
+fn double_elimination(seeds: &[i64]) -> Vec<BracketNode> {
+    let mut nodes = single_elimination(seeds);
+    let main_rounds = nodes.iter().map(|node| node.round).max().unwrap_or(0);
+
+    let losers = first_round_losers(&nodes);
+    let mut round = 1;
+    let mut survivors = add_round(&mut nodes, &losers, Draw::Losers, round);
+    for main_round in 2..=main_rounds {
+        // Dropping players are placed in reverse order to avoid early rematches
+        let mut dropping: Vec<Slot> = (0..nodes.len())
+            .filter(|&index| nodes[index].draw == Draw::Main && nodes[index].round == main_round)
+            .map(Slot::LoserOf)
+            .collect();
+        dropping.reverse();
+        let pairs: Vec<Slot> = survivors
+            .iter()
+            .zip(dropping.iter())
+            .flat_map(|(&survivor, &dropped)| vec![survivor, dropped])
+            .collect();
+        round += 1;
+        survivors = add_round(&mut nodes, &pairs, Draw::Losers, round);
+        if survivors.len() > 1 {
+            round += 1;
+            survivors = add_round(&mut nodes, &survivors, Draw::Losers, round);
+        }
+    }
+
+    let main_final = nodes
+        .iter()
+        .rposition(|node| node.draw == Draw::Main)
+        .expect("The main draw has at least one match");
+    nodes.push(BracketNode {
+        draw: Draw::Main,
+        round: main_rounds + 1,
+        slots: [
+            Slot::WinnerOf(main_final),
+            survivors.first().copied().unwrap_or(Slot::Bye),
+        ],
+    });
+    nodes
+}
+
+// Nodes with a bye aren't played, the other participant advances directly instead.
+// The returned nodes are None for nodes that won't be played, the remaining
+// nodes only refer to other nodes that will be played.
+fn resolve_byes(nodes: &[BracketNode]) -> Vec<Option<BracketNode>> {
+    let mut resolved: Vec<Option<BracketNode>> = Vec::with_capacity(nodes.len());
+    // What advances from each node that isn't played
+    let mut advancing: Vec<Slot> = Vec::with_capacity(nodes.len());
+
+    for node in nodes {
+        let resolve_slot = |slot: Slot| match slot {
+            Slot::WinnerOf(index) if resolved[index].is_none() => advancing[index],
+            // Nobody loses a match that isn't played
+            Slot::LoserOf(index) if resolved[index].is_none() => Slot::Bye,
+            slot => slot,
+        };
+        let slots = [resolve_slot(node.slots[0]), resolve_slot(node.slots[1])];
+        match slots {
+            [Slot::Bye, other] | [other, Slot::Bye] => {
+                resolved.push(None);
+                advancing.push(other);
+            }
+            _ => {
+                resolved.push(Some(BracketNode { slots, ..*node }));
+                advancing.push(Slot::Bye);
+            }
+        }
+    }
+    resolved
+}
+
+async fn insert_bracket(
+    transaction: &mut Transaction<'_, Postgres>,
+    tournament_id: i32,
+    class: &str,
+    start_time: NaiveDateTime,
+    nodes: &[Option<BracketNode>],
+) -> Result<(), ServerError> {
+    let mut match_ids = vec![None; nodes.len()];
+    for (index, node) in nodes.iter().enumerate() {
+        if let Some(node) = node {
+            let player = |slot: Slot| match slot {
+                Slot::Player(player_id) => Some(player_id),
+                _ => None,
+            };
+            let match_data = Match {
+                id: 0,
+                player_one: player(node.slots[0]),
+                player_two: player(node.slots[1]),
+                tournament_id,
+                class: class.to_string(),
+                start_time,
+            };
+            let match_id = transaction
+                .insert_bracket_match(&match_data, node.round, node.draw)
+                .await?;
+            publish_event(
+                transaction,
+                tournament_id,
+                &TournamentEvent::MatchCreated {
+                    match_data: Match {
+                        id: match_id,
+                        ..match_data
+                    },
+                },
+            )
+            .await?;
+            match_ids[index] = Some(match_id);
+        }
+    }
+
+    for (node, to_match_id) in nodes.iter().zip(match_ids.iter()) {
+        if let (Some(node), Some(to_match_id)) = (node, to_match_id) {
+            for (slot_index, slot) in node.slots.iter().enumerate() {
+                let (index, advancing) = match slot {
+                    Slot::WinnerOf(index) => (index, Advancing::Winner),
+                    Slot::LoserOf(index) => (index, Advancing::Loser),
+                    _ => continue,
+                };
+                let from_match_id =
+                    match_ids[*index].expect("Resolved slots only refer to played matches");
+                let progression = MatchProgression {
+                    from_match_id,
+                    to_match_id: *to_match_id,
+                    slot: slot_index as i16 + 1,
+                    advancing,
+                };
+                transaction.insert_match_progression(&progression).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[tracing::instrument(name = "Generate draw", skip(storage))]
+pub async fn generate_draw(
+    storage: &PgPool,
+    tournament_id: i32,
+    payload: DrawPayload,
+) -> Result<Vec<BracketMatch>, ServerError> {
+    if storage.get_tournament(tournament_id).await?.is_none() {
+        return Err(ServerError::TournamentNotFound);
+    }
+    if payload.start_time < Local::now().naive_local() {
+        return Err(ServerError::InvalidStartTime);
+    }
+
+    let unique_players: HashSet<&i64> = payload.seeds.iter().collect();
+    if payload.seeds.len() < 2 || unique_players.len() != payload.seeds.len() {
+        return Err(ServerError::InvalidRooster);
+    }
+
+    for player_id in payload.seeds.iter() {
+        if storage.get_player(*player_id).await?.is_none() {
+            return Err(ServerError::PlayerNotFound);
+        }
+    }
+
+    let nodes = match payload.format {
+        DrawFormat::SingleElimination => single_elimination(&payload.seeds),
+        DrawFormat::DoubleElimination => double_elimination(&payload.seeds),
+        DrawFormat::Consolation => consolation(&payload.seeds),
+    };
+    let nodes = resolve_byes(&nodes);
+
+    let mut transaction = storage.begin().await?;
+    // Two requests for the same class would otherwise both find no draw and insert one each
+    lock_draw(&mut transaction, tournament_id, &payload.class).await?;
+    if transaction.has_draw(tournament_id, &payload.class).await? {
+        return Err(ServerError::DrawAlreadyExists(payload.class));
+    }
+    insert_bracket(
+        &mut transaction,
+        tournament_id,
+        &payload.class,
+        payload.start_time,
+        &nodes,
+    )
+    .await?;
+    transaction
+        .commit()
+        .await
+        .inspect_err(|_| error!("Transaction failed!"))?;
+    info!(
+        "Generated {:?} draw for class {} with {} players",
+        payload.format,
+        payload.class,
+        payload.seeds.len()
+    );
+
+    Ok(storage.get_draw(tournament_id, &payload.class).await?)
+}
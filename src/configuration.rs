@@ -2,21 +2,88 @@ use config::{Config, Environment, File};
 use serde::Deserialize;
 use serde_aux::field_attributes::deserialize_number_from_string;
 use sqlx::postgres::{PgConnectOptions, PgSslMode};
+use std::collections::HashMap;
+use std::net::IpAddr;
 use tracing::error;
 
+// Used as the id of application.private_key in the signing keys
+pub const DEFAULT_SIGNING_KEY_ID: &str = "default";
+
 #[derive(Deserialize)]
 pub struct ApplicationSettings {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub port: u16,
     pub host: String,
     // DO NOT PRINT THIS IN LOGS!!
+    // Kept for existing deployments, it's added to the signing keys as "default"
+    #[serde(default)]
     pub private_key: String,
 }
+
+#[derive(Clone, Deserialize)]
+pub struct SigningKeySettings {
+    // New tokens are signed with this key
+    pub active_key_id: String,
+    // DO NOT PRINT THIS IN LOGS!!
+    // Key id -> base64 encoded secret, tokens signed with any of them are accepted
+    // ex APP_SIGNING_KEYS__KEYS__2021_04=<secret>, the ids are always lowercased
+    #[serde(default)]
+    pub keys: HashMap<String, String>,
+}
+#[derive(Deserialize)]
+pub struct EmailSettings {
+    // ex: "Tournament tracker <noreply@example.com>"
+    pub sender: String,
+    // Emails are only kept in memory when no SMTP server is configured
+    #[serde(default)]
+    pub smtp: Option<SmtpSettings>,
+}
+
+#[derive(Deserialize)]
+pub struct SmtpSettings {
+    pub host: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub port: u16,
+    pub username: String,
+    // DO NOT PRINT THIS IN LOGS!!
+    pub password: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LoginSettings {
+    pub max_failed_attempts_per_email: u32,
+    pub max_failed_attempts_per_ip: u32,
+    pub lockout_seconds: u64,
+    pub max_lockout_seconds: u64,
+    pub failure_window_seconds: u64,
+    // The reverse proxies in front of the server, X-Forwarded-For is only read from them
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookSettings {
+    // How often the outbox is checked for deliveries that are due
+    pub poll_interval_milliseconds: u64,
+    pub request_timeout_seconds: u64,
+    // Doubled for every failed attempt
+    pub retry_backoff_seconds: u64,
+    pub max_attempts: i32,
+    // Hosts webhooks may be sent to even though they aren't public, only meant for
+    // local development and tests
+    #[serde(default)]
+    pub allowed_private_hosts: Vec<String>,
+}
+
 // DON'T DERIVE DEBUG TO AVOID ACCIDENTAL LOGGING!
 #[derive(Deserialize)]
 pub struct Settings {
     pub database: DatabaseSettings,
     pub application: ApplicationSettings,
+    pub signing_keys: SigningKeySettings,
+    pub email: EmailSettings,
+    pub login: LoginSettings,
+    pub webhooks: WebhookSettings,
 }
 
 #[derive(Debug, Deserialize)]
@@ -73,11 +140,26 @@ pub fn get_configuration() -> Result<Settings, config::ConfigError> {
     // ex APP_APPLICATION__PORT=1337
     settings.merge(Environment::with_prefix("app").separator("__"))?;
 
-    let settings: Settings = settings.try_into()?;
-    if settings.application.private_key.is_empty() {
-        error!("Private key is not properly set!");
+    let mut settings: Settings = settings.try_into()?;
+    settings.signing_keys.active_key_id = settings.signing_keys.active_key_id.to_lowercase();
+    if !settings.application.private_key.is_empty() {
+        let private_key = settings.application.private_key.clone();
+        settings
+            .signing_keys
+            .keys
+            .entry(DEFAULT_SIGNING_KEY_ID.to_string())
+            .or_insert(private_key);
+    }
+    if settings
+        .signing_keys
+        .keys
+        .get(&settings.signing_keys.active_key_id)
+        .filter(|key| !key.is_empty())
+        .is_none()
+    {
+        error!("Active signing key is not properly set!");
         Err(config::ConfigError::Message(
-            "Private key isn't set".to_string(),
+            "Active signing key isn't set".to_string(),
         ))
     } else {
         Ok(settings)
@@ -2,30 +2,66 @@
 #![allow(clippy::suspicious_else_formatting)]
 #![allow(unused_braces)]
 
-use crate::authentication::{create_user, login_user, UserInfo};
-use crate::match_operations::finish_match;
-use crate::stores::match_store::MatchResult;
-use crate::stores::user_store::UserStore;
+use crate::authentication::{
+    authorize_caller_match_action, authorize_match_action, authorize_tournament_action,
+    change_password, create_api_key, create_user, get_registrar, login_user, logout_session,
+    refresh_session, reload_signing_keys, request_password_reset, resend_verification_email,
+    reset_password, verify_email, AdminUser, ApiKeyPayload, Caller, UserInfo,
+};
+use crate::bracket_operations::{generate_draw, DrawFormat};
+use crate::configuration::{LoginSettings, WebhookSettings};
+use crate::events::TournamentEvents;
+use crate::group_operations::{
+    create_group, generate_group_matches, generate_knockout_draw, get_group_standings,
+};
+use crate::mailer::Mailer;
+use crate::match_operations::{
+    correct_match_result, create_match, finish_match, get_court_queue_matches, move_in_court_queue,
+    pull_from_court_queue, put_back_in_court_queue, update_match_scheduling,
+};
+use crate::stores::bracket_store::BracketStore;
+use crate::stores::group_store::{GroupStore, TiebreakRule};
+use crate::stores::match_store::{MatchResult, MatchScheduling};
+use crate::stores::session_store::SessionStore;
+use crate::stores::user_store::{UserRole, UserStore};
+use crate::tournament_operations::{
+    check_valid_dates, delete_tournament, grant_tournament_role, revoke_tournament_role,
+    update_tournament, RolePayload, TournamentPatch,
+};
 use crate::{
     match_operations::register_player_to_match,
     stores::{
-        court_store::{CourtStore, TournamentCourtAllocation},
+        api_key_store::{ApiKeyScope, ApiKeyStore},
+        audit_log_store::{insert_audit_log, AuditLogFilter, AuditLogRecord, AuditLogStore},
+        court_store::{lock_court_queue, CourtQueueEntry, CourtStore, TournamentCourtAllocation},
         match_store::{Match, MatchStore},
         player_store::{Player, PlayerStore},
-        tournament_store::{Tournament, TournamentStore},
+        team_store::{Team, TeamStore},
+        tournament_role_store::{TournamentRole, TournamentRoleStore},
+        tournament_store::{MatchFormat, Tournament, TournamentStore},
+        webhook_store::WebhookStore,
     },
+    webhooks::{create_webhook, WebhookPayload},
     ServerError,
 };
 use actix_web::{
-    delete, get, post,
+    delete, get, http, patch, post, put,
+    rt::time::delay_for,
     web::Path,
-    web::{Data, Form, Json},
-    HttpResponse, Responder,
+    web::{Bytes, Data, Form, Json, Query},
+    HttpRequest, HttpResponse, Responder,
 };
-use chrono::Local;
+use chrono::{Local, NaiveDateTime};
+use futures::future::ready;
+use futures::stream::{self, StreamExt};
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
-use tracing::info;
+use serde_json::json;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+use uuid::Uuid;
 
 #[get("/health_check")]
 pub async fn health_check() -> HttpResponse {
@@ -39,11 +75,41 @@ pub struct CredentialsPayload {
     pub password: String,
 }
 
-#[tracing::instrument(name = "User login", skip(db, payload))]
+// The IP address of the client. X-Forwarded-For is only used when the request comes from
+// a trusted proxy, otherwise clients could pick the address they are locked out by.
+fn client_ip(req: &HttpRequest, trusted_proxies: &[IpAddr]) -> String {
+    let peer_ip = match req.peer_addr() {
+        Some(addr) => addr.ip(),
+        None => return "unknown".to_string(),
+    };
+    if !trusted_proxies.contains(&peer_ip) {
+        return peer_ip.to_string();
+    }
+    // Every proxy appends the address it got the request from so the client is the
+    // last one that isn't a trusted proxy, anything before it may be made up
+    let forwarded_for: Vec<&str> = req
+        .headers()
+        .get_all("X-Forwarded-For")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+    for addr in forwarded_for.into_iter().rev() {
+        match addr.trim().parse::<IpAddr>() {
+            Ok(ip) if trusted_proxies.contains(&ip) => continue,
+            Ok(ip) => return ip.to_string(),
+            Err(_) => break,
+        }
+    }
+    peer_ip.to_string()
+}
+
+#[tracing::instrument(name = "User login", skip(db, payload, settings, req))]
 #[post("/login")]
 pub async fn login(
     payload: Json<CredentialsPayload>,
     db: Data<PgPool>,
+    settings: Data<LoginSettings>,
+    req: HttpRequest,
 ) -> Result<impl Responder, ServerError> {
     if payload.email.is_empty() {
         return Err(ServerError::InvalidEmail);
@@ -52,15 +118,71 @@ pub async fn login(
         return Err(ServerError::InvalidPassword);
     }
     info!("Attempting login for user: {}", payload.email);
-    let token = login_user(&db, &payload.email, &payload.password).await?;
-    Ok(HttpResponse::Ok().body(token))
+    let tokens = login_user(
+        &db,
+        &payload.email,
+        &payload.password,
+        &client_ip(&req, &settings.trusted_proxies),
+        &settings,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshTokenPayload {
+    pub refresh_token: String,
+}
+
+#[tracing::instrument(name = "Refresh session", skip(db, payload))]
+#[post("/refresh")]
+pub async fn refresh(
+    payload: Json<RefreshTokenPayload>,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    let tokens = refresh_session(&db, &payload.refresh_token).await?;
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+#[tracing::instrument(name = "User logout", skip(db, payload))]
+#[post("/logout")]
+pub async fn logout(
+    payload: Json<RefreshTokenPayload>,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    logout_session(&db, &payload.refresh_token).await?;
+    Ok(HttpResponse::Ok())
 }
 
-#[tracing::instrument(name = "Create new user", skip(db, payload))]
+// Logs out all devices of the user, including the current one
+#[tracing::instrument(name = "User logout of all sessions", skip(db))]
+#[post("/logout_all")]
+pub async fn logout_all(
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    let revoked = db.revoke_user_sessions(user_info.id).await?;
+    info!("Revoked {} sessions of user: {}", revoked, user_info.id);
+    Ok(HttpResponse::Ok())
+}
+
+// The verification link points back to the server the request was sent to
+fn verify_url(req: &HttpRequest) -> String {
+    let connection_info = req.connection_info();
+    format!(
+        "{}://{}/user/verify",
+        connection_info.scheme(),
+        connection_info.host()
+    )
+}
+
+#[tracing::instrument(name = "Create new user", skip(db, payload, mailer, req))]
 #[post("/user")]
 pub async fn create_new_user(
     payload: Json<CredentialsPayload>,
     db: Data<PgPool>,
+    mailer: Data<Arc<dyn Mailer>>,
+    req: HttpRequest,
 ) -> Result<impl Responder, ServerError> {
     if payload.email.is_empty() {
         return Err(ServerError::InvalidEmail);
@@ -69,35 +191,209 @@ pub async fn create_new_user(
         return Err(ServerError::InvalidPassword);
     }
     info!("Attempting user registration for email: {}", payload.email);
-    let id = create_user(&db, &payload.email, &payload.password).await?;
+    let id = create_user(
+        &db,
+        mailer.as_ref().as_ref(),
+        &payload.email,
+        &payload.password,
+        &verify_url(&req),
+    )
+    .await?;
     info!("Created user: {} for email: {}", id, &payload.email);
     Ok(HttpResponse::Ok())
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyEmailQuery {
+    pub token: String,
+}
+
+#[tracing::instrument(name = "Verify email", skip(db, query))]
+#[get("/user/verify")]
+pub async fn verify_email_endpoint(
+    query: Query<VerifyEmailQuery>,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    verify_email(&db, &query.token).await?;
+    Ok(HttpResponse::Ok().body("Your email has been verified, you can now log in"))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailPayload {
+    pub email: String,
+}
+
+#[tracing::instrument(name = "Resend verification email", skip(db, mailer, req))]
+#[post("/user/verify/resend")]
+pub async fn resend_verification_email_endpoint(
+    payload: Json<EmailPayload>,
+    db: Data<PgPool>,
+    mailer: Data<Arc<dyn Mailer>>,
+    req: HttpRequest,
+) -> Result<impl Responder, ServerError> {
+    resend_verification_email(
+        &db,
+        mailer.as_ref().as_ref(),
+        &payload.email,
+        &verify_url(&req),
+    )
+    .await?;
+    Ok(HttpResponse::Ok())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangePasswordPayload {
+    pub old_password: String,
+    pub new_password: String,
+}
+
+#[tracing::instrument(name = "Change password", skip(db, payload))]
+#[put("/password")]
+pub async fn change_password_endpoint(
+    payload: Json<ChangePasswordPayload>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    change_password(
+        &db,
+        user_info.id,
+        &payload.old_password,
+        &payload.new_password,
+    )
+    .await?;
+    info!("Changed password of user: {}", user_info.id);
+    Ok(HttpResponse::Ok())
+}
+
+#[tracing::instrument(name = "Forgot password", skip(db, mailer))]
+#[post("/password/forgot")]
+pub async fn forgot_password(
+    payload: Json<EmailPayload>,
+    db: Data<PgPool>,
+    mailer: Data<Arc<dyn Mailer>>,
+) -> Result<impl Responder, ServerError> {
+    request_password_reset(&db, mailer.as_ref().as_ref(), &payload.email).await?;
+    Ok(HttpResponse::Ok())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResetPasswordPayload {
+    pub token: String,
+    pub new_password: String,
+}
+
+#[tracing::instrument(name = "Reset password", skip(db, payload))]
+#[post("/password/reset")]
+pub async fn reset_password_endpoint(
+    payload: Json<ResetPasswordPayload>,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    reset_password(&db, &payload.token, &payload.new_password).await?;
+    Ok(HttpResponse::Ok())
+}
+
 #[tracing::instrument(name = "Delete user", skip(db))]
 #[delete("/user")]
 pub async fn delete_user(
     user_info: UserInfo,
     db: Data<PgPool>,
 ) -> Result<impl Responder, ServerError> {
-    db.delete_user(user_info.id).await?;
+    let user = db
+        .get_user(user_info.id)
+        .await
+        .ok_or(ServerError::UserNotFound)?;
+    let mut transaction = db.begin().await?;
+    crate::stores::user_store::delete_user(&mut transaction, user_info.id).await?;
+    insert_audit_log(
+        &mut transaction,
+        AuditLogRecord {
+            user_id: Some(user_info.id),
+            endpoint: "delete_user",
+            before: Some(json!({ "id": user.id, "email": user.email, "role": user.role })),
+            ..Default::default()
+        },
+    )
+    .await?;
+    transaction
+        .commit()
+        .await
+        .inspect_err(|_| error!("Transaction failed!"))?;
     Ok(HttpResponse::Ok())
 }
 
+// Admin endpoints
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserRolePayload {
+    pub role: UserRole,
+}
+
+#[tracing::instrument(name = "Get users", skip(db))]
+#[get("/admin/users")]
+pub async fn get_users(admin: AdminUser, db: Data<PgPool>) -> Result<impl Responder, ServerError> {
+    let users = db.get_users().await?;
+    Ok(HttpResponse::Ok().json(users))
+}
+
+#[tracing::instrument(name = "Set user role", skip(db))]
+#[put("/admin/users/{id}/role")]
+pub async fn set_user_role_endpoint(
+    id: Path<Uuid>,
+    payload: Json<UserRolePayload>,
+    admin: AdminUser,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    if !db.set_user_role(*id, payload.role).await? {
+        return Err(ServerError::UserNotFound);
+    }
+    info!(
+        "User {} changed the role of user {} to {:?}",
+        admin.0.id, *id, payload.role
+    );
+    Ok(HttpResponse::Ok())
+}
+
+#[tracing::instrument(name = "Reload signing keys")]
+#[post("/admin/signing_keys/reload")]
+pub async fn reload_signing_keys_endpoint(admin: AdminUser) -> Result<impl Responder, ServerError> {
+    let key_info = reload_signing_keys()?;
+    info!("User {} reloaded the signing keys", admin.0.id);
+    Ok(HttpResponse::Ok().json(key_info))
+}
+
 // Tournament endpoints
 #[tracing::instrument(name = "Insert tournament", skip(db))]
 #[post("/tournaments")]
 pub async fn insert_tournament(
     tournament: Json<Tournament>,
+    user_info: UserInfo,
     db: Data<PgPool>,
 ) -> Result<impl Responder, ServerError> {
-    if tournament.start_date > tournament.end_date
-        || tournament.start_date < Local::today().naive_local()
-    {
-        return Err(ServerError::InvalidDate);
-    }
+    check_valid_dates(tournament.start_date, tournament.end_date)?;
 
-    let id = db.insert_tournament(tournament.into_inner()).await?;
+    let mut tournament = tournament.into_inner();
+    let mut transaction = db.begin().await?;
+    let id = crate::stores::tournament_store::insert_tournament(
+        &mut transaction,
+        tournament.clone(),
+        user_info.id,
+    )
+    .await?;
+    tournament.id = id;
+    insert_audit_log(
+        &mut transaction,
+        AuditLogRecord {
+            user_id: Some(user_info.id),
+            endpoint: "insert_tournament",
+            tournament_id: Some(id),
+            after: serde_json::to_value(&tournament).ok(),
+            ..Default::default()
+        },
+    )
+    .await?;
+    transaction
+        .commit()
+        .await
+        .inspect_err(|_| error!("Transaction failed!"))?;
     Ok(HttpResponse::Ok().body(id.to_string()))
 }
 
@@ -108,6 +404,62 @@ pub async fn get_tournaments(db: Data<PgPool>) -> Result<impl Responder, ServerE
     Ok(HttpResponse::Ok().json(tournaments))
 }
 
+#[tracing::instrument(name = "Get tournament", skip(db))]
+#[get("/tournaments/{id}")]
+pub async fn get_tournament(
+    id: Path<i32>,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    if let Some(tournament) = db.get_tournament(*id).await? {
+        Ok(HttpResponse::Ok().json(tournament))
+    } else {
+        Err(ServerError::TournamentNotFound)
+    }
+}
+
+#[tracing::instrument(name = "Update tournament", skip(db))]
+#[put("/tournaments/{id}")]
+pub async fn update_tournament_endpoint(
+    id: Path<i32>,
+    tournament: Json<Tournament>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    authorize_tournament_action(&db, *id, &user_info, TournamentRole::MANAGE).await?;
+    let tournament = tournament.into_inner();
+    let patch = TournamentPatch {
+        name: Some(tournament.name),
+        start_date: Some(tournament.start_date),
+        end_date: Some(tournament.end_date),
+    };
+    let tournament = update_tournament(&db, *id, patch).await?;
+    Ok(HttpResponse::Ok().json(tournament))
+}
+
+#[tracing::instrument(name = "Patch tournament", skip(db))]
+#[patch("/tournaments/{id}")]
+pub async fn patch_tournament(
+    id: Path<i32>,
+    patch: Json<TournamentPatch>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    authorize_tournament_action(&db, *id, &user_info, TournamentRole::MANAGE).await?;
+    let tournament = update_tournament(&db, *id, patch.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(tournament))
+}
+
+#[tracing::instrument(name = "Delete tournament", skip(db))]
+#[delete("/tournaments/{id}")]
+pub async fn delete_tournament_endpoint(
+    id: Path<i32>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    delete_tournament(&db, *id, &user_info).await?;
+    Ok(HttpResponse::Ok())
+}
+
 #[tracing::instrument(name = "Get tournament matches", skip(db))]
 #[get("/tournaments/{id}/matches")]
 pub async fn get_tournament_matches(
@@ -119,9 +471,88 @@ pub async fn get_tournament_matches(
     Ok(HttpResponse::Ok().json(tournaments))
 }
 
+const EVENT_HEARTBEAT_SECONDS: u64 = 15;
+
+// Server-Sent Events stream of everything that happens in the tournament
+#[tracing::instrument(name = "Get tournament events", skip(db, events))]
+#[get("/tournaments/{id}/events")]
+pub async fn get_tournament_events(
+    id: Path<i32>,
+    db: Data<PgPool>,
+    events: Data<TournamentEvents>,
+) -> Result<HttpResponse, ServerError> {
+    if db.get_tournament(*id).await?.is_none() {
+        return Err(ServerError::TournamentNotFound);
+    }
+    let events = events
+        .subscribe(*id)
+        .map(|event| format!("data: {}\n\n", event));
+    // Comments are ignored by the clients, writing them makes disconnected clients noticed
+    let heartbeat = stream::unfold((), |_| async {
+        delay_for(Duration::from_secs(EVENT_HEARTBEAT_SECONDS)).await;
+        Some((": heartbeat\n\n".to_string(), ()))
+    });
+    let body = stream::once(ready(": connected\n\n".to_string()))
+        .chain(stream::select(events, heartbeat))
+        .map(|message| Ok::<_, actix_web::Error>(Bytes::from(message)))
+        .boxed_local();
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header(http::header::CACHE_CONTROL, "no-cache")
+        .streaming(body))
+}
+
+#[tracing::instrument(name = "Get match format", skip(db))]
+#[get("/tournaments/{id}/format")]
+pub async fn get_match_format(
+    id: Path<i32>,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    if let Some(match_format) = db.get_match_format(*id).await? {
+        Ok(HttpResponse::Ok().json(match_format))
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+#[tracing::instrument(name = "Update match format", skip(db))]
+#[put("/tournaments/{id}/format")]
+pub async fn update_match_format(
+    id: Path<i32>,
+    match_format: Json<MatchFormat>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    authorize_tournament_action(&db, *id, &user_info, TournamentRole::MANAGE).await?;
+    if match_format.best_of != 3 && match_format.best_of != 5 {
+        return Err(ServerError::InvalidMatchFormat);
+    }
+    if db.update_match_format(*id, &match_format).await? {
+        Ok(HttpResponse::Ok())
+    } else {
+        Err(ServerError::TournamentNotFound)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CourtForm {
     pub name: String,
+    #[serde(default)]
+    pub surface: Option<String>,
+    #[serde(default)]
+    pub indoor: bool,
+    #[serde(default)]
+    pub lighting: bool,
+}
+
+#[tracing::instrument(name = "Get tournament courts", skip(db))]
+#[get("/tournaments/{id}/courts")]
+pub async fn get_courts(id: Path<i32>, db: Data<PgPool>) -> Result<impl Responder, ServerError> {
+    db.get_tournament(*id)
+        .await?
+        .ok_or(ServerError::TournamentNotFound)?;
+    let courts = db.get_courts(*id).await?;
+    Ok(HttpResponse::Ok().json(courts))
 }
 
 #[tracing::instrument(name = "Add court to tournament", skip(db))]
@@ -129,18 +560,499 @@ pub struct CourtForm {
 pub async fn add_court_to_tournament(
     id: Path<i32>,
     court_form: Form<CourtForm>,
+    user_info: UserInfo,
     db: Data<PgPool>,
 ) -> Result<impl Responder, ServerError> {
+    authorize_tournament_action(&db, *id, &user_info, TournamentRole::MANAGE).await?;
+    let court_form = court_form.into_inner();
     let court_allocation = TournamentCourtAllocation {
-        court_name: court_form.into_inner().name,
+        court_name: court_form.name,
         tournament_id: *id,
         match_id: None,
+        surface: court_form.surface,
+        indoor: court_form.indoor,
+        lighting: court_form.lighting,
+    };
+    let after = serde_json::to_value(&court_allocation).ok();
+    let mut transaction = db.begin().await?;
+    (&mut transaction)
+        .insert_tournament_court_allocation(court_allocation)
+        .await?;
+    insert_audit_log(
+        &mut transaction,
+        AuditLogRecord {
+            user_id: Some(user_info.id),
+            endpoint: "add_court_to_tournament",
+            tournament_id: Some(*id),
+            after,
+            ..Default::default()
+        },
+    )
+    .await?;
+    transaction
+        .commit()
+        .await
+        .inspect_err(|_| error!("Transaction failed!"))?;
+    Ok(HttpResponse::Ok())
+}
+
+// Role endpoints
+#[tracing::instrument(name = "Grant tournament role", skip(db))]
+#[post("/tournaments/{id}/roles")]
+pub async fn grant_tournament_role_endpoint(
+    id: Path<i32>,
+    payload: Json<RolePayload>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    grant_tournament_role(&db, *id, &user_info, payload.into_inner()).await?;
+    Ok(HttpResponse::Ok())
+}
+
+#[tracing::instrument(name = "Get tournament roles", skip(db))]
+#[get("/tournaments/{id}/roles")]
+pub async fn get_tournament_roles(
+    id: Path<i32>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    authorize_tournament_action(&db, *id, &user_info, TournamentRole::MANAGE).await?;
+    let roles = db.get_tournament_roles(*id).await?;
+    Ok(HttpResponse::Ok().json(roles))
+}
+
+#[tracing::instrument(name = "Revoke tournament role", skip(db))]
+#[delete("/tournaments/{id}/roles/{user_id}")]
+pub async fn revoke_tournament_role_endpoint(
+    path: Path<(i32, Uuid)>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    let (tournament_id, user_id) = path.into_inner();
+    revoke_tournament_role(&db, tournament_id, &user_info, user_id).await?;
+    Ok(HttpResponse::Ok())
+}
+
+// Api key endpoints
+#[tracing::instrument(name = "Create api key", skip(db))]
+#[post("/tournaments/{id}/api_keys")]
+pub async fn create_api_key_endpoint(
+    id: Path<i32>,
+    payload: Json<ApiKeyPayload>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    let new_api_key = create_api_key(&db, *id, &user_info, payload.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(new_api_key))
+}
+
+#[tracing::instrument(name = "Get api keys", skip(db))]
+#[get("/tournaments/{id}/api_keys")]
+pub async fn get_api_keys(
+    id: Path<i32>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    authorize_tournament_action(&db, *id, &user_info, TournamentRole::MANAGE).await?;
+    let api_keys = db.get_api_keys(*id).await?;
+    Ok(HttpResponse::Ok().json(api_keys))
+}
+
+const AUDIT_LOG_PAGE_SIZE: i64 = 50;
+const AUDIT_LOG_MAX_PAGE_SIZE: i64 = 500;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AuditLogQuery {
+    pub user_id: Option<Uuid>,
+    pub endpoint: Option<String>,
+    pub match_id: Option<i64>,
+    pub player_id: Option<i64>,
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+    // Starts at 0
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+#[tracing::instrument(name = "Get audit log", skip(db))]
+#[get("/tournaments/{id}/audit_log")]
+pub async fn get_audit_log(
+    id: Path<i32>,
+    query: Query<AuditLogQuery>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    // Only the owner can see the log
+    authorize_tournament_action(&db, *id, &user_info, &[]).await?;
+    let query = query.into_inner();
+    let per_page = query
+        .per_page
+        .unwrap_or(AUDIT_LOG_PAGE_SIZE)
+        .clamp(1, AUDIT_LOG_MAX_PAGE_SIZE);
+    let page = query.page.unwrap_or(0).max(0);
+    let filter = AuditLogFilter {
+        user_id: query.user_id,
+        endpoint: query.endpoint,
+        match_id: query.match_id,
+        player_id: query.player_id,
+        from: query.from,
+        to: query.to,
     };
-    db.insert_tournament_court_allocation(court_allocation)
+    let entries = db
+        .get_audit_log(*id, &filter, per_page, page * per_page)
         .await?;
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+#[tracing::instrument(name = "Revoke api key", skip(db))]
+#[delete("/tournaments/{id}/api_keys/{key_id}")]
+pub async fn revoke_api_key(
+    path: Path<(i32, Uuid)>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    let (tournament_id, key_id) = path.into_inner();
+    authorize_tournament_action(&db, tournament_id, &user_info, TournamentRole::MANAGE).await?;
+    if !db.revoke_api_key(tournament_id, key_id).await? {
+        return Err(ServerError::ApiKeyNotFound);
+    }
+    info!(
+        "Revoked api key {} of tournament: {}",
+        key_id, tournament_id
+    );
+    Ok(HttpResponse::Ok())
+}
+
+// Webhook endpoints
+const WEBHOOK_DELIVERY_LIMIT: i64 = 50;
+
+#[tracing::instrument(name = "Create webhook", skip(db, settings))]
+#[post("/tournaments/{id}/webhooks")]
+pub async fn create_webhook_endpoint(
+    id: Path<i32>,
+    payload: Json<WebhookPayload>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+    settings: Data<WebhookSettings>,
+) -> Result<impl Responder, ServerError> {
+    let new_webhook = create_webhook(&db, &settings, *id, &user_info, payload.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(new_webhook))
+}
+
+#[tracing::instrument(name = "Get webhooks", skip(db))]
+#[get("/tournaments/{id}/webhooks")]
+pub async fn get_webhooks(
+    id: Path<i32>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    authorize_tournament_action(&db, *id, &user_info, TournamentRole::MANAGE).await?;
+    let webhooks = db.get_webhooks(*id).await?;
+    Ok(HttpResponse::Ok().json(webhooks))
+}
+
+#[tracing::instrument(name = "Delete webhook", skip(db))]
+#[delete("/tournaments/{id}/webhooks/{webhook_id}")]
+pub async fn delete_webhook(
+    path: Path<(i32, Uuid)>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    let (tournament_id, webhook_id) = path.into_inner();
+    authorize_tournament_action(&db, tournament_id, &user_info, TournamentRole::MANAGE).await?;
+    if !db.delete_webhook(tournament_id, webhook_id).await? {
+        return Err(ServerError::WebhookNotFound);
+    }
+    info!(
+        "Deleted webhook {} of tournament: {}",
+        webhook_id, tournament_id
+    );
     Ok(HttpResponse::Ok())
 }
 
+// The most recent deliveries of the webhook, newest first
+#[tracing::instrument(name = "Get webhook deliveries", skip(db))]
+#[get("/tournaments/{id}/webhooks/{webhook_id}/deliveries")]
+pub async fn get_webhook_deliveries(
+    path: Path<(i32, Uuid)>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    let (tournament_id, webhook_id) = path.into_inner();
+    authorize_tournament_action(&db, tournament_id, &user_info, TournamentRole::MANAGE).await?;
+    let webhooks = db.get_webhooks(tournament_id).await?;
+    if !webhooks.iter().any(|webhook| webhook.id == webhook_id) {
+        return Err(ServerError::WebhookNotFound);
+    }
+    let deliveries = db
+        .get_webhook_deliveries(webhook_id, WEBHOOK_DELIVERY_LIMIT)
+        .await?;
+    Ok(HttpResponse::Ok().json(deliveries))
+}
+
+// Draw endpoints
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DrawPayload {
+    pub class: String,
+    pub start_time: NaiveDateTime,
+    // Player ids ordered by seed, the first player is the top seed
+    pub seeds: Vec<i64>,
+    #[serde(default)]
+    pub format: DrawFormat,
+}
+
+#[tracing::instrument(name = "Generate draw", skip(db))]
+#[post("/tournaments/{id}/draws")]
+pub async fn generate_tournament_draw(
+    id: Path<i32>,
+    payload: Json<DrawPayload>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    authorize_tournament_action(&db, *id, &user_info, TournamentRole::MANAGE).await?;
+    let draw = generate_draw(&db, *id, payload.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(draw))
+}
+
+#[tracing::instrument(name = "Get draw", skip(db))]
+#[get("/tournaments/{id}/draws/{class}")]
+pub async fn get_tournament_draw(
+    path: Path<(i32, String)>,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    let (tournament_id, class) = path.into_inner();
+    let draw = db.get_ref().get_draw(tournament_id, &class).await?;
+    if draw.is_empty() {
+        Ok(HttpResponse::NotFound().finish())
+    } else {
+        Ok(HttpResponse::Ok().json(draw))
+    }
+}
+
+// Group endpoints
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GroupPayload {
+    pub class: String,
+    pub name: String,
+    pub players: Vec<i64>,
+    // Falls back to the default rules if empty
+    #[serde(default)]
+    pub tiebreak_rules: Vec<TiebreakRule>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GroupMatchesPayload {
+    pub start_time: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KnockoutPayload {
+    pub class: String,
+    pub start_time: NaiveDateTime,
+    // How many players from the top of each group that advance
+    pub players_per_group: usize,
+}
+
+#[tracing::instrument(name = "Insert group", skip(db))]
+#[post("/tournaments/{id}/groups")]
+pub async fn insert_group(
+    id: Path<i32>,
+    payload: Json<GroupPayload>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    authorize_tournament_action(&db, *id, &user_info, TournamentRole::MANAGE).await?;
+    let group = create_group(&db, *id, payload.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(group))
+}
+
+#[tracing::instrument(name = "Get groups", skip(db))]
+#[get("/tournaments/{id}/groups")]
+pub async fn get_tournament_groups(
+    id: Path<i32>,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    let groups = db.get_ref().get_groups(*id).await?;
+    Ok(HttpResponse::Ok().json(groups))
+}
+
+#[tracing::instrument(name = "Generate group matches", skip(db))]
+#[post("/tournaments/{id}/groups/{group_id}/matches")]
+pub async fn generate_group_matches_endpoint(
+    path: Path<(i32, i32)>,
+    payload: Json<GroupMatchesPayload>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    let (tournament_id, group_id) = path.into_inner();
+    authorize_tournament_action(&db, tournament_id, &user_info, TournamentRole::MANAGE).await?;
+    let matches = generate_group_matches(&db, tournament_id, group_id, payload.start_time).await?;
+    Ok(HttpResponse::Ok().json(matches))
+}
+
+#[tracing::instrument(name = "Get group standings", skip(db))]
+#[get("/tournaments/{id}/groups/{group_id}/standings")]
+pub async fn get_group_standings_endpoint(
+    path: Path<(i32, i32)>,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    let (tournament_id, group_id) = path.into_inner();
+    let standings = get_group_standings(&db, tournament_id, group_id).await?;
+    Ok(HttpResponse::Ok().json(standings))
+}
+
+#[tracing::instrument(name = "Generate knockout draw from groups", skip(db))]
+#[post("/tournaments/{id}/groups/knockout")]
+pub async fn generate_knockout_draw_endpoint(
+    id: Path<i32>,
+    payload: Json<KnockoutPayload>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    authorize_tournament_action(&db, *id, &user_info, TournamentRole::MANAGE).await?;
+    let draw = generate_knockout_draw(&db, *id, payload.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(draw))
+}
+
+// Court queue endpoints
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueuePositionPayload {
+    // Starts at 1
+    pub position: usize,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PutBackPayload {
+    // Starts at 1, last in the queue if missing
+    #[serde(default)]
+    pub position: Option<usize>,
+}
+
+#[tracing::instrument(name = "Get court queue", skip(db))]
+#[get("/tournaments/{id}/queue")]
+pub async fn get_court_queue(
+    id: Path<i32>,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    let queue = get_court_queue_matches(*id, &db).await?;
+    Ok(HttpResponse::Ok().json(queue))
+}
+
+#[tracing::instrument(name = "Move match in court queue", skip(db))]
+#[put("/tournaments/{id}/queue/{match_id}")]
+pub async fn move_in_court_queue_endpoint(
+    path: Path<(i32, i64)>,
+    payload: Json<QueuePositionPayload>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    let (tournament_id, match_id) = path.into_inner();
+    authorize_tournament_action(&db, tournament_id, &user_info, TournamentRole::MANAGE_QUEUE)
+        .await?;
+    let mut transaction = db.begin().await?;
+    let before = lock_court_queue(&mut transaction, tournament_id).await?;
+    move_in_court_queue(tournament_id, match_id, payload.position, &mut transaction).await?;
+    record_court_queue_change(
+        &db,
+        transaction,
+        &user_info,
+        "move_in_court_queue",
+        tournament_id,
+        match_id,
+        before,
+    )
+    .await
+}
+
+#[tracing::instrument(name = "Pull match from court queue", skip(db))]
+#[post("/tournaments/{id}/queue/{match_id}/pull")]
+pub async fn pull_from_court_queue_endpoint(
+    path: Path<(i32, i64)>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    let (tournament_id, match_id) = path.into_inner();
+    authorize_tournament_action(&db, tournament_id, &user_info, TournamentRole::MANAGE_QUEUE)
+        .await?;
+    let mut transaction = db.begin().await?;
+    let before = lock_court_queue(&mut transaction, tournament_id).await?;
+    pull_from_court_queue(tournament_id, match_id, &mut transaction).await?;
+    record_court_queue_change(
+        &db,
+        transaction,
+        &user_info,
+        "pull_from_court_queue",
+        tournament_id,
+        match_id,
+        before,
+    )
+    .await
+}
+
+#[tracing::instrument(name = "Put match back in court queue", skip(db))]
+#[post("/tournaments/{id}/queue/{match_id}/put_back")]
+pub async fn put_back_in_court_queue_endpoint(
+    path: Path<(i32, i64)>,
+    payload: Json<PutBackPayload>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    let (tournament_id, match_id) = path.into_inner();
+    authorize_tournament_action(&db, tournament_id, &user_info, TournamentRole::MANAGE_QUEUE)
+        .await?;
+    let mut transaction = db.begin().await?;
+    let before = lock_court_queue(&mut transaction, tournament_id).await?;
+    put_back_in_court_queue(tournament_id, match_id, payload.position, &mut transaction).await?;
+    record_court_queue_change(
+        &db,
+        transaction,
+        &user_info,
+        "put_back_in_court_queue",
+        tournament_id,
+        match_id,
+        before,
+    )
+    .await
+}
+
+// Audit logs the waiting matches before and after the change and responds with the new queue
+async fn record_court_queue_change(
+    db: &PgPool,
+    mut transaction: Transaction<'_, Postgres>,
+    user_info: &UserInfo,
+    endpoint: &'static str,
+    tournament_id: i32,
+    match_id: i64,
+    before: Vec<CourtQueueEntry>,
+) -> Result<HttpResponse, ServerError> {
+    // The entries are locked before the change so they can't be changed meanwhile
+    let before: Vec<i64> = before
+        .into_iter()
+        .filter(|entry| !entry.on_hold)
+        .map(|entry| entry.match_id)
+        .collect();
+    let after = (&mut transaction).get_court_queue(tournament_id).await?;
+    insert_audit_log(
+        &mut transaction,
+        AuditLogRecord {
+            user_id: Some(user_info.id),
+            endpoint,
+            tournament_id: Some(tournament_id),
+            match_id: Some(match_id),
+            before: serde_json::to_value(&before).ok(),
+            after: serde_json::to_value(&after).ok(),
+            ..Default::default()
+        },
+    )
+    .await?;
+    transaction
+        .commit()
+        .await
+        .inspect_err(|_| error!("Transaction failed!"))?;
+    let queue = get_court_queue_matches(tournament_id, db).await?;
+    Ok(HttpResponse::Ok().json(queue))
+}
+
 // Player endpoints
 #[tracing::instrument(name = "Insert player", skip(db))]
 #[post("/players")]
@@ -162,19 +1074,77 @@ pub async fn get_player(id: Path<i64>, db: Data<PgPool>) -> Result<impl Responde
     }
 }
 
+#[tracing::instrument(name = "Insert team", skip(db))]
+#[post("/teams")]
+pub async fn insert_team(
+    team: Json<Team>,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    if team.player_one == team.player_two {
+        return Err(ServerError::InvalidTeam);
+    }
+    for player_id in team.players().iter() {
+        if db.get_player(*player_id).await?.is_none() {
+            return Err(ServerError::PlayerNotFound);
+        }
+        // Teams can't be part of other teams
+        if db.get_team(*player_id).await?.is_some() {
+            return Err(ServerError::InvalidTeam);
+        }
+    }
+    db.insert_team(&team).await?;
+    Ok(HttpResponse::Ok())
+}
+
+#[tracing::instrument(name = "Get team", skip(db))]
+#[get("/teams/{id}")]
+pub async fn get_team(id: Path<i64>, db: Data<PgPool>) -> Result<impl Responder, ServerError> {
+    if let Some(team) = db.get_team(*id).await? {
+        Ok(HttpResponse::Ok().json(team))
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
 // Match endpoints
 #[tracing::instrument(name = "Insert match", skip(db))]
 #[post("/matches")]
 pub async fn insert_match(
     match_data: Json<Match>,
+    user_info: UserInfo,
     db: Data<PgPool>,
 ) -> Result<impl Responder, ServerError> {
+    authorize_tournament_action(
+        &db,
+        match_data.tournament_id,
+        &user_info,
+        TournamentRole::MANAGE,
+    )
+    .await?;
     if match_data.start_time < Local::now().naive_local() {
         Err(ServerError::InvalidStartTime)
-    } else if match_data.player_one == match_data.player_two {
-        Err(ServerError::InvalidRooster)
     } else {
-        let id = db.insert_match(match_data.into_inner()).await?;
+        let mut match_data = match_data.into_inner();
+        let tournament_id = match_data.tournament_id;
+        let mut transaction = db.begin().await?;
+        let id = create_match(&db, &mut transaction, match_data.clone()).await?;
+        match_data.id = id;
+        insert_audit_log(
+            &mut transaction,
+            AuditLogRecord {
+                user_id: Some(user_info.id),
+                endpoint: "insert_match",
+                tournament_id: Some(tournament_id),
+                match_id: Some(id),
+                after: serde_json::to_value(&match_data).ok(),
+                ..Default::default()
+            },
+        )
+        .await?;
+        transaction
+            .commit()
+            .await
+            .inspect_err(|_| error!("Transaction failed!"))?;
         Ok(HttpResponse::Ok().body(id.to_string()))
     }
 }
@@ -184,16 +1154,120 @@ pub async fn insert_match(
 pub async fn finish_match_endpoint(
     id: Path<i64>,
     result: Json<MatchResult>,
+    caller: Caller,
     db: Data<PgPool>,
 ) -> Result<impl Responder, ServerError> {
-    let match_info = finish_match(*id, result.into_inner(), &db).await?;
+    authorize_caller_match_action(
+        &db,
+        *id,
+        &caller,
+        TournamentRole::REPORT_RESULTS,
+        ApiKeyScope::FinishMatch,
+    )
+    .await?;
+    let before = db.get_match(*id).await?.ok_or(ServerError::MatchNotFound)?;
+    let mut transaction = db.begin().await?;
+    let match_info = finish_match(*id, result.into_inner(), &db, &mut transaction).await?;
+    insert_audit_log(
+        &mut transaction,
+        AuditLogRecord {
+            user_id: caller.user_id(),
+            api_key_id: caller.api_key_id(),
+            endpoint: "finish_match",
+            tournament_id: Some(before.tournament_id),
+            match_id: Some(*id),
+            before: serde_json::to_value(&before).ok(),
+            after: serde_json::to_value(&match_info).ok(),
+            ..Default::default()
+        },
+    )
+    .await?;
+    transaction
+        .commit()
+        .await
+        .inspect_err(|_| error!("Transaction failed!"))?;
     Ok(HttpResponse::Ok().json(match_info))
 }
 
+#[tracing::instrument(name = "Correct match result", skip(db))]
+#[put("/matches/{match_id}/result")]
+pub async fn correct_match_result_endpoint(
+    id: Path<i64>,
+    result: Json<MatchResult>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    authorize_match_action(&db, *id, &user_info, TournamentRole::REPORT_RESULTS).await?;
+    let match_info = correct_match_result(*id, result.into_inner(), user_info.id, &db).await?;
+    Ok(HttpResponse::Ok().json(match_info))
+}
+
+#[tracing::instrument(name = "Get match result corrections", skip(db))]
+#[get("/matches/{match_id}/corrections")]
+pub async fn get_match_result_corrections(
+    id: Path<i64>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    authorize_match_action(&db, *id, &user_info, TournamentRole::CHECK_IN).await?;
+    let corrections = db.get_match_result_corrections(*id).await?;
+    Ok(HttpResponse::Ok().json(corrections))
+}
+
+#[tracing::instrument(name = "Get match scheduling", skip(db))]
+#[get("/matches/{match_id}/scheduling")]
+pub async fn get_match_scheduling(
+    id: Path<i64>,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    let scheduling = db
+        .get_match_scheduling(*id)
+        .await?
+        .ok_or(ServerError::MatchNotFound)?;
+    Ok(HttpResponse::Ok().json(scheduling))
+}
+
+#[tracing::instrument(name = "Update match scheduling", skip(db))]
+#[put("/matches/{match_id}/scheduling")]
+pub async fn update_match_scheduling_endpoint(
+    id: Path<i64>,
+    scheduling: Json<MatchScheduling>,
+    user_info: UserInfo,
+    db: Data<PgPool>,
+) -> Result<impl Responder, ServerError> {
+    authorize_match_action(&db, *id, &user_info, TournamentRole::MANAGE).await?;
+    let before = db
+        .get_match_scheduling(*id)
+        .await?
+        .ok_or(ServerError::MatchNotFound)?;
+    let scheduling = scheduling.into_inner();
+    let after = serde_json::to_value(&scheduling).ok();
+    let match_data = db.get_match(*id).await?.ok_or(ServerError::MatchNotFound)?;
+    let mut transaction = db.begin().await?;
+    update_match_scheduling(*id, scheduling, &db, &mut transaction).await?;
+    insert_audit_log(
+        &mut transaction,
+        AuditLogRecord {
+            user_id: Some(user_info.id),
+            endpoint: "update_match_scheduling",
+            tournament_id: Some(match_data.tournament_id),
+            match_id: Some(*id),
+            before: serde_json::to_value(&before).ok(),
+            after,
+            ..Default::default()
+        },
+    )
+    .await?;
+    transaction
+        .commit()
+        .await
+        .inspect_err(|_| error!("Transaction failed!"))?;
+    Ok(HttpResponse::Ok())
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PlayerMatchRegistrationPayload {
     pub player_id: i64,
-    pub registered_by: String,
 }
 
 // TODO: This should probably take a form instead
@@ -202,9 +1276,48 @@ pub struct PlayerMatchRegistrationPayload {
 pub async fn register_player(
     match_id: Path<i64>,
     payload: Json<PlayerMatchRegistrationPayload>,
+    caller: Caller,
     storage: Data<PgPool>,
 ) -> Result<impl Responder, ServerError> {
-    let match_registration =
-        register_player_to_match(&*storage.into_inner(), *match_id, payload.into_inner()).await?;
+    authorize_caller_match_action(
+        &storage,
+        *match_id,
+        &caller,
+        TournamentRole::CHECK_IN,
+        ApiKeyScope::RegisterPlayer,
+    )
+    .await?;
+    let registrar = get_registrar(&storage, &caller).await?;
+    let match_data = storage
+        .get_match(*match_id)
+        .await?
+        .ok_or(ServerError::MatchNotFound)?;
+    let mut transaction = storage.begin().await?;
+    let match_registration = register_player_to_match(
+        &storage,
+        &mut transaction,
+        *match_id,
+        payload.into_inner(),
+        registrar,
+    )
+    .await?;
+    insert_audit_log(
+        &mut transaction,
+        AuditLogRecord {
+            user_id: caller.user_id(),
+            api_key_id: caller.api_key_id(),
+            endpoint: "register_player",
+            tournament_id: Some(match_data.tournament_id),
+            match_id: Some(*match_id),
+            player_id: Some(match_registration.player_id),
+            after: serde_json::to_value(&match_registration).ok(),
+            ..Default::default()
+        },
+    )
+    .await?;
+    transaction
+        .commit()
+        .await
+        .inspect_err(|_| error!("Transaction failed!"))?;
     Ok(HttpResponse::Ok().json(match_registration))
 }
@@ -0,0 +1,244 @@
+#![allow(clippy::toplevel_ref_arg)]
+use crate::{match_operations::MatchInfo, stores::match_store::Match, webhooks::queue_webhooks};
+use actix_web::rt::{spawn, time::delay_for};
+use chrono::Local;
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgListener, PgPool, Postgres, Transaction};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, error, warn};
+
+// Every instance listens to the channel so events reach clients connected to any of them
+const EVENT_CHANNEL: &str = "tournament_events";
+// How long the published events are kept for the listeners to fetch them
+const EVENT_RETENTION_MINUTES: i64 = 10;
+
+// Pushed to everyone following a tournament
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum TournamentEvent {
+    // Sent again with the new players when a player advances into a match of a draw,
+    // or when a corrected result changes who advanced into it
+    MatchCreated { match_data: Match },
+    PlayerCheckedIn { match_id: i64, player_id: i64 },
+    MatchStarted { match_id: i64, court: String },
+    // The match ids in the court queue in the order they will get a court
+    QueueChanged { queue: Vec<i64> },
+    MatchFinished { match_info: MatchInfo },
+}
+
+impl TournamentEvent {
+    pub fn event_type(&self) -> TournamentEventType {
+        match self {
+            TournamentEvent::MatchCreated { .. } => TournamentEventType::MatchCreated,
+            TournamentEvent::PlayerCheckedIn { .. } => TournamentEventType::PlayerCheckedIn,
+            TournamentEvent::MatchStarted { .. } => TournamentEventType::MatchStarted,
+            TournamentEvent::QueueChanged { .. } => TournamentEventType::QueueChanged,
+            TournamentEvent::MatchFinished { .. } => TournamentEventType::MatchFinished,
+        }
+    }
+}
+
+// Same as the type tag of the serialized events, used to choose the events of a webhook
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TournamentEventType {
+    MatchCreated,
+    PlayerCheckedIn,
+    MatchStarted,
+    QueueChanged,
+    MatchFinished,
+}
+
+impl TournamentEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TournamentEventType::MatchCreated => "match_created",
+            TournamentEventType::PlayerCheckedIn => "player_checked_in",
+            TournamentEventType::MatchStarted => "match_started",
+            TournamentEventType::QueueChanged => "queue_changed",
+            TournamentEventType::MatchFinished => "match_finished",
+        }
+    }
+}
+
+impl FromStr for TournamentEventType {
+    type Err = String;
+
+    fn from_str(event_type: &str) -> Result<Self, Self::Err> {
+        match event_type {
+            "match_created" => Ok(TournamentEventType::MatchCreated),
+            "player_checked_in" => Ok(TournamentEventType::PlayerCheckedIn),
+            "match_started" => Ok(TournamentEventType::MatchStarted),
+            "queue_changed" => Ok(TournamentEventType::QueueChanged),
+            "match_finished" => Ok(TournamentEventType::MatchFinished),
+            _ => Err(format!("Unknown tournament event type: {}", event_type)),
+        }
+    }
+}
+
+// Keeps track of the clients following each tournament on this instance,
+// the events are sent as serialized json
+#[derive(Debug, Default)]
+pub struct TournamentEvents {
+    subscribers: Mutex<HashMap<i32, Vec<UnboundedSender<String>>>>,
+}
+
+impl TournamentEvents {
+    pub fn subscribe(&self, tournament_id: i32) -> UnboundedReceiver<String> {
+        let (sender, receiver) = unbounded();
+        self.subscribers
+            .lock()
+            .expect("Subscribers lock poisoned")
+            .entry(tournament_id)
+            .or_default()
+            .push(sender);
+        receiver
+    }
+
+    fn send(&self, tournament_id: i32, event: &str) {
+        let mut subscribers = self.subscribers.lock().expect("Subscribers lock poisoned");
+        if let Some(senders) = subscribers.get_mut(&tournament_id) {
+            // Sending fails when the client has disconnected
+            senders.retain(|sender| sender.unbounded_send(event.to_string()).is_ok());
+            if senders.is_empty() {
+                subscribers.remove(&tournament_id);
+            }
+        }
+    }
+
+    // Drops the clients that disconnected from tournaments without new events
+    fn prune(&self) {
+        let mut subscribers = self.subscribers.lock().expect("Subscribers lock poisoned");
+        subscribers.retain(|_, senders| {
+            senders.retain(|sender| !sender.is_closed());
+            !senders.is_empty()
+        });
+    }
+}
+
+// Sends the event to the clients following the tournament on all instances and queues
+// it for the webhooks of the tournament. It's done in the transaction making the change
+// so the event is sent if, and only if, the change is committed.
+pub async fn publish_event(
+    transaction: &mut Transaction<'_, Postgres>,
+    tournament_id: i32,
+    event: &TournamentEvent,
+) -> Result<(), sqlx::Error> {
+    queue_webhooks(transaction, tournament_id, event).await?;
+    let event = match serde_json::to_string(event) {
+        Ok(event) => event,
+        Err(err) => {
+            error!("Failed to serialize tournament event {}", err);
+            return Ok(());
+        }
+    };
+    debug!(
+        "Publishing event for tournament {}: {}",
+        tournament_id, event
+    );
+    // Postgres limits the NOTIFY payload to 8000 bytes so only the id of the
+    // stored event is sent, the listeners fetch the event itself
+    let row = sqlx::query!(
+        "INSERT INTO tournament_events (tournament_id, payload, created_at) VALUES ($1, $2, $3)
+        RETURNING id",
+        tournament_id,
+        event,
+        Local::now().naive_local()
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err(|err| {
+        error!("Failed to insert tournament event {}", err);
+        err
+    })?;
+    sqlx::query!(
+        "SELECT pg_notify($1, $2)",
+        EVENT_CHANNEL,
+        row.id.to_string()
+    )
+    .execute(transaction)
+    .await
+    .map_err(|err| {
+        error!("Failed to publish tournament event {}", err);
+        err
+    })?;
+    Ok(())
+}
+
+async fn get_tournament_event(
+    storage: &PgPool,
+    event_id: i64,
+) -> Result<Option<(i32, String)>, sqlx::Error> {
+    let row = sqlx::query!(
+        "SELECT tournament_id, payload FROM tournament_events WHERE id = $1",
+        event_id
+    )
+    .fetch_optional(storage)
+    .await
+    .map_err(|err| {
+        error!("Failed to fetch tournament event {}", err);
+        err
+    })?;
+    Ok(row.map(|row| (row.tournament_id, row.payload)))
+}
+
+// The events are only needed until every listener has fetched them
+async fn delete_old_tournament_events(storage: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM tournament_events WHERE created_at < $1",
+        Local::now().naive_local() - chrono::Duration::minutes(EVENT_RETENTION_MINUTES)
+    )
+    .execute(storage)
+    .await
+    .map_err(|err| {
+        error!("Failed to delete old tournament events {}", err);
+        err
+    })?;
+    Ok(())
+}
+
+// Starts listening to the events published by every instance and sends them to the
+// local subscribers. Holds on to one of the connections in the pool.
+pub async fn start_event_listener(pool: &PgPool) -> Result<Arc<TournamentEvents>, sqlx::Error> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen(EVENT_CHANNEL).await?;
+    let events = Arc::new(TournamentEvents::default());
+    let subscribers = events.clone();
+    let storage = pool.clone();
+    spawn(async move {
+        loop {
+            match listener.try_recv().await {
+                Ok(Some(notification)) => match notification.payload().parse() {
+                    // Errors are logged when fetching
+                    Ok(event_id) => match get_tournament_event(&storage, event_id).await {
+                        Ok(Some((tournament_id, event))) => subscribers.send(tournament_id, &event),
+                        Ok(None) => warn!("Tournament event {} not found", event_id),
+                        Err(_) => {}
+                    },
+                    Err(_) => error!("Invalid tournament event: {}", notification.payload()),
+                },
+                // The listener reconnects on the next call, events sent meanwhile are lost
+                Ok(None) => warn!("Lost connection to the tournament event channel"),
+                Err(err) => {
+                    error!("Failed to receive tournament events {}", err);
+                    delay_for(Duration::from_secs(1)).await;
+                }
+            }
+        }
+    });
+    let storage = pool.clone();
+    let subscribers = events.clone();
+    spawn(async move {
+        loop {
+            // Errors are logged by delete_old_tournament_events
+            let _ = delete_old_tournament_events(&storage).await;
+            subscribers.prune();
+            delay_for(Duration::from_secs(60)).await;
+        }
+    });
+    Ok(events)
+}
@@ -0,0 +1,426 @@
+use crate::{
+    bracket_operations::{generate_draw, DrawFormat},
+    endpoints::{DrawPayload, GroupPayload, KnockoutPayload},
+    events::{publish_event, TournamentEvent},
+    score::{Score, SetScore},
+    stores::{
+        bracket_store::BracketMatch,
+        group_store::{Group, GroupMatchResult, GroupStore, TiebreakRule},
+        match_store::{Match, MatchOutcome},
+        player_store::{Player, PlayerStore},
+        tournament_store::TournamentStore,
+    },
+    ServerError,
+};
+use chrono::{Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use tracing::{error, info, warn};
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Standing {
+    pub rank: usize,
+    pub player: Player,
+    pub played: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub sets_won: u32,
+    pub sets_lost: u32,
+    pub games_won: u32,
+    pub games_lost: u32,
+}
+
+impl Standing {
+    fn new(player: Player) -> Self {
+        Standing {
+            rank: 0,
+            player,
+            played: 0,
+            wins: 0,
+            losses: 0,
+            sets_won: 0,
+            sets_lost: 0,
+            games_won: 0,
+            games_lost: 0,
+        }
+    }
+}
+
+#[tracing::instrument(name = "Create group", skip(storage))]
+pub async fn create_group(
+    storage: &PgPool,
+    tournament_id: i32,
+    payload: GroupPayload,
+) -> Result<Group, ServerError> {
+    if storage.get_tournament(tournament_id).await?.is_none() {
+        return Err(ServerError::TournamentNotFound);
+    }
+    let unique_players: HashSet<&i64> = payload.players.iter().collect();
+    if payload.players.len() < 2 || unique_players.len() != payload.players.len() {
+        return Err(ServerError::InvalidRooster);
+    }
+
+    for player_id in payload.players.iter() {
+        if storage.get_player(*player_id).await?.is_none() {
+            return Err(ServerError::PlayerNotFound);
+        }
+    }
+
+    if storage
+        .get_groups(tournament_id)
+        .await?
+        .iter()
+        .any(|group| group.class == payload.class && group.name == payload.name)
+    {
+        return Err(ServerError::GroupAlreadyExists(payload.name));
+    }
+
+    let tiebreak_rules = if payload.tiebreak_rules.is_empty() {
+        TiebreakRule::DEFAULT_RULES.to_vec()
+    } else {
+        payload.tiebreak_rules
+    };
+    let mut group = Group {
+        id: 0,
+        tournament_id,
+        class: payload.class,
+        name: payload.name,
+        tiebreak_rules,
+    };
+
+    let mut transaction = storage.begin().await?;
+    group.id = transaction.insert_group(&group).await?;
+    for player_id in payload.players.iter() {
+        transaction
+            .insert_group_player(group.id, *player_id)
+            .await?;
+    }
+    transaction
+        .commit()
+        .await
+        .inspect_err(|_| error!("Transaction failed!"))?;
+    Ok(group)
+}
+
+// Circle method, every player meets every other player once and
+// the matches are ordered so nobody has to play several matches in a row
+fn round_robin_pairings(players: &[i64]) -> Vec<(i64, i64)> {
+    let mut slots: Vec<Option<i64>> = players.iter().copied().map(Some).collect();
+    if slots.len() % 2 == 1 {
+        // Whoever meets the empty slot sits out that round
+        slots.push(None);
+    }
+    let mut pairings = Vec::with_capacity(players.len() * (players.len() - 1) / 2);
+    for _ in 1..slots.len() {
+        for index in 0..slots.len() / 2 {
+            if let (Some(player_one), Some(player_two)) =
+                (slots[index], slots[slots.len() - 1 - index])
+            {
+                pairings.push((player_one, player_two));
+            }
+        }
+        // Keep the first slot fixed and rotate the rest
+        slots[1..].rotate_right(1);
+    }
+    pairings
+}
+
+#[tracing::instrument(name = "Generate group matches", skip(storage))]
+pub async fn generate_group_matches(
+    storage: &PgPool,
+    tournament_id: i32,
+    group_id: i32,
+    start_time: NaiveDateTime,
+) -> Result<Vec<Match>, ServerError> {
+    if start_time < Local::now().naive_local() {
+        return Err(ServerError::InvalidStartTime);
+    }
+
+    let group = match storage.get_group(tournament_id, group_id).await? {
+        Some(group) => group,
+        None => return Err(ServerError::GroupNotFound),
+    };
+
+    let player_ids: Vec<i64> = storage
+        .get_group_players(group_id)
+        .await?
+        .iter()
+        .map(|player| player.id)
+        .collect();
+
+    let mut transaction = storage.begin().await?;
+    if transaction.has_group_matches(group_id).await? {
+        return Err(ServerError::GroupMatchesAlreadyExist);
+    }
+    let mut matches = Vec::new();
+    for (player_one, player_two) in round_robin_pairings(&player_ids) {
+        let mut match_data = Match {
+            id: 0,
+            player_one: Some(player_one),
+            player_two: Some(player_two),
+            tournament_id,
+            class: group.class.clone(),
+            start_time,
+        };
+        match_data.id = transaction
+            .insert_group_match(&match_data, group_id)
+            .await?;
+        publish_event(
+            &mut transaction,
+            tournament_id,
+            &TournamentEvent::MatchCreated {
+                match_data: match_data.clone(),
+            },
+        )
+        .await?;
+        matches.push(match_data);
+    }
+    transaction
+        .commit()
+        .await
+        .inspect_err(|_| error!("Transaction failed!"))?;
+    info!(
+        "Generated {} matches for group {}",
+        matches.len(),
+        group.name
+    );
+    Ok(matches)
+}
+
+fn add_result(standing: &mut Standing, won: bool, set_scores: &[(u32, u32)]) {
+    standing.played += 1;
+    if won {
+        standing.wins += 1;
+    } else {
+        standing.losses += 1;
+    }
+    for (games_won, games_lost) in set_scores.iter() {
+        standing.games_won += games_won;
+        standing.games_lost += games_lost;
+        if games_won > games_lost {
+            standing.sets_won += 1;
+        } else {
+            standing.sets_lost += 1;
+        }
+    }
+}
+
+fn tiebreak_value(rule: TiebreakRule, standing: &Standing) -> i64 {
+    match rule {
+        TiebreakRule::HeadToHead => 0,
+        TiebreakRule::SetDifference => standing.sets_won as i64 - standing.sets_lost as i64,
+        TiebreakRule::GameDifference => standing.games_won as i64 - standing.games_lost as i64,
+        TiebreakRule::SetsWon => standing.sets_won as i64,
+        TiebreakRule::GamesWon => standing.games_won as i64,
+    }
+}
+
+// Wins in the matches played between the tied players only
+fn head_to_head_wins(player_id: i64, tied: &[i64], results: &[GroupMatchResult]) -> i64 {
+    results
+        .iter()
+        .filter(|result| tied.contains(&result.player_one) && tied.contains(&result.player_two))
+        .filter(|result| result.winner == player_id)
+        .count() as i64
+}
+
+// Orders players that are tied by applying the tiebreak rules in order
+fn rank_tied(
+    tied: Vec<Standing>,
+    rules: &[TiebreakRule],
+    results: &[GroupMatchResult],
+) -> Vec<Standing> {
+    let (rule, remaining_rules) = match rules.split_first() {
+        Some(split) if tied.len() > 1 => split,
+        _ => return tied,
+    };
+    match rule {
+        TiebreakRule::HeadToHead => {
+            // Ranked on a table of only the matches between the tied players
+            let tied_players: Vec<i64> = tied.iter().map(|standing| standing.player.id).collect();
+            rank_by(
+                tied,
+                |standing| head_to_head_wins(standing.player.id, &tied_players, results),
+                remaining_rules,
+                results,
+            )
+        }
+        rule => {
+            let rule = *rule;
+            rank_by(
+                tied,
+                |standing| tiebreak_value(rule, standing),
+                remaining_rules,
+                results,
+            )
+        }
+    }
+}
+
+// Sorts the players by the key (highest first), players with the same key
+// are ordered by the tiebreak rules
+fn rank_by(
+    mut standings: Vec<Standing>,
+    key: impl Fn(&Standing) -> i64,
+    rules: &[TiebreakRule],
+    results: &[GroupMatchResult],
+) -> Vec<Standing> {
+    standings.sort_by_key(|standing| Reverse(key(standing)));
+    let mut ranked = Vec::with_capacity(standings.len());
+    let mut tied: Vec<Standing> = Vec::new();
+    for standing in standings.into_iter() {
+        if let Some(previous) = tied.last() {
+            if key(previous) != key(&standing) {
+                ranked.extend(rank_tied(std::mem::take(&mut tied), rules, results));
+            }
+        }
+        tied.push(standing);
+    }
+    ranked.extend(rank_tied(tied, rules, results));
+    ranked
+}
+
+fn compute_standings(
+    players: Vec<Player>,
+    results: &[GroupMatchResult],
+    rules: &[TiebreakRule],
+) -> Vec<Standing> {
+    let mut standings: Vec<Standing> = players.into_iter().map(Standing::new).collect();
+
+    for result in results.iter() {
+        // Walkovers have no score and matches that ended early might not have one either,
+        // those still count as a win but don't add any sets or games
+        let has_score = match result.outcome {
+            MatchOutcome::Completed => true,
+            MatchOutcome::Walkover => false,
+            MatchOutcome::Retired | MatchOutcome::Disqualified => !result.result.is_empty(),
+        };
+        let set_scores: Vec<(u32, u32)> = if has_score {
+            match result.result.parse::<Score>() {
+                Ok(score) => score.sets.iter().map(SetScore::games).collect(),
+                Err(err) => {
+                    warn!("Unable to parse match result: {}", err);
+                    Vec::new()
+                }
+            }
+        } else {
+            Vec::new()
+        };
+        let flipped_scores: Vec<(u32, u32)> = set_scores
+            .iter()
+            .map(|(player_one_games, player_two_games)| (*player_two_games, *player_one_games))
+            .collect();
+
+        for standing in standings.iter_mut() {
+            if standing.player.id == result.player_one {
+                add_result(standing, result.winner == result.player_one, &set_scores);
+            } else if standing.player.id == result.player_two {
+                add_result(
+                    standing,
+                    result.winner == result.player_two,
+                    &flipped_scores,
+                );
+            }
+        }
+    }
+
+    let mut ranked = rank_by(standings, |standing| standing.wins as i64, rules, results);
+    for (index, standing) in ranked.iter_mut().enumerate() {
+        standing.rank = index + 1;
+    }
+    ranked
+}
+
+#[tracing::instrument(name = "Get group standings", skip(storage))]
+pub async fn get_group_standings(
+    storage: &PgPool,
+    tournament_id: i32,
+    group_id: i32,
+) -> Result<Vec<Standing>, ServerError> {
+    let group = match storage.get_group(tournament_id, group_id).await? {
+        Some(group) => group,
+        None => return Err(ServerError::GroupNotFound),
+    };
+    let players = storage.get_group_players(group_id).await?;
+    let results = storage.get_group_results(group_id).await?;
+    Ok(compute_standings(players, &results, &group.tiebreak_rules))
+}
+
+// Orders the advancing players so the standard seeding order gives a crossover,
+// ex: with two groups A1 meets B2 and B1 meets A2 in the first round.
+// Every inner vec is the ranked players of a group.
+fn crossover_seeds(group_rankings: &[Vec<i64>]) -> Vec<i64> {
+    let group_count = group_rankings.len();
+    // Groups are paired with their neighbour, a single group is its own partner.
+    // Only even group counts are paired up, see generate_knockout_draw
+    let partner = |group: usize| if group_count == 1 { group } else { group ^ 1 };
+    let players_per_group = group_rankings.iter().map(Vec::len).max().unwrap_or(0);
+    let mut seeds = Vec::with_capacity(group_count * players_per_group);
+    for place in 0..players_per_group {
+        for position in 0..group_count {
+            let group = if place % 2 == 0 {
+                position
+            } else {
+                // Mirrors the group order so the runner up of a group
+                // meets the winner of the partner group
+                partner(group_count - 1 - position)
+            };
+            if let Some(player_id) = group_rankings[group].get(place) {
+                seeds.push(*player_id);
+            }
+        }
+    }
+    seeds
+}
+
+#[tracing::instrument(name = "Generate knockout draw from groups", skip(storage))]
+pub async fn generate_knockout_draw(
+    storage: &PgPool,
+    tournament_id: i32,
+    payload: KnockoutPayload,
+) -> Result<Vec<BracketMatch>, ServerError> {
+    let groups: Vec<Group> = storage
+        .get_groups(tournament_id)
+        .await?
+        .into_iter()
+        .filter(|group| group.class == payload.class)
+        .collect();
+    if groups.is_empty() {
+        return Err(ServerError::GroupNotFound);
+    }
+    // The winner and runner up of a group can't be kept in opposite halves
+    // of the draw with an odd number of groups
+    if groups.len() > 1 && groups.len() % 2 == 1 && payload.players_per_group > 1 {
+        return Err(ServerError::UnsupportedGroupCount);
+    }
+
+    let mut group_rankings = Vec::with_capacity(groups.len());
+    for group in groups.iter() {
+        if !storage.has_group_matches(group.id).await?
+            || storage.count_unfinished_group_matches(group.id).await? > 0
+        {
+            return Err(ServerError::GroupNotFinished(group.name.clone()));
+        }
+        let standings = get_group_standings(storage, tournament_id, group.id).await?;
+        if payload.players_per_group == 0 || standings.len() < payload.players_per_group {
+            return Err(ServerError::InvalidRooster);
+        }
+        group_rankings.push(
+            standings
+                .iter()
+                .take(payload.players_per_group)
+                .map(|standing| standing.player.id)
+                .collect(),
+        );
+    }
+
+    let draw_payload = DrawPayload {
+        class: payload.class,
+        start_time: payload.start_time,
+        seeds: crossover_seeds(&group_rankings),
+        format: DrawFormat::SingleElimination,
+    };
+    generate_draw(storage, tournament_id, draw_payload).await
+}
@@ -1,11 +1,15 @@
-use actix_web::{dev::Server, http, web, HttpServer, ResponseError};
+use actix_web::{dev::Server, http, web, HttpResponse, HttpServer, ResponseError};
 use actix_web::{web::Data, App};
 use actix_web_httpauth::{extractors::bearer::BearerAuth, middleware::HttpAuthentication};
 use authentication::authenticate_request;
+use configuration::{LoginSettings, WebhookSettings};
 use endpoints::*;
+use events::TournamentEvents;
+use mailer::Mailer;
 use sqlx::PgPool;
 use std::io;
 use std::net::TcpListener;
+use std::sync::Arc;
 use thiserror::Error;
 use tracing::{subscriber::set_global_default, Subscriber};
 use tracing_actix_web::TracingLogger;
@@ -14,10 +18,17 @@ use tracing_log::LogTracer;
 use tracing_subscriber::{fmt::MakeWriter, prelude::*, EnvFilter, Registry};
 
 pub mod authentication;
+pub mod bracket_operations;
 pub mod configuration;
 pub mod endpoints;
+pub mod events;
+pub mod group_operations;
+pub mod mailer;
 pub mod match_operations;
+pub mod score;
 pub mod stores;
+pub mod tournament_operations;
+pub mod webhooks;
 
 /*
 Actix will log these via the Debug trait and not the display string from the error attribute.
@@ -32,16 +43,26 @@ pub enum ServerError {
     InvalidStartTime,
     #[error("Invalid rooster, two different players are needed")]
     InvalidRooster,
+    #[error("Invalid team, two different players are needed")]
+    InvalidTeam,
     #[error("Invalid player registration")]
     InvalidPlayerRegistration,
     #[error("Invalid winner, player not part of match")]
     InvalidWinner,
     #[error("Invalid result, the result string isn't a valid score")]
     InvalidResult,
+    #[error("Invalid winner, the score says the other player won")]
+    WinnerDoesNotMatchScore,
+    #[error("Invalid match format, matches are best of 3 or 5 sets")]
+    InvalidMatchFormat,
     #[error("Match already completed")]
     MatchAlreadyCompleted,
     #[error("Can't finish match, it hasn't started yet")]
     MatchNotStarted,
+    #[error("Can't correct match, it hasn't finished yet")]
+    MatchNotFinished,
+    #[error("Can't correct match, the next match has already started")]
+    NextMatchAlreadyStarted,
     #[error("Player already registered to match")]
     PlayerAlreadyReigstered,
     #[error("Can't start match, player is missing")]
@@ -50,8 +71,48 @@ pub enum ServerError {
     PlayerNotFound,
     #[error("Match can't be found")]
     MatchNotFound,
+    #[error("Tournament can't be found")]
+    TournamentNotFound,
+    #[error("Only the owner of the tournament can do that")]
+    NotTournamentOwner,
+    #[error("Missing the required role in the tournament")]
+    MissingTournamentRole,
+    #[error("Only admins can do that")]
+    AdminRequired,
+    #[error("The email hasn't been verified")]
+    EmailNotVerified,
+    #[error("The api key isn't allowed to do that")]
+    MissingApiKeyScope,
+    #[error("Invalid api key, a name and at least one scope are needed")]
+    InvalidApiKey,
+    #[error("Matches are scheduled outside of the tournament dates")]
+    MatchesOutsideTournamentDates,
     #[error("Match already started")]
     MatchAlreadyStarted,
+    #[error("Match isn't in the court queue")]
+    MatchNotInQueue,
+    #[error("Match hasn't been pulled out of the court queue")]
+    MatchNotOnHold,
+    #[error("Invalid position in the court queue")]
+    InvalidQueuePosition,
+    #[error(
+        "Invalid match scheduling, the preferred court has to be one of the tournament courts"
+    )]
+    InvalidMatchScheduling,
+    #[error("A draw already exists for class {0}")]
+    DrawAlreadyExists(String),
+    #[error("Group can't be found")]
+    GroupNotFound,
+    #[error("A group named {0} already exists for the class")]
+    GroupAlreadyExists(String),
+    #[error("Matches have already been generated for the group")]
+    GroupMatchesAlreadyExist,
+    #[error("Group {0} hasn't finished all of its matches")]
+    GroupNotFinished(String),
+    #[error(
+        "Unsupported number of groups, more than one player can only advance from an even number of groups"
+    )]
+    UnsupportedGroupCount,
     #[error("User with email {0} already exists")]
     AccountAlreadyExists(String),
     #[error("Invalid email")]
@@ -62,8 +123,22 @@ pub enum ServerError {
     InvalidToken(String),
     #[error("Login failed")]
     LoginFailed,
+    #[error("Too many failed login attempts, try again in {0} seconds")]
+    TooManyLoginAttempts(u64),
+    #[error("Failed to send email")]
+    EmailDeliveryFailed,
+    #[error("Failed to reload the signing keys")]
+    SigningKeyReloadFailed,
     #[error("User not found")]
     UserNotFound,
+    #[error("Api key not found")]
+    ApiKeyNotFound,
+    #[error(
+        "Invalid webhook, a http(s) url to a public address and at least one event type are needed"
+    )]
+    InvalidWebhook,
+    #[error("Webhook not found")]
+    WebhookNotFound,
     #[error("Internal Database error")]
     InternalDataBaseError(#[from] sqlx::Error),
 }
@@ -74,26 +149,64 @@ impl ResponseError for ServerError {
             ServerError::InvalidDate
             | ServerError::PlayerMissing
             | ServerError::InvalidRooster
+            | ServerError::InvalidTeam
             | ServerError::InvalidStartTime
             | ServerError::InvalidPlayerRegistration
             | ServerError::InvalidWinner
             | ServerError::InvalidResult
+            | ServerError::WinnerDoesNotMatchScore
+            | ServerError::InvalidMatchFormat
             | ServerError::MatchAlreadyStarted
             | ServerError::InvalidPassword
             | ServerError::InvalidEmail
+            | ServerError::InvalidApiKey
+            | ServerError::InvalidWebhook
+            | ServerError::InvalidQueuePosition
+            | ServerError::InvalidMatchScheduling
+            | ServerError::UnsupportedGroupCount
             | ServerError::PlayerAlreadyReigstered => http::StatusCode::BAD_REQUEST,
             ServerError::MatchNotFound
+            | ServerError::TournamentNotFound
             | ServerError::UserNotFound
+            | ServerError::ApiKeyNotFound
+            | ServerError::WebhookNotFound
+            | ServerError::MatchNotInQueue
+            | ServerError::GroupNotFound
             | ServerError::PlayerNotFound => http::StatusCode::NOT_FOUND,
-            ServerError::InternalDataBaseError(_) | ServerError::LoginFailed => {
-                http::StatusCode::INTERNAL_SERVER_ERROR
-            }
+            ServerError::InternalDataBaseError(_)
+            | ServerError::LoginFailed
+            | ServerError::EmailDeliveryFailed
+            | ServerError::SigningKeyReloadFailed => http::StatusCode::INTERNAL_SERVER_ERROR,
             ServerError::InvalidToken(_) => http::StatusCode::UNAUTHORIZED,
+            ServerError::NotTournamentOwner
+            | ServerError::MissingTournamentRole
+            | ServerError::AdminRequired
+            | ServerError::EmailNotVerified
+            | ServerError::MissingApiKeyScope => http::StatusCode::FORBIDDEN,
             ServerError::MatchNotStarted
+            | ServerError::MatchNotFinished
+            | ServerError::MatchesOutsideTournamentDates
+            | ServerError::NextMatchAlreadyStarted
             | ServerError::AccountAlreadyExists(_)
+            | ServerError::DrawAlreadyExists(_)
+            | ServerError::GroupAlreadyExists(_)
+            | ServerError::GroupMatchesAlreadyExist
+            | ServerError::GroupNotFinished(_)
+            | ServerError::MatchNotOnHold
             | ServerError::MatchAlreadyCompleted => http::StatusCode::CONFLICT,
+            ServerError::TooManyLoginAttempts(_) => http::StatusCode::TOO_MANY_REQUESTS,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if let ServerError::TooManyLoginAttempts(retry_after) = self {
+            response.set_header(http::header::RETRY_AFTER, retry_after.to_string());
+        }
+        response
+            .content_type("text/plain; charset=utf-8")
+            .body(self.to_string())
+    }
 }
 
 pub fn get_trace_subscriber(
@@ -116,7 +229,16 @@ pub fn init_subscriber(subscriber: impl Subscriber + Send + Sync) {
     set_global_default(subscriber).expect("Failed to set subscriber");
 }
 
-pub fn run(listener: TcpListener, db_pool: PgPool) -> io::Result<Server> {
+pub fn run(
+    listener: TcpListener,
+    db_pool: PgPool,
+    mailer: Arc<dyn Mailer>,
+    login_settings: LoginSettings,
+    webhook_settings: WebhookSettings,
+    events: Arc<TournamentEvents>,
+) -> io::Result<Server> {
+    // Shared by all workers so events reach every client following the tournament
+    let events = Data::from(events);
     let server = HttpServer::new(move || {
         let pool_clone = db_pool.clone();
         let auth = HttpAuthentication::bearer(move |req, credentials: BearerAuth| {
@@ -125,25 +247,76 @@ pub fn run(listener: TcpListener, db_pool: PgPool) -> io::Result<Server> {
         });
         App::new()
             .app_data(Data::new(db_pool.clone()))
+            .app_data(Data::new(mailer.clone()))
+            .app_data(Data::new(login_settings.clone()))
+            .app_data(Data::new(webhook_settings.clone()))
+            .app_data(events.clone())
             .wrap(TracingLogger)
             // authenticated scope
             .service(
                 web::scope("/authenticated")
                     .wrap(auth)
                     .service(insert_tournament)
+                    .service(update_tournament_endpoint)
+                    .service(patch_tournament)
+                    .service(delete_tournament_endpoint)
+                    .service(grant_tournament_role_endpoint)
+                    .service(get_tournament_roles)
+                    .service(revoke_tournament_role_endpoint)
+                    .service(create_api_key_endpoint)
+                    .service(get_api_keys)
+                    .service(revoke_api_key)
+                    .service(get_audit_log)
+                    .service(create_webhook_endpoint)
+                    .service(get_webhooks)
+                    .service(delete_webhook)
+                    .service(get_webhook_deliveries)
                     .service(insert_match)
+                    .service(update_match_format)
                     .service(insert_player)
+                    .service(insert_team)
                     .service(register_player)
                     .service(add_court_to_tournament)
                     .service(finish_match_endpoint)
-                    .service(delete_user),
+                    .service(move_in_court_queue_endpoint)
+                    .service(pull_from_court_queue_endpoint)
+                    .service(put_back_in_court_queue_endpoint)
+                    .service(correct_match_result_endpoint)
+                    .service(update_match_scheduling_endpoint)
+                    .service(get_match_result_corrections)
+                    .service(generate_tournament_draw)
+                    .service(insert_group)
+                    .service(generate_group_matches_endpoint)
+                    .service(generate_knockout_draw_endpoint)
+                    .service(delete_user)
+                    .service(logout_all)
+                    .service(change_password_endpoint)
+                    .service(get_users)
+                    .service(set_user_role_endpoint)
+                    .service(reload_signing_keys_endpoint),
             )
             .service(create_new_user)
+            .service(verify_email_endpoint)
+            .service(resend_verification_email_endpoint)
             .service(login)
+            .service(refresh)
+            .service(logout)
+            .service(forgot_password)
+            .service(reset_password_endpoint)
             .service(get_tournaments)
+            .service(get_tournament)
             .service(health_check)
             .service(get_player)
+            .service(get_team)
             .service(get_tournament_matches)
+            .service(get_tournament_events)
+            .service(get_court_queue)
+            .service(get_match_format)
+            .service(get_courts)
+            .service(get_match_scheduling)
+            .service(get_tournament_draw)
+            .service(get_tournament_groups)
+            .service(get_group_standings_endpoint)
     })
     .listen(listener)?
     .run();
@@ -0,0 +1,92 @@
+use crate::{configuration::SmtpSettings, ServerError};
+use actix_web::web;
+use async_trait::async_trait;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, Message, SmtpTransport,
+    Transport,
+};
+use std::sync::{Arc, Mutex};
+use tracing::error;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: Email) -> Result<(), ServerError>;
+}
+
+pub struct SmtpMailer {
+    sender: Mailbox,
+    transport: SmtpTransport,
+}
+
+impl SmtpMailer {
+    pub fn new(sender: &str, settings: &SmtpSettings) -> Self {
+        let transport = SmtpTransport::starttls_relay(&settings.host)
+            .expect("Failed to create SMTP transport")
+            .port(settings.port)
+            .credentials(Credentials::new(
+                settings.username.clone(),
+                settings.password.clone(),
+            ))
+            .build();
+        SmtpMailer {
+            sender: sender.parse().expect("Invalid email sender"),
+            transport,
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    #[tracing::instrument(name = "Sending email", skip(self, email), fields(to = %email.to))]
+    async fn send(&self, email: Email) -> Result<(), ServerError> {
+        let to: Mailbox = email.to.parse().map_err(|_| ServerError::InvalidEmail)?;
+        let message = Message::builder()
+            .from(self.sender.clone())
+            .to(to)
+            .subject(email.subject)
+            .body(email.body)
+            .map_err(|err| {
+                error!("Failed to build email {}", err);
+                ServerError::EmailDeliveryFailed
+            })?;
+        // The SMTP transport is blocking
+        let transport = self.transport.clone();
+        web::block(move || transport.send(&message))
+            .await
+            .map_err(|err| {
+                error!("Failed to send email {}", err);
+                ServerError::EmailDeliveryFailed
+            })?;
+        Ok(())
+    }
+}
+
+// Keeps the emails instead of sending them, used by the tests and when no SMTP server is configured
+#[derive(Debug, Default, Clone)]
+pub struct InMemoryMailer {
+    emails: Arc<Mutex<Vec<Email>>>,
+}
+
+impl InMemoryMailer {
+    pub fn sent_emails(&self) -> Vec<Email> {
+        self.emails.lock().expect("Mailer lock poisoned").clone()
+    }
+}
+
+#[async_trait]
+impl Mailer for InMemoryMailer {
+    async fn send(&self, email: Email) -> Result<(), ServerError> {
+        self.emails
+            .lock()
+            .expect("Mailer lock poisoned")
+            .push(email);
+        Ok(())
+    }
+}
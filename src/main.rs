@@ -1,9 +1,15 @@
 use sqlx::postgres::PgPoolOptions;
-use std::{io, net::TcpListener};
+use std::{io, net::TcpListener, sync::Arc};
 use tournament_tracker_backend::authentication::set_keys;
 use tournament_tracker_backend::{
-    configuration::get_configuration, get_trace_subscriber, init_subscriber, run,
+    configuration::get_configuration,
+    events::start_event_listener,
+    get_trace_subscriber, init_subscriber,
+    mailer::{InMemoryMailer, Mailer, SmtpMailer},
+    run,
+    webhooks::start_webhook_dispatcher,
 };
+use tracing::warn;
 
 #[actix_web::main]
 async fn main() -> io::Result<()> {
@@ -25,10 +31,31 @@ async fn main() -> io::Result<()> {
         .await
         .expect("Failed to migrate the database");
 
+    let events = start_event_listener(&connection_pool)
+        .await
+        .expect("Failed to listen for tournament events");
+    start_webhook_dispatcher(connection_pool.clone(), config.webhooks.clone());
+
+    let mailer: Arc<dyn Mailer> = match &config.email.smtp {
+        Some(smtp) => Arc::new(SmtpMailer::new(&config.email.sender, smtp)),
+        None => {
+            warn!("No SMTP server configured, emails won't be delivered");
+            Arc::new(InMemoryMailer::default())
+        }
+    };
+
     let listener = TcpListener::bind(format!(
         "{}:{}",
         config.application.host, config.application.port
     ))
     .expect("Failed to bind address");
-    run(listener, connection_pool)?.await
+    run(
+        listener,
+        connection_pool,
+        mailer,
+        config.login,
+        config.webhooks,
+        events,
+    )?
+    .await
 }
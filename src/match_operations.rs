@@ -1,27 +1,48 @@
-use crate::stores::court_store::pop_court_queue;
-use crate::stores::match_store::MatchResult;
+use crate::score::{Score, Side};
+use crate::stores::bracket_store::{
+    advance_player, lock_next_matches, Advancing, BracketStore, Draw,
+};
+use crate::stores::court_store::{
+    append_court_queue, assign_courts_from_queue, delete_from_court_queue, lock_court_queue,
+    reorder_court_queue, set_court_queue_on_hold,
+};
+use crate::stores::match_store::{
+    insert_match, insert_match_result, insert_match_result_correction, lock_match,
+    lock_match_result, set_match_scheduling, update_match_result, MatchOutcome, MatchResult,
+    MatchResultCorrection, MatchScheduling,
+};
+use crate::stores::tournament_store::{lock_tournament, MatchFormat, TournamentStore};
 use crate::{
     endpoints::PlayerMatchRegistrationPayload,
+    events::{publish_event, TournamentEvent},
     stores::match_store::Match,
     stores::{
         court_store::CourtStore,
         match_store::MatchStore,
-        player_registration_store::{PlayerMatchRegistration, PlayerRegistrationStore},
+        player_registration_store::{
+            insert_player_registration, PlayerMatchRegistration, PlayerRegistrationStore, Registrar,
+        },
         player_store::Player,
         player_store::PlayerStore,
+        team_store::TeamStore,
     },
     ServerError,
 };
 use chrono::{Local, NaiveDateTime};
 use futures::future;
-use once_cell::sync::Lazy;
-use regex::Regex;
 use serde::Deserialize;
 use serde::Serialize;
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 use tracing::{error, info, warn};
+use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TeamMember {
+    pub player: Player,
+    pub arrived: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct MatchInfo {
     pub id: i64,
     pub class: String,
@@ -29,23 +50,29 @@ pub struct MatchInfo {
     pub player_two: Player,
     pub player_one_arrived: bool,
     pub player_two_arrived: bool,
+    // The players of each team in doubles, empty in singles
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub player_one_team: Vec<TeamMember>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub player_two_team: Vec<TeamMember>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub court: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub winner: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub result: Option<String>,
+    // Structured form of the result
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<Score>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<MatchOutcome>,
+    // The draw the match is part of, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub draw: Option<Draw>,
     pub start_time: NaiveDateTime,
 }
 
 impl MatchInfo {
-    fn without_winner(match_data: Match, player_info: PlayerMatchInfo, court: String) -> Self {
-        MatchInfo {
-            court: Some(court),
-            ..MatchInfo::without_winner_and_court(match_data, player_info)
-        }
-    }
-
     fn without_winner_and_court(match_data: Match, player_info: PlayerMatchInfo) -> Self {
         MatchInfo {
             id: match_data.id,
@@ -55,34 +82,71 @@ impl MatchInfo {
             player_two_arrived: player_info.second_player_arrived,
             player_one: player_info.first_player,
             player_two: player_info.second_player,
+            player_one_team: player_info.first_team,
+            player_two_team: player_info.second_team,
             winner: None,
             court: None,
             result: None,
+            score: None,
+            outcome: None,
+            draw: None,
         }
     }
 
     fn with_winner(match_data: Match, player_info: PlayerMatchInfo, result: MatchResult) -> Self {
         MatchInfo {
             winner: Some(result.winner),
-            result: Some(result.result),
+            score: result.result.parse().ok(),
+            outcome: Some(result.outcome),
+            // Walkovers don't have a score
+            result: Some(result.result).filter(|result| !result.is_empty()),
             ..MatchInfo::without_winner_and_court(match_data, player_info)
         }
     }
 }
 
+// Locks the match so nobody else can finish it or check in to it until the transaction is done
+// and returns it, fails if it already has a result. The court queue is locked before the match,
+// the same order as update_match_scheduling, so the two can't deadlock.
+async fn lock_match_for_result(
+    storage: &PgPool,
+    transaction: &mut Transaction<'_, Postgres>,
+    match_id: i64,
+) -> Result<Match, ServerError> {
+    let tournament_id = storage
+        .get_match(match_id)
+        .await?
+        .ok_or(ServerError::MatchNotFound)?
+        .tournament_id;
+    lock_court_queue(transaction, tournament_id).await?;
+    let match_data = lock_match(transaction, match_id)
+        .await?
+        .ok_or(ServerError::MatchNotFound)?;
+    // The match might have been given away as a walkover
+    if lock_match_result(transaction, match_id).await?.is_some() {
+        return Err(ServerError::MatchAlreadyCompleted);
+    }
+    Ok(match_data)
+}
+
+// The changes are made in the transaction but not committed
 pub async fn register_player_to_match(
     storage: &PgPool,
+    transaction: &mut Transaction<'_, Postgres>,
     match_id: i64,
-    mut request: PlayerMatchRegistrationPayload,
+    request: PlayerMatchRegistrationPayload,
+    registrar: Registrar,
 ) -> Result<PlayerMatchRegistration, ServerError> {
-    let match_data = storage.get_match(match_id).await?;
+    let match_data = lock_match_for_result(storage, transaction, match_id).await?;
 
-    if match_data.is_none() {
-        return Err(ServerError::MatchNotFound);
-    }
-    let match_data = match_data.unwrap();
-
-    if match_data.player_one != request.player_id && match_data.player_two != request.player_id {
+    let (player_one, player_two) = match (match_data.player_one, match_data.player_two) {
+        (Some(player_one), Some(player_two)) => (player_one, player_two),
+        _ => return Err(ServerError::InvalidPlayerRegistration),
+    };
+    // Every player on the court checks in separately, in doubles that's two per side
+    let mut expected_players = get_side_players(storage, player_one).await?;
+    expected_players.extend(get_side_players(storage, player_two).await?);
+    if !expected_players.contains(&request.player_id) {
         return Err(ServerError::InvalidPlayerRegistration);
     }
 
@@ -95,18 +159,55 @@ pub async fn register_player_to_match(
         return Err(ServerError::PlayerAlreadyReigstered);
     }
 
-    let second_player_registerd = !previous_registrations.is_empty();
+    let all_players_registered = previous_registrations.len() + 1 == expected_players.len();
 
-    let registered_by = std::mem::take(&mut request.registered_by);
-    let match_registration = storage
-        .insert_player_registration(request.player_id, match_id, registered_by)
-        .await?;
+    let match_registration =
+        insert_player_registration(&mut *transaction, request.player_id, match_id, registrar)
+            .await?;
+    publish_event(
+        transaction,
+        match_data.tournament_id,
+        &TournamentEvent::PlayerCheckedIn {
+            match_id,
+            player_id: request.player_id,
+        },
+    )
+    .await?;
 
-    if second_player_registerd {
-        start_match(match_id, storage).await?;
+    if all_players_registered {
+        start_match(transaction, &match_data).await?;
     }
     Ok(match_registration)
 }
+// Both sides must be known and either be single players or teams without any shared players
+pub async fn check_valid_rooster(storage: &PgPool, match_data: &Match) -> Result<(), ServerError> {
+    let (player_one, player_two) = match (match_data.player_one, match_data.player_two) {
+        (Some(player_one), Some(player_two)) if player_one != player_two => {
+            (player_one, player_two)
+        }
+        _ => return Err(ServerError::InvalidRooster),
+    };
+    match (
+        storage.get_team(player_one).await?,
+        storage.get_team(player_two).await?,
+    ) {
+        (None, None) => Ok(()),
+        (Some(team_one), Some(team_two)) => {
+            if team_one
+                .players()
+                .iter()
+                .any(|player_id| team_two.players().contains(player_id))
+            {
+                Err(ServerError::InvalidRooster)
+            } else {
+                Ok(())
+            }
+        }
+        // Singles players can't meet a doubles team
+        _ => Err(ServerError::InvalidRooster),
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TournamentMatchList {
     pub scheduled: Vec<MatchInfo>,
@@ -120,12 +221,18 @@ pub async fn get_tournament_matches(
     storage: &PgPool,
 ) -> Result<TournamentMatchList, ServerError> {
     let query_result = storage.get_tournament_matches(tournament_id).await?;
+    let match_draws = storage.get_match_draws(tournament_id).await?;
 
     let mut finished = Vec::new();
     let mut playing = Vec::new();
     let mut scheduled = Vec::new();
 
     for match_data in query_result.into_iter() {
+        if match_data.player_one.is_none() || match_data.player_two.is_none() {
+            // Placeholder in a draw, can't be played until both players are known
+            continue;
+        }
+        let draw = match_draws.get(&match_data.id).copied();
         let match_info_future = future::join(
             get_match_player_info(storage, &match_data),
             storage.get_match_result(match_data.id),
@@ -133,15 +240,16 @@ pub async fn get_tournament_matches(
         match match_info_future.await {
             (Ok(player_match_info), Some(result)) => {
                 // The match is finished
-                finished.push(MatchInfo::with_winner(
-                    match_data,
-                    player_match_info,
-                    result,
-                ));
+                finished.push(MatchInfo {
+                    draw,
+                    ..MatchInfo::with_winner(match_data, player_match_info, result)
+                });
             }
             (Ok(player_match_info), None) => {
-                let incomplete_match_info =
-                    MatchInfo::without_winner_and_court(match_data, player_match_info);
+                let incomplete_match_info = MatchInfo {
+                    draw,
+                    ..MatchInfo::without_winner_and_court(match_data, player_match_info)
+                };
                 if let Some(court) = storage
                     .get_match_court(tournament_id, incomplete_match_info.id)
                     .await
@@ -158,12 +266,18 @@ pub async fn get_tournament_matches(
                         .await
                     {
                         // If the match has not been assigned a court and doesn't have a winner it hasn't started
-                        Ok(queue_placement) => {
+                        Ok(Some(queue_placement)) => {
                             scheduled.push(MatchInfo {
                                 court: Some(get_placement_string(queue_placement)),
                                 ..incomplete_match_info
                             });
                         }
+                        Ok(None) => {
+                            scheduled.push(MatchInfo {
+                                court: Some(ON_HOLD_PLACEMENT.into()),
+                                ..incomplete_match_info
+                            });
+                        }
                         Err(sqlx::Error::RowNotFound) => {
                             error!(
                                 "Match {} should be in the court queue!",
@@ -184,103 +298,447 @@ pub async fn get_tournament_matches(
     })
 }
 
-#[tracing::instrument(name = "Start match", skip(storage))]
-pub async fn start_match(match_id: i64, storage: &PgPool) -> Result<MatchInfo, ServerError> {
-    let match_data = storage.get_match(match_id).await?;
-
-    if match_data.is_none() {
-        return Err(ServerError::MatchNotFound);
+// The match is inserted in the transaction but not committed
+#[tracing::instrument(name = "Create match", skip(storage, transaction))]
+pub async fn create_match(
+    storage: &PgPool,
+    transaction: &mut Transaction<'_, Postgres>,
+    match_data: Match,
+) -> Result<i64, ServerError> {
+    check_valid_rooster(storage, &match_data).await?;
+    let tournament_id = match_data.tournament_id;
+    // Locked so the dates can't change before the match is inserted
+    let tournament = lock_tournament(&mut *transaction, tournament_id)
+        .await?
+        .ok_or(ServerError::TournamentNotFound)?;
+    let match_date = match_data.start_time.date();
+    if match_date < tournament.start_date || match_date > tournament.end_date {
+        return Err(ServerError::InvalidStartTime);
     }
-    let match_data = match_data.unwrap();
+    let id = insert_match(&mut *transaction, &match_data).await?;
+    publish_event(
+        transaction,
+        tournament_id,
+        &TournamentEvent::MatchCreated {
+            match_data: Match { id, ..match_data },
+        },
+    )
+    .await?;
+    Ok(id)
+}
 
-    // check if court alreay has assigned court
-    if storage
-        .get_match_court(match_data.tournament_id, match_data.id)
+// Gives the match a free court that suits it, otherwise it waits in the court queue
+#[tracing::instrument(name = "Start match", skip(transaction))]
+async fn start_match(
+    transaction: &mut Transaction<'_, Postgres>,
+    match_data: &Match,
+) -> Result<(), ServerError> {
+    let tournament_id = match_data.tournament_id;
+    if (&mut *transaction)
+        .get_match_court(tournament_id, match_data.id)
         .await
         .is_some()
     {
         return Err(ServerError::MatchAlreadyStarted);
     }
 
-    // check player registration
-    let player_info = get_match_player_info(storage, &match_data).await?;
-    // if no court assigned and players are present
-    // try to assign free court
-    if let Ok(assigned_court) = storage
-        .try_assign_free_court(match_data.tournament_id, match_data.id)
+    if let Ok(court) = (&mut *transaction)
+        .try_assign_free_court(tournament_id, match_data.id)
         .await
     {
-        Ok(MatchInfo {
-            start_time: Local::now().naive_local(),
-            ..MatchInfo::without_winner(match_data, player_info, assigned_court)
-        })
+        publish_event(
+            transaction,
+            tournament_id,
+            &TournamentEvent::MatchStarted {
+                match_id: match_data.id,
+                court,
+            },
+        )
+        .await?;
     } else {
-        let court =
-            append_to_queue_and_get_placement(storage, match_data.tournament_id, match_id).await?;
-        Ok(MatchInfo::without_winner(match_data, player_info, court))
+        append_court_queue(&mut *transaction, tournament_id, match_data.id).await?;
+        publish_court_queue(transaction, tournament_id).await?;
     }
+    Ok(())
 }
 
-#[tracing::instrument(name = "Finish match", skip(storage))]
+#[tracing::instrument(name = "Finish match", skip(storage, transaction))]
 pub async fn finish_match(
     match_id: i64,
     result: MatchResult,
     storage: &PgPool,
+    transaction: &mut Transaction<'_, Postgres>,
 ) -> Result<MatchInfo, ServerError> {
-    let match_data = storage.get_match(match_id).await?;
+    let match_data = lock_match_for_result(storage, transaction, match_id).await?;
 
-    let match_data = match match_data {
-        Some(data) => data,
-        None => return Err(ServerError::MatchNotFound),
-    };
+    let match_format = storage
+        .get_match_format(match_data.tournament_id)
+        .await?
+        .unwrap_or_default();
+    // The result is stored with the score in the normalized string form
+    let result = check_valid_match_result(result, &match_data, &match_format)?;
 
-    if storage.get_match_result(match_id).await.is_some() {
-        return Err(ServerError::MatchAlreadyCompleted);
-    }
-
-    let _ = check_valid_match_result(&result, &match_data)?;
-
-    if storage
+    let court = storage
         .get_match_court(match_data.tournament_id, match_id)
-        .await
-        .is_none()
+        .await;
+    // Walkovers and disqualifications may be given before the match is started
+    if court.is_none()
+        && result.outcome != MatchOutcome::Walkover
+        && result.outcome != MatchOutcome::Disqualified
     {
         return Err(ServerError::MatchNotStarted);
     }
 
-    storage.insert_match_result(match_id, &result).await?;
-    // will rollback if dropped -> failures will result in rollback
-    // 1. create transaction
-    // 2. remove court assoication to the match
-    // 3. pop court queue
-    // 4. assign next match in the queue the free court
-    let mut transaction = storage.begin().await?;
-    let _ = transaction
-        .remove_assigned_court(match_data.tournament_id, match_id)
-        .await?;
-    if let Some(waiting_match) = pop_court_queue(&mut transaction, match_data.tournament_id).await?
-    {
-        // There should always exist a free court here since the finished match just
-        // freed up the court it used
-        let court_name = transaction
-            .try_assign_free_court(match_data.tournament_id, waiting_match)
+    // The changes are made in the transaction but not committed, failures roll them back
+    // 1. store the result and advance the winner and loser if the match is part of a draw
+    // 2. remove court assoication to the match, or remove it from the queue if it never got a court
+    // 3. assign the free court to the first match in the queue that it suits
+    // 4. publish the events, they are only sent if the transaction is committed
+    let player_info = get_match_player_info(storage, &match_data).await?;
+    let mut started_matches = Vec::new();
+    insert_match_result(&mut *transaction, match_id, &result).await?;
+    let advancing_players = [
+        (Advancing::Winner, Some(result.winner)),
+        (Advancing::Loser, get_loser(&match_data, result.winner)),
+    ];
+    for (advancing, player_id) in advancing_players.iter() {
+        let player_id = match player_id {
+            Some(player_id) => *player_id,
+            None => continue,
+        };
+        if let Some(next_match) =
+            advance_player(&mut *transaction, match_id, *advancing, player_id).await?
+        {
+            info!("Player: {} advanced to match: {}", player_id, next_match.id);
+            if next_match.player_one.is_some() && next_match.player_two.is_some() {
+                info!("Match: {} is ready to be played", next_match.id);
+            }
+            publish_event(
+                transaction,
+                next_match.tournament_id,
+                &TournamentEvent::MatchCreated {
+                    match_data: next_match,
+                },
+            )
             .await?;
-        info!(
-            "Assigning court: {} to match: {}",
-            court_name, waiting_match
-        );
-        transaction.commit().await.map_err(|err| {
-            error!("Transaction failed!");
-            err
-        })?;
+        }
+    }
+    if court.is_some() {
+        let _ = (&mut *transaction)
+            .remove_assigned_court(match_data.tournament_id, match_id)
+            .await?;
+        started_matches =
+            assign_courts_from_queue(&mut *transaction, match_data.tournament_id).await?;
     } else {
-        transaction.commit().await.map_err(|err| {
-            error!("Transaction failed!");
-            err
-        })?;
+        // No court is freed up, the match just shouldn't wait for one anymore
+        delete_from_court_queue(&mut *transaction, match_data.tournament_id, match_id).await?;
     }
+    let tournament_id = match_data.tournament_id;
+    let match_info = MatchInfo::with_winner(match_data, player_info, result);
+    publish_event(
+        transaction,
+        tournament_id,
+        &TournamentEvent::MatchFinished {
+            match_info: match_info.clone(),
+        },
+    )
+    .await?;
+    let queue_changed = !started_matches.is_empty() || court.is_none();
+    publish_started_matches(transaction, tournament_id, started_matches).await?;
+    if queue_changed {
+        publish_court_queue(transaction, tournament_id).await?;
+    }
+    Ok(match_info)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CourtQueueMatch {
+    // Starts at 1, missing for matches pulled out of the queue
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<usize>,
+    pub on_hold: bool,
+    // When the match joined the queue
+    pub place_in_queue: NaiveDateTime,
+    pub match_info: MatchInfo,
+}
+
+// The matches waiting for a court in the order they will get one, followed by
+// the matches that have been pulled out of the queue
+#[tracing::instrument(name = "Get court queue", skip(storage))]
+pub async fn get_court_queue_matches(
+    tournament_id: i32,
+    storage: &PgPool,
+) -> Result<Vec<CourtQueueMatch>, ServerError> {
+    if storage.get_tournament(tournament_id).await?.is_none() {
+        return Err(ServerError::TournamentNotFound);
+    }
+    let entries = storage.get_court_queue_entries(tournament_id).await?;
+    let mut queue = Vec::with_capacity(entries.len());
+    let mut position = 0;
+    for entry in entries.into_iter() {
+        let match_data = match storage.get_match(entry.match_id).await? {
+            Some(match_data) => match_data,
+            None => {
+                error!("Match {} in the court queue not found!", entry.match_id);
+                continue;
+            }
+        };
+        let player_info = get_match_player_info(storage, &match_data).await?;
+        let position = if entry.on_hold {
+            None
+        } else {
+            position += 1;
+            Some(position)
+        };
+        queue.push(CourtQueueMatch {
+            position,
+            on_hold: entry.on_hold,
+            place_in_queue: entry.place_in_queue,
+            match_info: MatchInfo::without_winner_and_court(match_data, player_info),
+        });
+    }
+    Ok(queue)
+}
+
+// Moves a waiting match to the position, starting at 1, the matches in between are moved back
+#[tracing::instrument(name = "Move match in court queue", skip(transaction))]
+pub async fn move_in_court_queue(
+    tournament_id: i32,
+    match_id: i64,
+    position: usize,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(), ServerError> {
+    let mut waiting = waiting_matches(transaction, tournament_id).await?;
+    let index = waiting
+        .iter()
+        .position(|queued| *queued == match_id)
+        .ok_or(ServerError::MatchNotInQueue)?;
+    if position == 0 || position > waiting.len() {
+        return Err(ServerError::InvalidQueuePosition);
+    }
+    waiting.remove(index);
+    waiting.insert(position - 1, match_id);
+    reorder_court_queue(&mut *transaction, tournament_id, &waiting).await?;
+    publish_court_queue(transaction, tournament_id).await?;
+    info!("Moved match: {} to queue position: {}", match_id, position);
+    Ok(())
+}
+
+// The match keeps its row in the queue but won't get a court until it's put back
+#[tracing::instrument(name = "Pull match from court queue", skip(transaction))]
+pub async fn pull_from_court_queue(
+    tournament_id: i32,
+    match_id: i64,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(), ServerError> {
+    let mut waiting = waiting_matches(transaction, tournament_id).await?;
+    let index = waiting
+        .iter()
+        .position(|queued| *queued == match_id)
+        .ok_or(ServerError::MatchNotInQueue)?;
+    waiting.remove(index);
+    set_court_queue_on_hold(&mut *transaction, tournament_id, match_id, true).await?;
+    reorder_court_queue(&mut *transaction, tournament_id, &waiting).await?;
+    publish_court_queue(transaction, tournament_id).await?;
+    info!("Pulled match: {} from the court queue", match_id);
+    Ok(())
+}
+
+// Puts a pulled match back at the position, starting at 1, or last in the queue.
+// It gets a court right away if a free one suits it.
+#[tracing::instrument(name = "Put match back in court queue", skip(transaction))]
+pub async fn put_back_in_court_queue(
+    tournament_id: i32,
+    match_id: i64,
+    position: Option<usize>,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(), ServerError> {
+    let entries = lock_court_queue(transaction, tournament_id).await?;
+    match entries.iter().find(|entry| entry.match_id == match_id) {
+        Some(entry) if entry.on_hold => {}
+        Some(_) => return Err(ServerError::MatchNotOnHold),
+        None => return Err(ServerError::MatchNotInQueue),
+    }
+    let mut waiting: Vec<i64> = entries
+        .iter()
+        .filter(|entry| !entry.on_hold)
+        .map(|entry| entry.match_id)
+        .collect();
+    let position = position.unwrap_or(waiting.len() + 1);
+    if position == 0 || position > waiting.len() + 1 {
+        return Err(ServerError::InvalidQueuePosition);
+    }
+    waiting.insert(position - 1, match_id);
+    set_court_queue_on_hold(&mut *transaction, tournament_id, match_id, false).await?;
+    reorder_court_queue(&mut *transaction, tournament_id, &waiting).await?;
+    // Courts may have been left free while the match was on hold
+    let started_matches = assign_courts_from_queue(&mut *transaction, tournament_id).await?;
+    publish_started_matches(transaction, tournament_id, started_matches).await?;
+    publish_court_queue(transaction, tournament_id).await?;
+    info!("Put match: {} back in the court queue", match_id);
+    Ok(())
+}
+
+// A waiting match is moved in the queue if its priority changes and it gets a court
+// right away if a free one suits it now
+#[tracing::instrument(name = "Update match scheduling", skip(storage, transaction))]
+pub async fn update_match_scheduling(
+    match_id: i64,
+    scheduling: MatchScheduling,
+    storage: &PgPool,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(), ServerError> {
+    let match_data = storage
+        .get_match(match_id)
+        .await?
+        .ok_or(ServerError::MatchNotFound)?;
+    let tournament_id = match_data.tournament_id;
+    if let Some(preferred_court) = &scheduling.preferred_court {
+        let courts = storage.get_courts(tournament_id).await?;
+        if !courts
+            .iter()
+            .any(|court| &court.court_name == preferred_court)
+        {
+            return Err(ServerError::InvalidMatchScheduling);
+        }
+    } else if scheduling.court_required {
+        return Err(ServerError::InvalidMatchScheduling);
+    }
+
+    let entries = lock_court_queue(transaction, tournament_id).await?;
+    set_match_scheduling(&mut *transaction, match_id, &scheduling).await?;
+    let previous_priority = match entries
+        .iter()
+        .find(|entry| entry.match_id == match_id && !entry.on_hold)
+    {
+        Some(entry) => entry.priority,
+        None => return Ok(()),
+    };
+    if previous_priority != scheduling.priority {
+        // Placed after the other matches with the same or a higher priority
+        let waiting: Vec<_> = entries
+            .iter()
+            .filter(|entry| !entry.on_hold && entry.match_id != match_id)
+            .collect();
+        let index = waiting
+            .iter()
+            .rposition(|entry| entry.priority >= scheduling.priority)
+            .map_or(0, |index| index + 1);
+        let mut waiting: Vec<i64> = waiting.iter().map(|entry| entry.match_id).collect();
+        waiting.insert(index, match_id);
+        reorder_court_queue(&mut *transaction, tournament_id, &waiting).await?;
+    }
+    let started_matches = assign_courts_from_queue(&mut *transaction, tournament_id).await?;
+    publish_started_matches(transaction, tournament_id, started_matches).await?;
+    publish_court_queue(transaction, tournament_id).await?;
+    Ok(())
+}
+
+// The waiting matches in queue order, the queue is locked until the transaction is done
+async fn waiting_matches(
+    transaction: &mut Transaction<'_, Postgres>,
+    tournament_id: i32,
+) -> Result<Vec<i64>, sqlx::Error> {
+    Ok(lock_court_queue(transaction, tournament_id)
+        .await?
+        .into_iter()
+        .filter(|entry| !entry.on_hold)
+        .map(|entry| entry.match_id)
+        .collect())
+}
+
+#[tracing::instrument(name = "Correct match result", skip(storage))]
+pub async fn correct_match_result(
+    match_id: i64,
+    result: MatchResult,
+    corrected_by: Uuid,
+    storage: &PgPool,
+) -> Result<MatchInfo, ServerError> {
+    let match_data = match storage.get_match(match_id).await? {
+        Some(data) => data,
+        None => return Err(ServerError::MatchNotFound),
+    };
+
+    let match_format = storage
+        .get_match_format(match_data.tournament_id)
+        .await?
+        .unwrap_or_default();
+    let result = check_valid_match_result(result, &match_data, &match_format)?;
+
+    // The result and the next matches are locked so neither another correction nor
+    // checking in to a next match can happen before this is committed
     let player_info = get_match_player_info(storage, &match_data).await?;
-    Ok(MatchInfo::with_winner(match_data, player_info, result))
+    let mut transaction = storage.begin().await?;
+    let previous_result = match lock_match_result(&mut transaction, match_id).await? {
+        Some(previous_result) => previous_result,
+        None => return Err(ServerError::MatchNotFinished),
+    };
+    let started_next_matches = lock_next_matches(&mut transaction, match_id).await?;
+
+    // The player that advances from the match given the winner
+    let advancing_player = |winner: i64, advancing: Advancing| match advancing {
+        Advancing::Winner => Some(winner),
+        Advancing::Loser => get_loser(&match_data, winner),
+    };
+    // Next matches that got the wrong player advanced into them, they can only be fixed
+    // as long as they haven't started
+    let mut changed_progressions = Vec::new();
+    for progression in transaction.get_match_progressions(match_id).await? {
+        let player_id = advancing_player(result.winner, progression.advancing);
+        if player_id == advancing_player(previous_result.winner, progression.advancing) {
+            continue;
+        }
+        if started_next_matches.contains(&progression.to_match_id) {
+            return Err(ServerError::NextMatchAlreadyStarted);
+        }
+        changed_progressions.push((progression.advancing, player_id));
+    }
+
+    let correction = MatchResultCorrection {
+        match_id,
+        previous_result: previous_result.result,
+        previous_winner: previous_result.winner,
+        previous_outcome: previous_result.outcome,
+        corrected_by: Some(corrected_by),
+        corrected_at: Local::now().naive_local(),
+    };
+    insert_match_result_correction(&mut transaction, &correction).await?;
+    update_match_result(&mut transaction, match_id, &result).await?;
+    for (advancing, player_id) in changed_progressions.into_iter() {
+        if let Some(player_id) = player_id {
+            if let Some(next_match) =
+                advance_player(&mut transaction, match_id, advancing, player_id).await?
+            {
+                info!(
+                    "Player: {} advanced to match: {} instead",
+                    player_id, next_match.id
+                );
+                publish_event(
+                    &mut transaction,
+                    next_match.tournament_id,
+                    &TournamentEvent::MatchCreated {
+                        match_data: next_match,
+                    },
+                )
+                .await?;
+            }
+        }
+    }
+    let tournament_id = match_data.tournament_id;
+    let match_info = MatchInfo::with_winner(match_data, player_info, result);
+    publish_event(
+        &mut transaction,
+        tournament_id,
+        &TournamentEvent::MatchFinished {
+            match_info: match_info.clone(),
+        },
+    )
+    .await?;
+    transaction
+        .commit()
+        .await
+        .inspect_err(|_| error!("Transaction failed!"))?;
+    Ok(match_info)
 }
 
 // HELPERS:
@@ -288,56 +746,154 @@ pub async fn finish_match(
 struct PlayerMatchInfo {
     first_player: Player,
     first_player_arrived: bool,
+    first_team: Vec<TeamMember>,
     second_player: Player,
     second_player_arrived: bool,
+    second_team: Vec<TeamMember>,
+}
+
+// The players that need to check in for a side of a match
+async fn get_side_players<S: TeamStore>(
+    storage: &S,
+    player_id: i64,
+) -> Result<Vec<i64>, ServerError> {
+    match storage.get_team(player_id).await? {
+        Some(team) => Ok(team.players().to_vec()),
+        None => Ok(vec![player_id]),
+    }
+}
+
+// Empty if the player isn't a team
+async fn get_team_members<S: PlayerStore + TeamStore>(
+    storage: &S,
+    player_id: i64,
+    registrations: &[PlayerMatchRegistration],
+) -> Result<Vec<TeamMember>, ServerError> {
+    let team = match storage.get_team(player_id).await? {
+        Some(team) => team,
+        None => return Ok(Vec::new()),
+    };
+    let mut members = Vec::with_capacity(2);
+    for member_id in team.players().iter() {
+        let player = storage
+            .get_player(*member_id)
+            .await?
+            .ok_or(ServerError::PlayerNotFound)?;
+        let arrived = registrations
+            .iter()
+            .any(|registration| registration.player_id == player.id);
+        members.push(TeamMember { player, arrived });
+    }
+    Ok(members)
 }
 
-async fn get_match_player_info<S: PlayerStore + PlayerRegistrationStore>(
+async fn get_match_player_info<S: PlayerStore + PlayerRegistrationStore + TeamStore>(
     storage: &S,
     match_data: &Match,
 ) -> Result<PlayerMatchInfo, ServerError> {
+    let (player_one, player_two) = match (match_data.player_one, match_data.player_two) {
+        (Some(player_one), Some(player_two)) => (player_one, player_two),
+        _ => return Err(ServerError::PlayerMissing),
+    };
     if let (Ok(Some(first_player)), Ok(Some(second_player))) = future::join(
-        storage.get_player(match_data.player_one),
-        storage.get_player(match_data.player_two),
+        storage.get_player(player_one),
+        storage.get_player(player_two),
     )
     .await
     {
         let registered_players = storage.get_registered_players(match_data.id).await?;
-        let mut first_player_arrived = false;
-        let mut second_player_arrived = false;
-
-        for registration in registered_players.iter() {
-            if registration.player_id == first_player.id {
-                first_player_arrived = true;
-            } else if registration.player_id == second_player.id {
-                second_player_arrived = true;
+        let first_team = get_team_members(storage, first_player.id, &registered_players).await?;
+        let second_team = get_team_members(storage, second_player.id, &registered_players).await?;
+        // A team has arrived once both of its players have
+        let has_arrived = |player: &Player, team: &[TeamMember]| {
+            if team.is_empty() {
+                registered_players
+                    .iter()
+                    .any(|registration| registration.player_id == player.id)
+            } else {
+                team.iter().all(|member| member.arrived)
             }
-        }
+        };
 
         Ok(PlayerMatchInfo {
-            first_player_arrived,
-            second_player_arrived,
+            first_player_arrived: has_arrived(&first_player, &first_team),
+            second_player_arrived: has_arrived(&second_player, &second_team),
             first_player,
+            first_team,
             second_player,
+            second_team,
         })
     } else {
         Err(ServerError::PlayerNotFound)
     }
 }
 
-const PATTERN: &str = r"^([0-9]+-[0-9]+(\([0-9]+\))?\s{1})+([0-9]+-[0-9]+(\([0-9]+\))?)$";
-static RESULT_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(PATTERN).expect("Regex is invalid"));
+fn check_valid_match_result(
+    result: MatchResult,
+    match_data: &Match,
+    match_format: &MatchFormat,
+) -> Result<MatchResult, ServerError> {
+    if match_data.player_one != Some(result.winner) && match_data.player_two != Some(result.winner)
+    {
+        return Err(ServerError::InvalidWinner);
+    }
+    if match_data.player_one.is_none() || match_data.player_two.is_none() {
+        return Err(ServerError::PlayerMissing);
+    }
+    let parse_score = |result: &str| {
+        result.parse::<Score>().map_err(|err| {
+            warn!("Invalid score: {}", err);
+            ServerError::InvalidResult
+        })
+    };
+    let result_string = match result.outcome {
+        MatchOutcome::Completed => {
+            let score = parse_score(&result.result)?;
+            let score_winner = score.validate(match_format).map_err(|err| {
+                warn!("Invalid score: {}", err);
+                ServerError::InvalidResult
+            })?;
+            let winner = match score_winner {
+                Side::PlayerOne => match_data.player_one,
+                Side::PlayerTwo => match_data.player_two,
+            };
+            if winner != Some(result.winner) {
+                return Err(ServerError::WinnerDoesNotMatchScore);
+            }
+            score.to_string()
+        }
+        MatchOutcome::Walkover => {
+            if !result.result.trim().is_empty() {
+                return Err(ServerError::InvalidResult);
+            }
+            String::new()
+        }
+        // The partial score at the time the match was stopped, if any
+        MatchOutcome::Retired | MatchOutcome::Disqualified => {
+            if result.result.trim().is_empty() {
+                String::new()
+            } else {
+                parse_score(&result.result)?.to_string()
+            }
+        }
+    };
+    Ok(MatchResult {
+        result: result_string,
+        ..result
+    })
+}
 
-fn check_valid_match_result(result: &MatchResult, match_data: &Match) -> Result<(), ServerError> {
-    if result.winner != match_data.player_one && result.winner != match_data.player_two {
-        Err(ServerError::InvalidWinner)
-    } else if !RESULT_REGEX.is_match(&result.result.trim()) {
-        Err(ServerError::InvalidResult)
+fn get_loser(match_data: &Match, winner: i64) -> Option<i64> {
+    if match_data.player_one == Some(winner) {
+        match_data.player_two
     } else {
-        Ok(())
+        match_data.player_one
     }
 }
 
+// Shown instead of the queue placement for matches pulled out of the queue
+const ON_HOLD_PLACEMENT: &str = "Tillfälligt ur kön";
+
 fn get_placement_string(placement: usize) -> String {
     match placement {
         1 => "Först i kön",
@@ -347,14 +903,31 @@ fn get_placement_string(placement: usize) -> String {
     .into()
 }
 
-async fn append_to_queue_and_get_placement(
-    storage: &PgPool,
+async fn publish_started_matches(
+    transaction: &mut Transaction<'_, Postgres>,
     tournament_id: i32,
-    match_id: i64,
-) -> Result<String, sqlx::Error> {
-    storage.append_court_queue(tournament_id, match_id).await?;
-    let placement = storage
-        .get_court_queue_placement(tournament_id, match_id)
+    started_matches: Vec<(i64, String)>,
+) -> Result<(), sqlx::Error> {
+    for (match_id, court) in started_matches.into_iter() {
+        publish_event(
+            transaction,
+            tournament_id,
+            &TournamentEvent::MatchStarted { match_id, court },
+        )
         .await?;
-    Ok(get_placement_string(placement))
+    }
+    Ok(())
+}
+
+async fn publish_court_queue(
+    transaction: &mut Transaction<'_, Postgres>,
+    tournament_id: i32,
+) -> Result<(), sqlx::Error> {
+    let queue = (&mut *transaction).get_court_queue(tournament_id).await?;
+    publish_event(
+        transaction,
+        tournament_id,
+        &TournamentEvent::QueueChanged { queue },
+    )
+    .await
 }
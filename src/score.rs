@@ -0,0 +1,180 @@
+use crate::stores::tournament_store::MatchFormat;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+// Games in a set are counted from player one's perspective.
+// A match tiebreak counts points instead of games.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SetScore {
+    pub player_one: u8,
+    pub player_two: u8,
+    // Points of the player that lost the tiebreak, ex: 7-6(5)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tiebreak: Option<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Side {
+    PlayerOne,
+    PlayerTwo,
+}
+
+impl SetScore {
+    // A regular set never goes past 7 games
+    pub fn is_match_tiebreak(&self) -> bool {
+        self.player_one.max(self.player_two) > 7
+    }
+
+    pub fn winner(&self) -> Side {
+        if self.player_one > self.player_two {
+            Side::PlayerOne
+        } else {
+            Side::PlayerTwo
+        }
+    }
+
+    // Games won by each player, a match tiebreak counts as a single game
+    pub fn games(&self) -> (u32, u32) {
+        match (self.is_match_tiebreak(), self.winner()) {
+            (true, Side::PlayerOne) => (1, 0),
+            (true, Side::PlayerTwo) => (0, 1),
+            (false, _) => (self.player_one as u32, self.player_two as u32),
+        }
+    }
+
+    fn validate_set(&self) -> Result<(), String> {
+        let most = self.player_one.max(self.player_two);
+        let least = self.player_one.min(self.player_two);
+        let finished = match (most, least) {
+            (6, 0..=4) | (7, 5) => self.tiebreak.is_none(),
+            // Tiebreak at 6-6
+            (7, 6) => true,
+            _ => false,
+        };
+        if finished {
+            Ok(())
+        } else {
+            Err(format!("{} isn't a finished set", self))
+        }
+    }
+
+    fn validate_match_tiebreak(&self) -> Result<(), String> {
+        let most = self.player_one.max(self.player_two);
+        let least = self.player_one.min(self.player_two);
+        // First to 10 points with a margin of two
+        let finished = self.tiebreak.is_none()
+            && most >= 10
+            && most - least >= 2
+            && (most == 10 || most - least == 2);
+        if finished {
+            Ok(())
+        } else {
+            Err(format!("{} isn't a finished match tiebreak", self))
+        }
+    }
+}
+
+impl fmt::Display for SetScore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.player_one, self.player_two)?;
+        if let Some(points) = self.tiebreak {
+            write!(f, "({})", points)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SetScore {
+    type Err = String;
+
+    // ex: "6-3", "7-6(5)" or "10-8"
+    fn from_str(set: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid set score: {}", set);
+        let (games, tiebreak) = match set.find('(') {
+            Some(start) => {
+                let points = set[start + 1..]
+                    .strip_suffix(')')
+                    .and_then(|points| points.parse().ok())
+                    .ok_or_else(invalid)?;
+                (&set[..start], Some(points))
+            }
+            None => (set, None),
+        };
+        let mut games = games.split('-');
+        let (player_one, player_two) = match (games.next(), games.next(), games.next()) {
+            (Some(player_one), Some(player_two), None) => (player_one, player_two),
+            _ => return Err(invalid()),
+        };
+        Ok(SetScore {
+            player_one: player_one.parse().map_err(|_| invalid())?,
+            player_two: player_two.parse().map_err(|_| invalid())?,
+            tiebreak,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Score {
+    pub sets: Vec<SetScore>,
+}
+
+impl Score {
+    // Checks that the score is a finished match in the given format and returns the winner
+    pub fn validate(&self, format: &MatchFormat) -> Result<Side, String> {
+        // More than half of the sets
+        let sets_to_win = format.best_of as usize / 2 + 1;
+        let deciding_set = sets_to_win * 2 - 2;
+        let mut player_one_sets = 0;
+        let mut player_two_sets = 0;
+
+        for (index, set) in self.sets.iter().enumerate() {
+            if player_one_sets == sets_to_win || player_two_sets == sets_to_win {
+                return Err(format!(
+                    "The match was already decided before set {}",
+                    index + 1
+                ));
+            }
+            if format.match_tiebreak && index == deciding_set {
+                set.validate_match_tiebreak()?;
+            } else {
+                set.validate_set()?;
+            }
+            match set.winner() {
+                Side::PlayerOne => player_one_sets += 1,
+                Side::PlayerTwo => player_two_sets += 1,
+            }
+        }
+
+        if player_one_sets == sets_to_win {
+            Ok(Side::PlayerOne)
+        } else if player_two_sets == sets_to_win {
+            Ok(Side::PlayerTwo)
+        } else {
+            Err("Nobody has won the match".to_string())
+        }
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sets: Vec<String> = self.sets.iter().map(SetScore::to_string).collect();
+        write!(f, "{}", sets.join(" "))
+    }
+}
+
+impl FromStr for Score {
+    type Err = String;
+
+    // Sets are separated by whitespace, ex: "6-3 4-6 7-6(5)"
+    fn from_str(score: &str) -> Result<Self, Self::Err> {
+        let sets = score
+            .split_whitespace()
+            .map(SetScore::from_str)
+            .collect::<Result<Vec<SetScore>, String>>()?;
+        if sets.is_empty() {
+            return Err("The score is empty".to_string());
+        }
+        Ok(Score { sets })
+    }
+}
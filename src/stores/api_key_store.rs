@@ -0,0 +1,190 @@
+#![allow(clippy::toplevel_ref_arg)]
+use async_trait::async_trait;
+use chrono::{Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use sqlx::{Done, PgPool};
+use std::str::FromStr;
+use tracing::error;
+use uuid::Uuid;
+
+// The actions an API key is allowed to do in its tournament
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeyScope {
+    RegisterPlayer,
+    FinishMatch,
+}
+
+impl ApiKeyScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiKeyScope::RegisterPlayer => "register_player",
+            ApiKeyScope::FinishMatch => "finish_match",
+        }
+    }
+}
+
+impl FromStr for ApiKeyScope {
+    type Err = String;
+
+    fn from_str(scope: &str) -> Result<Self, Self::Err> {
+        match scope {
+            "register_player" => Ok(ApiKeyScope::RegisterPlayer),
+            "finish_match" => Ok(ApiKeyScope::FinishMatch),
+            _ => Err(format!("Unknown api key scope: {}", scope)),
+        }
+    }
+}
+
+// Never contains the key itself, it's only shown once when the key is created
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub tournament_id: i32,
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub created_by: Option<Uuid>,
+    pub created_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct ApiKeyRow {
+    id: Uuid,
+    tournament_id: i32,
+    name: String,
+    scopes: Vec<String>,
+    created_by: Option<Uuid>,
+    created_at: NaiveDateTime,
+    revoked_at: Option<NaiveDateTime>,
+}
+
+impl From<ApiKeyRow> for ApiKey {
+    fn from(row: ApiKeyRow) -> Self {
+        ApiKey {
+            id: row.id,
+            tournament_id: row.tournament_id,
+            name: row.name,
+            scopes: row
+                .scopes
+                .iter()
+                .filter_map(|scope| {
+                    scope
+                        .parse()
+                        .map_err(|err| error!("Invalid stored api key scope: {}", err))
+                        .ok()
+                })
+                .collect(),
+            created_by: row.created_by,
+            created_at: row.created_at,
+            revoked_at: row.revoked_at,
+        }
+    }
+}
+
+#[async_trait]
+pub trait ApiKeyStore {
+    async fn insert_api_key(
+        &self,
+        tournament_id: i32,
+        name: &str,
+        key_hash: &str,
+        scopes: &[ApiKeyScope],
+        created_by: Uuid,
+    ) -> Result<ApiKey, sqlx::Error>;
+    async fn get_api_keys(&self, tournament_id: i32) -> Result<Vec<ApiKey>, sqlx::Error>;
+    // Revoked keys aren't returned
+    async fn find_active_api_key(&self, key_hash: &str) -> Result<Option<ApiKey>, sqlx::Error>;
+    // Returns false if the tournament has no active key with the id
+    async fn revoke_api_key(&self, tournament_id: i32, id: Uuid) -> Result<bool, sqlx::Error>;
+}
+
+#[async_trait]
+impl ApiKeyStore for PgPool {
+    #[tracing::instrument(name = "Inserting api key", skip(self, key_hash))]
+    async fn insert_api_key(
+        &self,
+        tournament_id: i32,
+        name: &str,
+        key_hash: &str,
+        scopes: &[ApiKeyScope],
+        created_by: Uuid,
+    ) -> Result<ApiKey, sqlx::Error> {
+        let scopes: Vec<String> = scopes
+            .iter()
+            .map(|scope| scope.as_str().to_string())
+            .collect();
+        let row = sqlx::query_as!(
+            ApiKeyRow,
+            "INSERT INTO api_keys (id, tournament_id, name, key_hash, scopes, created_by, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id, tournament_id, name, scopes, created_by, created_at, revoked_at",
+            Uuid::new_v4(),
+            tournament_id,
+            name,
+            key_hash,
+            &scopes,
+            created_by,
+            Local::now().naive_local()
+        )
+        .fetch_one(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to insert api key {}", err);
+            err
+        })?;
+        Ok(row.into())
+    }
+
+    #[tracing::instrument(name = "Fetching api keys", skip(self))]
+    async fn get_api_keys(&self, tournament_id: i32) -> Result<Vec<ApiKey>, sqlx::Error> {
+        let rows = sqlx::query_as!(
+            ApiKeyRow,
+            "SELECT id, tournament_id, name, scopes, created_by, created_at, revoked_at
+            FROM api_keys WHERE tournament_id = $1 ORDER BY created_at ASC",
+            tournament_id
+        )
+        .fetch_all(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to fetch api keys {}", err);
+            err
+        })?;
+        Ok(rows.into_iter().map(ApiKey::from).collect())
+    }
+
+    #[tracing::instrument(name = "Finding api key", skip(self, key_hash))]
+    async fn find_active_api_key(&self, key_hash: &str) -> Result<Option<ApiKey>, sqlx::Error> {
+        let row = sqlx::query_as!(
+            ApiKeyRow,
+            "SELECT id, tournament_id, name, scopes, created_by, created_at, revoked_at
+            FROM api_keys WHERE key_hash = $1 AND revoked_at IS NULL",
+            key_hash
+        )
+        .fetch_optional(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to find api key {}", err);
+            err
+        })?;
+        Ok(row.map(ApiKey::from))
+    }
+
+    #[tracing::instrument(name = "Revoking api key", skip(self))]
+    async fn revoke_api_key(&self, tournament_id: i32, id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE api_keys SET revoked_at = $1
+            WHERE tournament_id = $2 AND id = $3 AND revoked_at IS NULL",
+            Local::now().naive_local(),
+            tournament_id,
+            id
+        )
+        .execute(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to revoke api key {}", err);
+            err
+        })?;
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,130 @@
+#![allow(clippy::toplevel_ref_arg)]
+use async_trait::async_trait;
+use chrono::{Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{Executor, PgPool, Postgres};
+use tracing::error;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct AuditLogEntry {
+    pub id: i64,
+    // Requests made with an api key have the key instead of a user
+    pub user_id: Option<Uuid>,
+    pub api_key_id: Option<Uuid>,
+    pub endpoint: String,
+    pub tournament_id: Option<i32>,
+    pub match_id: Option<i64>,
+    pub player_id: Option<i64>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub created_at: NaiveDateTime,
+}
+
+// What an endpoint did, the targets that don't apply are left as None
+#[derive(Debug, Default)]
+pub struct AuditLogRecord {
+    pub user_id: Option<Uuid>,
+    pub api_key_id: Option<Uuid>,
+    pub endpoint: &'static str,
+    pub tournament_id: Option<i32>,
+    pub match_id: Option<i64>,
+    pub player_id: Option<i64>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+#[derive(Debug, Default)]
+pub struct AuditLogFilter {
+    pub user_id: Option<Uuid>,
+    pub endpoint: Option<String>,
+    pub match_id: Option<i64>,
+    pub player_id: Option<i64>,
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+}
+
+#[async_trait]
+pub trait AuditLogStore {
+    // Newest entries first
+    async fn get_audit_log(
+        &self,
+        tournament_id: i32,
+        filter: &AuditLogFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<AuditLogEntry>, sqlx::Error>;
+}
+
+// Done in the transaction of the change being logged so neither is kept without the other
+#[tracing::instrument(name = "Transactional Inserting audit log", skip(executor))]
+pub async fn insert_audit_log(
+    executor: impl Executor<'_, Database = Postgres>,
+    record: AuditLogRecord,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO audit_log
+        (user_id, api_key_id, endpoint, tournament_id, match_id, player_id, before, after, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+        record.user_id,
+        record.api_key_id,
+        record.endpoint,
+        record.tournament_id,
+        record.match_id,
+        record.player_id,
+        record.before,
+        record.after,
+        Local::now().naive_local()
+    )
+    .execute(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to insert audit log {}", err);
+        err
+    })?;
+    Ok(())
+}
+
+#[async_trait]
+impl AuditLogStore for PgPool {
+    #[tracing::instrument(name = "Fetching audit log", skip(self))]
+    async fn get_audit_log(
+        &self,
+        tournament_id: i32,
+        filter: &AuditLogFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<AuditLogEntry>, sqlx::Error> {
+        sqlx::query_as!(
+            AuditLogEntry,
+            "SELECT id, user_id, api_key_id, endpoint, tournament_id, match_id, player_id,
+            before, after, created_at
+            FROM audit_log
+            WHERE tournament_id = $1
+            AND ($2::UUID IS NULL OR user_id = $2)
+            AND ($3::TEXT IS NULL OR endpoint = $3)
+            AND ($4::BIGINT IS NULL OR match_id = $4)
+            AND ($5::BIGINT IS NULL OR player_id = $5)
+            AND ($6::TIMESTAMP IS NULL OR created_at >= $6)
+            AND ($7::TIMESTAMP IS NULL OR created_at <= $7)
+            ORDER BY id DESC
+            LIMIT $8 OFFSET $9",
+            tournament_id,
+            filter.user_id,
+            filter.endpoint,
+            filter.match_id,
+            filter.player_id,
+            filter.from,
+            filter.to,
+            limit,
+            offset
+        )
+        .fetch_all(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to fetch audit log {}", err);
+            err
+        })
+    }
+}
@@ -0,0 +1,525 @@
+#![allow(clippy::toplevel_ref_arg)]
+use crate::stores::{match_store::Match, player_store::Player};
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, Executor, PgPool, Postgres, Transaction};
+use std::collections::HashMap;
+use std::str::FromStr;
+use tracing::error;
+
+// The first key of the advisory lock taken while a draw is generated, the second key is a hash
+// of the tournament id and class. Different from the court queue lock in court_store.
+const DRAW_LOCK: i32 = 2;
+
+// The draws a class can consist of
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Draw {
+    Main,
+    // Losers bracket of a double elimination draw
+    Losers,
+    // First round losers of the main draw
+    Consolation,
+}
+
+impl Draw {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Draw::Main => "main",
+            Draw::Losers => "losers",
+            Draw::Consolation => "consolation",
+        }
+    }
+}
+
+impl FromStr for Draw {
+    type Err = String;
+
+    fn from_str(draw: &str) -> Result<Self, Self::Err> {
+        match draw {
+            "main" => Ok(Draw::Main),
+            "losers" => Ok(Draw::Losers),
+            "consolation" => Ok(Draw::Consolation),
+            _ => Err(format!("Unknown draw: {}", draw)),
+        }
+    }
+}
+
+// Which player of a finished match that advances to the next match
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Advancing {
+    Winner,
+    Loser,
+}
+
+impl Advancing {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Advancing::Winner => "winner",
+            Advancing::Loser => "loser",
+        }
+    }
+}
+
+impl FromStr for Advancing {
+    type Err = String;
+
+    fn from_str(advancing: &str) -> Result<Self, Self::Err> {
+        match advancing {
+            "winner" => Ok(Advancing::Winner),
+            "loser" => Ok(Advancing::Loser),
+            _ => Err(format!("Unknown advancing player: {}", advancing)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct MatchProgression {
+    pub from_match_id: i64,
+    pub to_match_id: i64,
+    // 1 = player_one, 2 = player_two
+    pub slot: i16,
+    pub advancing: Advancing,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct BracketMatch {
+    pub id: i64,
+    pub draw: Draw,
+    pub round: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub player_one: Option<Player>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub player_two: Option<Player>,
+    // Matches whose winners will fill the missing player slots
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub player_one_from: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub player_two_from: Option<i64>,
+    // If it's the winner or loser of the match above that fills the slot
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub player_one_advancing: Option<Advancing>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub player_two_advancing: Option<Advancing>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub winner: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    pub start_time: NaiveDateTime,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct BracketMatchRow {
+    id: i64,
+    draw: String,
+    round: i32,
+    player_one: Option<i64>,
+    player_one_name: Option<String>,
+    player_two: Option<i64>,
+    player_two_name: Option<String>,
+    player_one_from: Option<i64>,
+    player_two_from: Option<i64>,
+    player_one_advancing: Option<String>,
+    player_two_advancing: Option<String>,
+    winner: Option<i64>,
+    result: Option<String>,
+    start_time: NaiveDateTime,
+}
+
+impl From<BracketMatchRow> for BracketMatch {
+    fn from(row: BracketMatchRow) -> Self {
+        let to_player = |id: Option<i64>, name: Option<String>| match (id, name) {
+            (Some(id), Some(name)) => Some(Player { id, name }),
+            _ => None,
+        };
+        let to_advancing = |advancing: Option<String>| {
+            advancing.and_then(|advancing| {
+                advancing
+                    .parse()
+                    .map_err(|err| error!("Invalid stored progression: {}", err))
+                    .ok()
+            })
+        };
+        let draw = row.draw.parse().unwrap_or_else(|err| {
+            error!("Invalid stored draw: {}", err);
+            Draw::Main
+        });
+        BracketMatch {
+            id: row.id,
+            draw,
+            round: row.round,
+            player_one: to_player(row.player_one, row.player_one_name),
+            player_two: to_player(row.player_two, row.player_two_name),
+            player_one_from: row.player_one_from,
+            player_two_from: row.player_two_from,
+            player_one_advancing: to_advancing(row.player_one_advancing),
+            player_two_advancing: to_advancing(row.player_two_advancing),
+            winner: row.winner,
+            result: row.result,
+            start_time: row.start_time,
+        }
+    }
+}
+
+#[async_trait]
+pub trait BracketStore {
+    async fn insert_bracket_match(
+        self,
+        match_data: &Match,
+        round: i32,
+        draw: Draw,
+    ) -> Result<i64, Error>;
+
+    async fn insert_match_progression(self, progression: &MatchProgression) -> Result<(), Error>;
+
+    async fn has_draw(self, tournament_id: i32, class: &str) -> Result<bool, Error>;
+
+    async fn get_draw(self, tournament_id: i32, class: &str) -> Result<Vec<BracketMatch>, Error>;
+
+    async fn get_match_draws(self, tournament_id: i32) -> Result<HashMap<i64, Draw>, Error>;
+
+    async fn get_match_progressions(
+        self,
+        from_match_id: i64,
+    ) -> Result<Vec<MatchProgression>, Error>;
+}
+
+async fn insert_bracket_match(
+    executor: impl Executor<'_, Database = Postgres>,
+    match_data: &Match,
+    round: i32,
+    draw: Draw,
+) -> Result<i64, Error> {
+    let row = sqlx::query!(
+        "INSERT INTO matches (tournament_id, player_one, player_two, class, start_time, round, draw)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                RETURNING id",
+        match_data.tournament_id,
+        match_data.player_one,
+        match_data.player_two,
+        match_data.class,
+        match_data.start_time,
+        round,
+        draw.as_str(),
+    )
+    .fetch_one(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to insert bracket match {}", err);
+        err
+    })?;
+    Ok(row.id)
+}
+
+async fn insert_match_progression(
+    executor: impl Executor<'_, Database = Postgres>,
+    progression: &MatchProgression,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "INSERT INTO match_progression (from_match_id, to_match_id, slot, advancing)
+            VALUES ($1, $2, $3, $4)",
+        progression.from_match_id,
+        progression.to_match_id,
+        progression.slot,
+        progression.advancing.as_str(),
+    )
+    .execute(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to insert match progression {}", err);
+        err
+    })?;
+    Ok(())
+}
+
+// Keeps anyone else from generating a draw for the class until the transaction is done,
+// the advisory lock is needed since there are no rows to lock before the draw exists
+#[tracing::instrument(name = "Transactional Locking draw", skip(executor))]
+pub async fn lock_draw(
+    executor: &mut Transaction<'_, Postgres>,
+    tournament_id: i32,
+    class: &str,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "SELECT 1 AS locked FROM pg_advisory_xact_lock($1, hashtext($2::INTEGER || '/' || $3))",
+        DRAW_LOCK,
+        tournament_id,
+        class
+    )
+    .fetch_one(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to lock draw {}", err);
+        err
+    })?;
+    Ok(())
+}
+
+async fn has_draw(
+    executor: impl Executor<'_, Database = Postgres>,
+    tournament_id: i32,
+    class: &str,
+) -> Result<bool, Error> {
+    let row = sqlx::query!(
+        r#"SELECT EXISTS(SELECT 1 FROM matches
+            WHERE tournament_id = $1 AND class = $2 AND round IS NOT NULL) AS "exists!""#,
+        tournament_id,
+        class
+    )
+    .fetch_one(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to check for existing draw {}", err);
+        err
+    })?;
+    Ok(row.exists)
+}
+
+async fn get_draw(
+    executor: impl Executor<'_, Database = Postgres>,
+    tournament_id: i32,
+    class: &str,
+) -> Result<Vec<BracketMatch>, Error> {
+    let rows = sqlx::query_as!(
+        BracketMatchRow,
+        r#"SELECT m.id, m.draw AS "draw!", m.round AS "round!", m.start_time,
+            m.player_one, one.name AS "player_one_name?",
+            m.player_two, two.name AS "player_two_name?",
+            from_one.from_match_id AS "player_one_from?",
+            from_two.from_match_id AS "player_two_from?",
+            from_one.advancing AS "player_one_advancing?",
+            from_two.advancing AS "player_two_advancing?",
+            res.winner AS "winner?", res.result AS "result?"
+        FROM matches m
+        LEFT JOIN players one ON one.id = m.player_one
+        LEFT JOIN players two ON two.id = m.player_two
+        LEFT JOIN match_progression from_one ON from_one.to_match_id = m.id AND from_one.slot = 1
+        LEFT JOIN match_progression from_two ON from_two.to_match_id = m.id AND from_two.slot = 2
+        LEFT JOIN match_result res ON res.match_id = m.id
+        WHERE m.tournament_id = $1 AND m.class = $2 AND m.round IS NOT NULL
+        ORDER BY m.id ASC"#,
+        tournament_id,
+        class
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to fetch draw {}", err);
+        err
+    })?;
+    Ok(rows.into_iter().map(BracketMatch::from).collect())
+}
+
+async fn get_match_draws(
+    executor: impl Executor<'_, Database = Postgres>,
+    tournament_id: i32,
+) -> Result<HashMap<i64, Draw>, Error> {
+    let rows = sqlx::query!(
+        r#"SELECT id, draw AS "draw!" FROM matches WHERE tournament_id = $1 AND draw IS NOT NULL"#,
+        tournament_id
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to fetch match draws {}", err);
+        err
+    })?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            row.draw
+                .parse()
+                .map_err(|err| error!("Invalid stored draw: {}", err))
+                .ok()
+                .map(|draw| (row.id, draw))
+        })
+        .collect())
+}
+
+async fn get_match_progressions(
+    executor: impl Executor<'_, Database = Postgres>,
+    from_match_id: i64,
+) -> Result<Vec<MatchProgression>, Error> {
+    let rows = sqlx::query!(
+        "SELECT from_match_id, to_match_id, slot, advancing FROM match_progression
+            WHERE from_match_id = $1",
+        from_match_id
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to fetch match progressions {}", err);
+        err
+    })?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            row.advancing
+                .parse()
+                .map_err(|err| error!("Invalid stored progression: {}", err))
+                .ok()
+                .map(|advancing| MatchProgression {
+                    from_match_id: row.from_match_id,
+                    to_match_id: row.to_match_id,
+                    slot: row.slot,
+                    advancing,
+                })
+        })
+        .collect())
+}
+
+// Locks the next matches of the match and returns the ones that have started, ie a player
+// has checked in or they have a result. Checking in or finishing them has to wait until
+// the transaction is done since both reference the locked match.
+#[tracing::instrument(name = "Transactional Locking next matches", skip(executor))]
+pub async fn lock_next_matches(
+    executor: &mut Transaction<'_, Postgres>,
+    from_match_id: i64,
+) -> Result<Vec<i64>, Error> {
+    sqlx::query!(
+        "SELECT matches.id FROM matches
+            JOIN match_progression ON match_progression.to_match_id = matches.id
+            WHERE match_progression.from_match_id = $1
+            FOR UPDATE OF matches",
+        from_match_id
+    )
+    .fetch_all(&mut *executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to lock next matches {}", err);
+        err
+    })?;
+    // A new statement so registrations committed while waiting for the lock are seen
+    let rows = sqlx::query!(
+        r#"SELECT to_match_id FROM match_progression
+            WHERE from_match_id = $1 AND (
+                EXISTS (SELECT 1 FROM register WHERE register.match_id = to_match_id)
+                OR EXISTS (SELECT 1 FROM match_result WHERE match_result.match_id = to_match_id)
+            )"#,
+        from_match_id
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to fetch started next matches {}", err);
+        err
+    })?;
+    Ok(rows.into_iter().map(|row| row.to_match_id).collect())
+}
+
+// Fills in the winner or loser in their next match of the draw, returns the updated next match
+// or None if the player doesn't advance from the finished match
+#[tracing::instrument(name = "Transactional Advancing player", skip(executor))]
+pub async fn advance_player(
+    executor: &mut Transaction<'_, Postgres>,
+    match_id: i64,
+    advancing: Advancing,
+    player_id: i64,
+) -> Result<Option<Match>, Error> {
+    sqlx::query_as!(
+        Match,
+        "UPDATE matches SET
+            player_one = CASE WHEN progression.slot = 1 THEN $2 ELSE matches.player_one END,
+            player_two = CASE WHEN progression.slot = 2 THEN $2 ELSE matches.player_two END
+        FROM match_progression progression
+        WHERE progression.from_match_id = $1 AND progression.advancing = $3
+            AND matches.id = progression.to_match_id
+        RETURNING matches.id, matches.player_one, matches.player_two,
+            matches.tournament_id, matches.class, matches.start_time",
+        match_id,
+        player_id,
+        advancing.as_str()
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to advance player to the next match {}", err);
+        err
+    })
+}
+
+// async_trait names the lifetime of the pool reference, which clippy flags as needless
+#[allow(clippy::needless_lifetimes)]
+#[async_trait]
+impl BracketStore for &PgPool {
+    #[tracing::instrument(name = "Inserting bracket match", skip(self))]
+    async fn insert_bracket_match(
+        self,
+        match_data: &Match,
+        round: i32,
+        draw: Draw,
+    ) -> Result<i64, Error> {
+        insert_bracket_match(self, match_data, round, draw).await
+    }
+
+    #[tracing::instrument(name = "Inserting match progression", skip(self))]
+    async fn insert_match_progression(self, progression: &MatchProgression) -> Result<(), Error> {
+        insert_match_progression(self, progression).await
+    }
+
+    #[tracing::instrument(name = "Checking for existing draw", skip(self))]
+    async fn has_draw(self, tournament_id: i32, class: &str) -> Result<bool, Error> {
+        has_draw(self, tournament_id, class).await
+    }
+
+    #[tracing::instrument(name = "Fetching draw", skip(self))]
+    async fn get_draw(self, tournament_id: i32, class: &str) -> Result<Vec<BracketMatch>, Error> {
+        get_draw(self, tournament_id, class).await
+    }
+
+    #[tracing::instrument(name = "Fetching match draws", skip(self))]
+    async fn get_match_draws(self, tournament_id: i32) -> Result<HashMap<i64, Draw>, Error> {
+        get_match_draws(self, tournament_id).await
+    }
+
+    #[tracing::instrument(name = "Fetching match progressions", skip(self))]
+    async fn get_match_progressions(
+        self,
+        from_match_id: i64,
+    ) -> Result<Vec<MatchProgression>, Error> {
+        get_match_progressions(self, from_match_id).await
+    }
+}
+
+#[async_trait]
+impl BracketStore for &mut Transaction<'_, Postgres> {
+    #[tracing::instrument(name = "Transactional Inserting bracket match", skip(self))]
+    async fn insert_bracket_match(
+        self,
+        match_data: &Match,
+        round: i32,
+        draw: Draw,
+    ) -> Result<i64, Error> {
+        insert_bracket_match(self, match_data, round, draw).await
+    }
+
+    #[tracing::instrument(name = "Transactional Inserting match progression", skip(self))]
+    async fn insert_match_progression(self, progression: &MatchProgression) -> Result<(), Error> {
+        insert_match_progression(self, progression).await
+    }
+
+    #[tracing::instrument(name = "Transactional Checking for existing draw", skip(self))]
+    async fn has_draw(self, tournament_id: i32, class: &str) -> Result<bool, Error> {
+        has_draw(self, tournament_id, class).await
+    }
+
+    #[tracing::instrument(name = "Transactional Fetching draw", skip(self))]
+    async fn get_draw(self, tournament_id: i32, class: &str) -> Result<Vec<BracketMatch>, Error> {
+        get_draw(self, tournament_id, class).await
+    }
+
+    #[tracing::instrument(name = "Transactional Fetching match draws", skip(self))]
+    async fn get_match_draws(self, tournament_id: i32) -> Result<HashMap<i64, Draw>, Error> {
+        get_match_draws(self, tournament_id).await
+    }
+
+    #[tracing::instrument(name = "Transactional Fetching match progressions", skip(self))]
+    async fn get_match_progressions(
+        self,
+        from_match_id: i64,
+    ) -> Result<Vec<MatchProgression>, Error> {
+        get_match_progressions(self, from_match_id).await
+    }
+}
@@ -1,22 +1,37 @@
 #![allow(clippy::toplevel_ref_arg)]
 use async_trait::async_trait;
 use chrono::{Local, NaiveDateTime};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sqlx::{Error, Executor, PgPool, Postgres, Transaction};
 use tracing::{error, info};
 
-#[derive(Debug, sqlx::FromRow, Serialize)]
+// The first key of the advisory lock taken on the court queue of a tournament,
+// the second key is the tournament id
+const COURT_QUEUE_LOCK: i32 = 1;
+
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, Serialize, Deserialize)]
 pub struct CourtQueueEntry {
+    // When the match joined the queue
     pub place_in_queue: NaiveDateTime,
     pub match_id: i64,
     pub tournament_id: i32,
+    // The queue is ordered by this, ties are broken by place_in_queue and then match id
+    pub position: i32,
+    // Pulled out of the queue, it won't get a court until it's put back
+    pub on_hold: bool,
+    // The priority of the match
+    pub priority: i32,
 }
 
-#[derive(Debug, sqlx::FromRow, Serialize)]
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, Serialize, Deserialize)]
 pub struct TournamentCourtAllocation {
     pub court_name: String,
     pub tournament_id: i32,
     pub match_id: Option<i64>,
+    // Matches can require these, ex a surface or lighting for late matches
+    pub surface: Option<String>,
+    pub indoor: bool,
+    pub lighting: bool,
 }
 // Court service?
 #[async_trait]
@@ -28,6 +43,13 @@ pub trait CourtStore {
 
     async fn get_match_court(self, tournament_id: i32, match_id: i64) -> Option<String>;
 
+    async fn get_courts(
+        self,
+        tournament_id: i32,
+    ) -> Result<Vec<TournamentCourtAllocation>, sqlx::Error>;
+
+    // Assigns the match a free court that meets its requirements, the preferred
+    // court is used if it's free
     async fn try_assign_free_court(
         self,
         tournament_id: i32,
@@ -40,13 +62,19 @@ pub trait CourtStore {
         match_id: i64,
     ) -> Result<String, sqlx::Error>;
 
-    async fn append_court_queue(self, tournament_id: i32, match_id: i64)
-        -> Result<(), sqlx::Error>;
+    // None if the match has been pulled out of the queue
     async fn get_court_queue_placement(
         self,
         tournament_id: i32,
         match_id: i64,
-    ) -> Result<usize, sqlx::Error>;
+    ) -> Result<Option<usize>, sqlx::Error>;
+    // The match ids in the order they will be assigned a court
+    async fn get_court_queue(self, tournament_id: i32) -> Result<Vec<i64>, sqlx::Error>;
+    // Every entry in queue order, including the ones on hold
+    async fn get_court_queue_entries(
+        self,
+        tournament_id: i32,
+    ) -> Result<Vec<CourtQueueEntry>, sqlx::Error>;
 }
 
 async fn insert_tournament_court_allocation(
@@ -54,10 +82,13 @@ async fn insert_tournament_court_allocation(
     tournament_court_allocation: TournamentCourtAllocation,
 ) -> Result<(), sqlx::Error> {
     sqlx::query!(
-            "INSERT INTO tournament_court_allocation (court_name, tournament_id, match_id) VALUES ($1, $2, $3)",
+            "INSERT INTO tournament_court_allocation (court_name, tournament_id, match_id, surface, indoor, lighting) VALUES ($1, $2, $3, $4, $5, $6)",
             tournament_court_allocation.court_name,
             tournament_court_allocation.tournament_id,
             tournament_court_allocation.match_id,
+            tournament_court_allocation.surface,
+            tournament_court_allocation.indoor,
+            tournament_court_allocation.lighting,
         )
             .execute(executor)
             .await
@@ -88,14 +119,43 @@ async fn get_match_court(
             .map(|test| test.court_name)
 }
 
+async fn get_courts(
+    executor: impl Executor<'_, Database = Postgres>,
+    tournament_id: i32,
+) -> Result<Vec<TournamentCourtAllocation>, sqlx::Error> {
+    sqlx::query_as!(
+        TournamentCourtAllocation,
+        "SELECT court_name, tournament_id, match_id, surface, indoor, lighting \
+            FROM tournament_court_allocation WHERE tournament_id = $1 ORDER BY court_name",
+        tournament_id
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to fetch courts: {}", err);
+        err
+    })
+}
+
 async fn try_assign_free_court(
     executor: impl Executor<'_, Database = Postgres>,
     tournament_id: i32,
     match_id: i64,
 ) -> Result<String, sqlx::Error> {
+    // Courts are picked by name when the match has no preference
     let row = sqlx::query!(
         "UPDATE tournament_court_allocation SET match_id = $1 \
-                        WHERE tournament_id = $2 AND match_id IS NULL RETURNING court_name",
+            WHERE tournament_id = $2 AND match_id IS NULL AND court_name = ( \
+                SELECT court.court_name FROM tournament_court_allocation court, matches \
+                WHERE matches.id = $1 AND court.tournament_id = $2 AND court.match_id IS NULL \
+                AND (NOT matches.court_required OR court.court_name = matches.preferred_court) \
+                AND (matches.required_surface IS NULL OR court.surface = matches.required_surface) \
+                AND (matches.required_indoor IS NULL OR court.indoor = matches.required_indoor) \
+                AND (NOT matches.requires_lighting OR court.lighting) \
+                ORDER BY court.court_name IS NOT DISTINCT FROM matches.preferred_court DESC, \
+                    court.court_name \
+                LIMIT 1 \
+            ) RETURNING court_name",
         match_id,
         tournament_id
     )
@@ -136,13 +196,27 @@ async fn remove_assigned_court(
     }
 }
 
-async fn append_court_queue(
-    executor: impl Executor<'_, Database = Postgres>,
+// The match is placed after the waiting matches with the same or a higher priority
+#[tracing::instrument(name = "Transactional Appending match to court queue", skip(executor))]
+pub async fn append_court_queue(
+    executor: &mut Transaction<'_, Postgres>,
     tournament_id: i32,
     match_id: i64,
 ) -> Result<(), sqlx::Error> {
+    // Concurrent appends would otherwise get the same position
+    lock_court_queue(executor, tournament_id).await?;
     sqlx::query!(
-        "INSERT INTO court_queue (place_in_queue, match_id, tournament_id) VALUES ($1, $2, $3)",
+        "WITH placement AS ( \
+            SELECT COALESCE(MAX(court_queue.position), 0) + 1 AS position \
+            FROM court_queue JOIN matches ON matches.id = court_queue.match_id \
+            WHERE court_queue.tournament_id = $3 AND NOT court_queue.on_hold \
+            AND matches.priority >= (SELECT priority FROM matches WHERE id = $2) \
+        ), moved_back AS ( \
+            UPDATE court_queue SET position = position + 1 \
+            WHERE tournament_id = $3 AND position >= (SELECT position FROM placement) \
+        ) \
+        INSERT INTO court_queue (place_in_queue, match_id, tournament_id, position) \
+        SELECT $1, $2, $3, position FROM placement",
         Local::now().naive_local(),
         match_id,
         tournament_id
@@ -160,11 +234,11 @@ async fn get_court_queue_placement(
     executor: impl Executor<'_, Database = Postgres>,
     tournament_id: i32,
     match_id: i64,
-) -> Result<usize, sqlx::Error> {
+) -> Result<Option<usize>, sqlx::Error> {
     // TODO: Count in the query itself instead of doing it in memory here, doesn't scale as well
     let queue_entries = sqlx::query!(
-        "SELECT match_id FROM court_queue \
-            WHERE tournament_id = $1 ORDER BY place_in_queue ASC LIMIT 100",
+        "SELECT match_id, on_hold FROM court_queue \
+            WHERE tournament_id = $1 ORDER BY position, place_in_queue, match_id",
         tournament_id
     )
     .fetch_all(executor)
@@ -174,42 +248,142 @@ async fn get_court_queue_placement(
         err
     })?;
 
-    if let Some(queue_index) = queue_entries
-        .iter()
-        .position(|rec| rec.match_id == match_id)
-    {
-        Ok(queue_index + 1)
+    if let Some(entry) = queue_entries.iter().find(|rec| rec.match_id == match_id) {
+        if entry.on_hold {
+            return Ok(None);
+        }
+        let queue_index = queue_entries
+            .iter()
+            .filter(|rec| !rec.on_hold)
+            .position(|rec| rec.match_id == match_id)
+            .unwrap_or_default();
+        Ok(Some(queue_index + 1))
     } else {
         error!("Match {} not found in court queue!", match_id);
         Err(sqlx::Error::RowNotFound)
     }
 }
 
-#[tracing::instrument(name = "Transactional Peek court queue", skip(executor))]
-async fn peek_court_queue(
+#[tracing::instrument(name = "Get court queue", skip(executor))]
+async fn get_court_queue(
+    executor: impl Executor<'_, Database = Postgres>,
+    tournament_id: i32,
+) -> Result<Vec<i64>, sqlx::Error> {
+    let queue_entries = sqlx::query!(
+        "SELECT match_id FROM court_queue \
+            WHERE tournament_id = $1 AND NOT on_hold ORDER BY position, place_in_queue, match_id",
+        tournament_id
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to fetch court queue {}", err);
+        err
+    })?;
+    Ok(queue_entries.into_iter().map(|rec| rec.match_id).collect())
+}
+
+async fn get_court_queue_entries(
+    executor: impl Executor<'_, Database = Postgres>,
+    tournament_id: i32,
+) -> Result<Vec<CourtQueueEntry>, sqlx::Error> {
+    sqlx::query_as!(
+        CourtQueueEntry,
+        "SELECT place_in_queue, match_id, court_queue.tournament_id, position, on_hold, priority \
+            FROM court_queue JOIN matches ON matches.id = court_queue.match_id \
+            WHERE court_queue.tournament_id = $1 ORDER BY on_hold, position, place_in_queue, match_id",
+        tournament_id
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to fetch court queue entries {}", err);
+        err
+    })
+}
+
+// Same as get_court_queue_entries but keeps anyone else from changing the queue
+// until the transaction is done. The advisory lock also covers an empty queue where
+// there are no rows to lock.
+#[tracing::instrument(name = "Transactional Locking court queue", skip(executor))]
+pub async fn lock_court_queue(
     executor: &mut Transaction<'_, Postgres>,
     tournament_id: i32,
-) -> Result<Option<i64>, sqlx::Error> {
-    if let Some(head_of_queue) = sqlx::query!(
-        "SELECT match_id FROM court_queue WHERE \
-                tournament_id = $1 ORDER BY place_in_queue ASC LIMIT 1",
+) -> Result<Vec<CourtQueueEntry>, sqlx::Error> {
+    sqlx::query!(
+        "SELECT 1 AS locked FROM pg_advisory_xact_lock($1, $2)",
+        COURT_QUEUE_LOCK,
         tournament_id
     )
-    .fetch_optional(executor)
+    .fetch_one(&mut *executor)
     .await
     .map_err(|err| {
-        error!("Failed to fetch head of court queue");
+        error!("Failed to lock court queue {}", err);
         err
-    })? {
-        Ok(Some(head_of_queue.match_id))
-    } else {
-        info!("No court found in the court queue!");
-        Ok(None)
-    }
+    })?;
+    sqlx::query_as!(
+        CourtQueueEntry,
+        "SELECT place_in_queue, match_id, court_queue.tournament_id, position, on_hold, priority \
+            FROM court_queue JOIN matches ON matches.id = court_queue.match_id \
+            WHERE court_queue.tournament_id = $1 ORDER BY on_hold, position, place_in_queue, match_id \
+            FOR UPDATE OF court_queue",
+        tournament_id
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to lock court queue {}", err);
+        err
+    })
+}
+
+// Gives the matches the positions 1..n in the given order
+#[tracing::instrument(name = "Transactional Reordering court queue", skip(executor))]
+pub async fn reorder_court_queue(
+    executor: &mut Transaction<'_, Postgres>,
+    tournament_id: i32,
+    match_ids: &[i64],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE court_queue SET position = ordered.position::INTEGER \
+            FROM UNNEST($2::BIGINT[]) WITH ORDINALITY AS ordered(match_id, position) \
+            WHERE court_queue.tournament_id = $1 AND court_queue.match_id = ordered.match_id",
+        tournament_id,
+        match_ids
+    )
+    .execute(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to reorder court queue {}", err);
+        err
+    })?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Transactional Setting court queue hold", skip(executor))]
+pub async fn set_court_queue_on_hold(
+    executor: &mut Transaction<'_, Postgres>,
+    tournament_id: i32,
+    match_id: i64,
+    on_hold: bool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE court_queue SET on_hold = $1 WHERE tournament_id = $2 AND match_id = $3",
+        on_hold,
+        tournament_id,
+        match_id
+    )
+    .execute(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to set court queue hold {}", err);
+        err
+    })?;
+    Ok(())
 }
 
 #[tracing::instrument(name = "Transactional Delete from court queue", skip(executor))]
-async fn delete_from_court_queue(
+pub async fn delete_from_court_queue(
     executor: &mut Transaction<'_, Postgres>,
     tournament_id: i32,
     match_id: i64,
@@ -222,24 +396,32 @@ async fn delete_from_court_queue(
     .execute(executor)
     .await
     .map_err(|err| {
-        error!("Failed to delete head of court queue");
+        error!("Failed to delete match from court queue");
         err
     })?;
     Ok(())
 }
 
-#[tracing::instrument(name = "Transactional Popping of court queue", skip(executor))]
-pub async fn pop_court_queue(
-    executor: &mut Transaction<'_, Postgres>, // detta går nog att lösa med unsafe
+// Gives the free courts to the waiting matches in queue order, matches that none of
+// the free courts suit are skipped. Returns the started matches and their courts.
+#[tracing::instrument(name = "Transactional Assigning courts from queue", skip(executor))]
+pub async fn assign_courts_from_queue(
+    executor: &mut Transaction<'_, Postgres>,
     tournament_id: i32,
-) -> Result<Option<i64>, Error> {
-    match peek_court_queue(executor, tournament_id).await? {
-        Some(match_id) => {
-            delete_from_court_queue(executor, tournament_id, match_id).await?;
-            Ok(Some(match_id))
+) -> Result<Vec<(i64, String)>, Error> {
+    let mut started_matches = Vec::new();
+    for match_id in get_court_queue(&mut *executor, tournament_id).await? {
+        match try_assign_free_court(&mut *executor, tournament_id, match_id).await {
+            Ok(court_name) => {
+                delete_from_court_queue(executor, tournament_id, match_id).await?;
+                info!("Assigning court: {} to match: {}", court_name, match_id);
+                started_matches.push((match_id, court_name));
+            }
+            Err(Error::RowNotFound) => {}
+            Err(err) => return Err(err),
         }
-        None => Ok(None),
     }
+    Ok(started_matches)
 }
 
 #[async_trait]
@@ -257,6 +439,14 @@ impl CourtStore for &PgPool {
         get_match_court(self, tournament_id, match_id).await
     }
 
+    #[tracing::instrument(name = "Fetching courts", skip(self))]
+    async fn get_courts(
+        self,
+        tournament_id: i32,
+    ) -> Result<Vec<TournamentCourtAllocation>, sqlx::Error> {
+        get_courts(self, tournament_id).await
+    }
+
     #[tracing::instrument(name = "Trying to assign free court to match", skip(self))]
     async fn try_assign_free_court(
         self,
@@ -275,22 +465,26 @@ impl CourtStore for &PgPool {
         remove_assigned_court(self, tournament_id, match_id).await
     }
 
-    #[tracing::instrument(name = "Appending match to court queue", skip(self))]
-    async fn append_court_queue(
+    #[tracing::instrument(name = "Fetch court queue placement", skip(self))]
+    async fn get_court_queue_placement(
         self,
         tournament_id: i32,
         match_id: i64,
-    ) -> Result<(), sqlx::Error> {
-        append_court_queue(self, tournament_id, match_id).await
+    ) -> Result<Option<usize>, sqlx::Error> {
+        get_court_queue_placement(self, tournament_id, match_id).await
     }
 
-    #[tracing::instrument(name = "Fetch court queue placement", skip(self))]
-    async fn get_court_queue_placement(
+    #[tracing::instrument(name = "Fetch court queue", skip(self))]
+    async fn get_court_queue(self, tournament_id: i32) -> Result<Vec<i64>, Error> {
+        get_court_queue(self, tournament_id).await
+    }
+
+    #[tracing::instrument(name = "Fetch court queue entries", skip(self))]
+    async fn get_court_queue_entries(
         self,
         tournament_id: i32,
-        match_id: i64,
-    ) -> Result<usize, sqlx::Error> {
-        get_court_queue_placement(self, tournament_id, match_id).await
+    ) -> Result<Vec<CourtQueueEntry>, Error> {
+        get_court_queue_entries(self, tournament_id).await
     }
 }
 
@@ -312,6 +506,11 @@ impl CourtStore for &mut Transaction<'_, Postgres> {
         get_match_court(self, tournament_id, match_id).await
     }
 
+    #[tracing::instrument(name = "Transactional Fetching courts", skip(self))]
+    async fn get_courts(self, tournament_id: i32) -> Result<Vec<TournamentCourtAllocation>, Error> {
+        get_courts(self, tournament_id).await
+    }
+
     #[tracing::instrument(
         name = "Transactional Trying to assign free court to match",
         skip(self)
@@ -333,17 +532,25 @@ impl CourtStore for &mut Transaction<'_, Postgres> {
         remove_assigned_court(self, tournament_id, match_id).await
     }
 
-    #[tracing::instrument(name = "Transactional Appending match to court queue", skip(self))]
-    async fn append_court_queue(self, tournament_id: i32, match_id: i64) -> Result<(), Error> {
-        append_court_queue(self, tournament_id, match_id).await
-    }
-
     #[tracing::instrument(name = "Transactional Fetch court queue placement", skip(self))]
     async fn get_court_queue_placement(
         self,
         tournament_id: i32,
         match_id: i64,
-    ) -> Result<usize, Error> {
+    ) -> Result<Option<usize>, Error> {
         get_court_queue_placement(self, tournament_id, match_id).await
     }
+
+    #[tracing::instrument(name = "Transactional Fetch court queue", skip(self))]
+    async fn get_court_queue(self, tournament_id: i32) -> Result<Vec<i64>, Error> {
+        get_court_queue(self, tournament_id).await
+    }
+
+    #[tracing::instrument(name = "Transactional Fetch court queue entries", skip(self))]
+    async fn get_court_queue_entries(
+        self,
+        tournament_id: i32,
+    ) -> Result<Vec<CourtQueueEntry>, Error> {
+        get_court_queue_entries(self, tournament_id).await
+    }
 }
@@ -0,0 +1,67 @@
+#![allow(clippy::toplevel_ref_arg)]
+use async_trait::async_trait;
+use chrono::{Local, NaiveDateTime};
+use sqlx::PgPool;
+use tracing::error;
+use uuid::Uuid;
+
+#[async_trait]
+pub trait EmailVerificationStore {
+    async fn insert_email_verification_token(
+        &self,
+        user_id: Uuid,
+        token_hash: &str,
+        expires_at: NaiveDateTime,
+    ) -> Result<(), sqlx::Error>;
+    // Marks the token as used and the user as verified, returns the verified user.
+    // Returns None if the token is unknown, expired or already used
+    async fn verify_email(&self, token_hash: &str) -> Result<Option<Uuid>, sqlx::Error>;
+}
+
+#[async_trait]
+impl EmailVerificationStore for PgPool {
+    #[tracing::instrument(name = "Inserting email verification token", skip(self, token_hash))]
+    async fn insert_email_verification_token(
+        &self,
+        user_id: Uuid,
+        token_hash: &str,
+        expires_at: NaiveDateTime,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO email_verification_tokens (token_hash, user_id, expires_at) VALUES ($1, $2, $3)",
+            token_hash,
+            user_id,
+            expires_at
+        )
+        .execute(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to insert email verification token {}", err);
+            err
+        })?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Verifying email", skip(self, token_hash))]
+    async fn verify_email(&self, token_hash: &str) -> Result<Option<Uuid>, sqlx::Error> {
+        let now = Local::now().naive_local();
+        let row = sqlx::query!(
+            "WITH token AS (
+                UPDATE email_verification_tokens SET used_at = $1
+                WHERE token_hash = $2 AND used_at IS NULL AND expires_at > $1
+                RETURNING user_id
+            )
+            UPDATE users SET verified = TRUE FROM token WHERE users.id = token.user_id
+            RETURNING users.id",
+            now,
+            token_hash
+        )
+        .fetch_optional(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to verify email {}", err);
+            err
+        })?;
+        Ok(row.map(|row| row.id))
+    }
+}
@@ -0,0 +1,181 @@
+#![allow(clippy::toplevel_ref_arg)]
+use async_trait::async_trait;
+use chrono::{Local, NaiveDateTime};
+use sqlx::PgPool;
+use tracing::error;
+
+// What the failed login attempts are counted by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoginAttemptKind {
+    Email,
+    Ip,
+}
+
+impl LoginAttemptKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LoginAttemptKind::Email => "email",
+            LoginAttemptKind::Ip => "ip",
+        }
+    }
+}
+
+#[async_trait]
+pub trait FailedLoginStore {
+    // Returns when the lockout ends if logins currently are locked out
+    async fn get_login_lockout(
+        &self,
+        kind: LoginAttemptKind,
+        identifier: &str,
+    ) -> Result<Option<NaiveDateTime>, sqlx::Error>;
+    // Counts a failed login and returns the number of failures since window_start,
+    // older failures are forgotten
+    async fn insert_failed_login(
+        &self,
+        kind: LoginAttemptKind,
+        identifier: &str,
+        window_start: NaiveDateTime,
+    ) -> Result<i32, sqlx::Error>;
+    // A lockout that ends later than locked_until is kept
+    async fn lock_login(
+        &self,
+        kind: LoginAttemptKind,
+        identifier: &str,
+        locked_until: NaiveDateTime,
+    ) -> Result<(), sqlx::Error>;
+    // Returns the number of failures that were cleared
+    async fn clear_failed_logins(
+        &self,
+        kind: LoginAttemptKind,
+        identifier: &str,
+    ) -> Result<i32, sqlx::Error>;
+    // Takes back the given number of failures, without going below zero
+    async fn forgive_failed_logins(
+        &self,
+        kind: LoginAttemptKind,
+        identifier: &str,
+        failures: i32,
+    ) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+impl FailedLoginStore for PgPool {
+    #[tracing::instrument(name = "Fetching login lockout", skip(self))]
+    async fn get_login_lockout(
+        &self,
+        kind: LoginAttemptKind,
+        identifier: &str,
+    ) -> Result<Option<NaiveDateTime>, sqlx::Error> {
+        let row = sqlx::query!(
+            "SELECT locked_until FROM failed_logins
+            WHERE kind = $1 AND identifier = $2 AND locked_until > $3",
+            kind.as_str(),
+            identifier,
+            Local::now().naive_local()
+        )
+        .fetch_optional(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to fetch login lockout {}", err);
+            err
+        })?;
+        Ok(row.and_then(|row| row.locked_until))
+    }
+
+    #[tracing::instrument(name = "Inserting failed login", skip(self))]
+    async fn insert_failed_login(
+        &self,
+        kind: LoginAttemptKind,
+        identifier: &str,
+        window_start: NaiveDateTime,
+    ) -> Result<i32, sqlx::Error> {
+        let row = sqlx::query!(
+            "INSERT INTO failed_logins (kind, identifier, failures, last_failure)
+            VALUES ($1, $2, 1, $3)
+            ON CONFLICT (kind, identifier) DO UPDATE SET
+                failures = CASE
+                    WHEN failed_logins.last_failure < $4 THEN 1
+                    ELSE failed_logins.failures + 1
+                END,
+                last_failure = EXCLUDED.last_failure
+            RETURNING failures",
+            kind.as_str(),
+            identifier,
+            Local::now().naive_local(),
+            window_start
+        )
+        .fetch_one(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to insert failed login {}", err);
+            err
+        })?;
+        Ok(row.failures)
+    }
+
+    #[tracing::instrument(name = "Locking login", skip(self))]
+    async fn lock_login(
+        &self,
+        kind: LoginAttemptKind,
+        identifier: &str,
+        locked_until: NaiveDateTime,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE failed_logins SET locked_until = GREATEST(locked_until, $1)
+            WHERE kind = $2 AND identifier = $3",
+            locked_until,
+            kind.as_str(),
+            identifier
+        )
+        .execute(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to lock login {}", err);
+            err
+        })?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Clearing failed logins", skip(self))]
+    async fn clear_failed_logins(
+        &self,
+        kind: LoginAttemptKind,
+        identifier: &str,
+    ) -> Result<i32, sqlx::Error> {
+        let row = sqlx::query!(
+            "DELETE FROM failed_logins WHERE kind = $1 AND identifier = $2 RETURNING failures",
+            kind.as_str(),
+            identifier
+        )
+        .fetch_optional(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to clear failed logins {}", err);
+            err
+        })?;
+        Ok(row.map_or(0, |row| row.failures))
+    }
+
+    #[tracing::instrument(name = "Forgiving failed logins", skip(self))]
+    async fn forgive_failed_logins(
+        &self,
+        kind: LoginAttemptKind,
+        identifier: &str,
+        failures: i32,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE failed_logins SET failures = GREATEST(failures - $1, 0)
+            WHERE kind = $2 AND identifier = $3",
+            failures,
+            kind.as_str(),
+            identifier
+        )
+        .execute(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to forgive failed logins {}", err);
+            err
+        })?;
+        Ok(())
+    }
+}
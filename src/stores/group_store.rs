@@ -0,0 +1,435 @@
+#![allow(clippy::toplevel_ref_arg)]
+use crate::stores::{
+    match_store::{Match, MatchOutcome},
+    player_store::Player,
+};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, Executor, PgPool, Postgres, Transaction};
+use std::str::FromStr;
+use tracing::error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TiebreakRule {
+    // Wins in the matches between the tied players
+    HeadToHead,
+    SetDifference,
+    GameDifference,
+    SetsWon,
+    GamesWon,
+}
+
+impl TiebreakRule {
+    pub const DEFAULT_RULES: [TiebreakRule; 3] = [
+        TiebreakRule::HeadToHead,
+        TiebreakRule::SetDifference,
+        TiebreakRule::GameDifference,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TiebreakRule::HeadToHead => "head_to_head",
+            TiebreakRule::SetDifference => "set_difference",
+            TiebreakRule::GameDifference => "game_difference",
+            TiebreakRule::SetsWon => "sets_won",
+            TiebreakRule::GamesWon => "games_won",
+        }
+    }
+}
+
+impl FromStr for TiebreakRule {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        match rule {
+            "head_to_head" => Ok(TiebreakRule::HeadToHead),
+            "set_difference" => Ok(TiebreakRule::SetDifference),
+            "game_difference" => Ok(TiebreakRule::GameDifference),
+            "sets_won" => Ok(TiebreakRule::SetsWon),
+            "games_won" => Ok(TiebreakRule::GamesWon),
+            _ => Err(format!("Unknown tiebreak rule: {}", rule)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct Group {
+    #[serde(default)]
+    pub id: i32,
+    pub tournament_id: i32,
+    pub class: String,
+    pub name: String,
+    pub tiebreak_rules: Vec<TiebreakRule>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct GroupRow {
+    id: i32,
+    tournament_id: i32,
+    class: String,
+    name: String,
+    tiebreak_rules: Vec<String>,
+}
+
+impl From<GroupRow> for Group {
+    fn from(row: GroupRow) -> Self {
+        let tiebreak_rules = row
+            .tiebreak_rules
+            .iter()
+            .filter_map(|rule| {
+                rule.parse()
+                    .map_err(|err| error!("Invalid stored tiebreak rule: {}", err))
+                    .ok()
+            })
+            .collect();
+        Group {
+            id: row.id,
+            tournament_id: row.tournament_id,
+            class: row.class,
+            name: row.name,
+            tiebreak_rules,
+        }
+    }
+}
+
+// Result of a finished group match
+#[derive(Debug, PartialEq)]
+pub struct GroupMatchResult {
+    pub player_one: i64,
+    pub player_two: i64,
+    pub result: String,
+    pub winner: i64,
+    pub outcome: MatchOutcome,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct GroupMatchResultRow {
+    player_one: i64,
+    player_two: i64,
+    result: String,
+    winner: i64,
+    outcome: String,
+}
+
+impl From<GroupMatchResultRow> for GroupMatchResult {
+    fn from(row: GroupMatchResultRow) -> Self {
+        let outcome = row.outcome.parse().unwrap_or_else(|err| {
+            error!("Invalid stored match outcome: {}", err);
+            MatchOutcome::Completed
+        });
+        GroupMatchResult {
+            player_one: row.player_one,
+            player_two: row.player_two,
+            result: row.result,
+            winner: row.winner,
+            outcome,
+        }
+    }
+}
+
+#[async_trait]
+pub trait GroupStore {
+    async fn insert_group(self, group: &Group) -> Result<i32, Error>;
+
+    async fn insert_group_player(self, group_id: i32, player_id: i64) -> Result<(), Error>;
+
+    async fn get_group(self, tournament_id: i32, group_id: i32) -> Result<Option<Group>, Error>;
+
+    async fn get_groups(self, tournament_id: i32) -> Result<Vec<Group>, Error>;
+
+    async fn get_group_players(self, group_id: i32) -> Result<Vec<Player>, Error>;
+
+    async fn insert_group_match(self, match_data: &Match, group_id: i32) -> Result<i64, Error>;
+
+    async fn has_group_matches(self, group_id: i32) -> Result<bool, Error>;
+
+    async fn get_group_results(self, group_id: i32) -> Result<Vec<GroupMatchResult>, Error>;
+
+    async fn count_unfinished_group_matches(self, group_id: i32) -> Result<i64, Error>;
+}
+
+async fn insert_group(
+    executor: impl Executor<'_, Database = Postgres>,
+    group: &Group,
+) -> Result<i32, Error> {
+    let tiebreak_rules: Vec<String> = group
+        .tiebreak_rules
+        .iter()
+        .map(|rule| rule.as_str().to_string())
+        .collect();
+    let row = sqlx::query!(
+        "INSERT INTO tournament_groups (tournament_id, class, name, tiebreak_rules)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id",
+        group.tournament_id,
+        group.class,
+        group.name,
+        &tiebreak_rules,
+    )
+    .fetch_one(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to insert group {}", err);
+        err
+    })?;
+    Ok(row.id)
+}
+
+async fn insert_group_player(
+    executor: impl Executor<'_, Database = Postgres>,
+    group_id: i32,
+    player_id: i64,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "INSERT INTO group_players (group_id, player_id) VALUES ($1, $2)",
+        group_id,
+        player_id
+    )
+    .execute(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to insert group player {}", err);
+        err
+    })?;
+    Ok(())
+}
+
+async fn get_group(
+    executor: impl Executor<'_, Database = Postgres>,
+    tournament_id: i32,
+    group_id: i32,
+) -> Result<Option<Group>, Error> {
+    let row = sqlx::query_as!(
+        GroupRow,
+        "SELECT * FROM tournament_groups WHERE tournament_id = $1 AND id = $2",
+        tournament_id,
+        group_id
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to fetch group {}", err);
+        err
+    })?;
+    Ok(row.map(Group::from))
+}
+
+async fn get_groups(
+    executor: impl Executor<'_, Database = Postgres>,
+    tournament_id: i32,
+) -> Result<Vec<Group>, Error> {
+    let rows = sqlx::query_as!(
+        GroupRow,
+        "SELECT * FROM tournament_groups WHERE tournament_id = $1 ORDER BY class, name",
+        tournament_id
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to fetch groups {}", err);
+        err
+    })?;
+    Ok(rows.into_iter().map(Group::from).collect())
+}
+
+async fn get_group_players(
+    executor: impl Executor<'_, Database = Postgres>,
+    group_id: i32,
+) -> Result<Vec<Player>, Error> {
+    sqlx::query_as!(
+        Player,
+        "SELECT players.id, players.name FROM group_players
+            JOIN players ON players.id = group_players.player_id
+            WHERE group_players.group_id = $1
+            ORDER BY players.id",
+        group_id
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to fetch group players {}", err);
+        err
+    })
+}
+
+async fn insert_group_match(
+    executor: impl Executor<'_, Database = Postgres>,
+    match_data: &Match,
+    group_id: i32,
+) -> Result<i64, Error> {
+    let row = sqlx::query!(
+        "INSERT INTO matches (tournament_id, player_one, player_two, class, start_time, group_id)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id",
+        match_data.tournament_id,
+        match_data.player_one,
+        match_data.player_two,
+        match_data.class,
+        match_data.start_time,
+        group_id,
+    )
+    .fetch_one(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to insert group match {}", err);
+        err
+    })?;
+    Ok(row.id)
+}
+
+async fn has_group_matches(
+    executor: impl Executor<'_, Database = Postgres>,
+    group_id: i32,
+) -> Result<bool, Error> {
+    let row = sqlx::query!(
+        r#"SELECT EXISTS(SELECT 1 FROM matches WHERE group_id = $1) AS "exists!""#,
+        group_id
+    )
+    .fetch_one(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to check for group matches {}", err);
+        err
+    })?;
+    Ok(row.exists)
+}
+
+async fn get_group_results(
+    executor: impl Executor<'_, Database = Postgres>,
+    group_id: i32,
+) -> Result<Vec<GroupMatchResult>, Error> {
+    let rows = sqlx::query_as!(
+        GroupMatchResultRow,
+        r#"SELECT matches.player_one AS "player_one!", matches.player_two AS "player_two!",
+            match_result.result, match_result.winner, match_result.outcome
+            FROM matches
+            JOIN match_result ON match_result.match_id = matches.id
+            WHERE matches.group_id = $1"#,
+        group_id
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to fetch group results {}", err);
+        err
+    })?;
+    Ok(rows.into_iter().map(GroupMatchResult::from).collect())
+}
+
+async fn count_unfinished_group_matches(
+    executor: impl Executor<'_, Database = Postgres>,
+    group_id: i32,
+) -> Result<i64, Error> {
+    let row = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM matches
+            LEFT JOIN match_result ON match_result.match_id = matches.id
+            WHERE matches.group_id = $1 AND match_result.match_id IS NULL"#,
+        group_id
+    )
+    .fetch_one(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to count unfinished group matches {}", err);
+        err
+    })?;
+    Ok(row.count)
+}
+
+// Same generated pool lifetime as the bracket store impl
+#[allow(clippy::needless_lifetimes)]
+#[async_trait]
+impl GroupStore for &PgPool {
+    #[tracing::instrument(name = "Inserting group", skip(self))]
+    async fn insert_group(self, group: &Group) -> Result<i32, Error> {
+        insert_group(self, group).await
+    }
+
+    #[tracing::instrument(name = "Inserting group player", skip(self))]
+    async fn insert_group_player(self, group_id: i32, player_id: i64) -> Result<(), Error> {
+        insert_group_player(self, group_id, player_id).await
+    }
+
+    #[tracing::instrument(name = "Fetching group", skip(self))]
+    async fn get_group(self, tournament_id: i32, group_id: i32) -> Result<Option<Group>, Error> {
+        get_group(self, tournament_id, group_id).await
+    }
+
+    #[tracing::instrument(name = "Fetching groups", skip(self))]
+    async fn get_groups(self, tournament_id: i32) -> Result<Vec<Group>, Error> {
+        get_groups(self, tournament_id).await
+    }
+
+    #[tracing::instrument(name = "Fetching group players", skip(self))]
+    async fn get_group_players(self, group_id: i32) -> Result<Vec<Player>, Error> {
+        get_group_players(self, group_id).await
+    }
+
+    #[tracing::instrument(name = "Inserting group match", skip(self))]
+    async fn insert_group_match(self, match_data: &Match, group_id: i32) -> Result<i64, Error> {
+        insert_group_match(self, match_data, group_id).await
+    }
+
+    #[tracing::instrument(name = "Checking for group matches", skip(self))]
+    async fn has_group_matches(self, group_id: i32) -> Result<bool, Error> {
+        has_group_matches(self, group_id).await
+    }
+
+    #[tracing::instrument(name = "Fetching group results", skip(self))]
+    async fn get_group_results(self, group_id: i32) -> Result<Vec<GroupMatchResult>, Error> {
+        get_group_results(self, group_id).await
+    }
+
+    #[tracing::instrument(name = "Counting unfinished group matches", skip(self))]
+    async fn count_unfinished_group_matches(self, group_id: i32) -> Result<i64, Error> {
+        count_unfinished_group_matches(self, group_id).await
+    }
+}
+
+#[async_trait]
+impl GroupStore for &mut Transaction<'_, Postgres> {
+    #[tracing::instrument(name = "Transactional Inserting group", skip(self))]
+    async fn insert_group(self, group: &Group) -> Result<i32, Error> {
+        insert_group(self, group).await
+    }
+
+    #[tracing::instrument(name = "Transactional Inserting group player", skip(self))]
+    async fn insert_group_player(self, group_id: i32, player_id: i64) -> Result<(), Error> {
+        insert_group_player(self, group_id, player_id).await
+    }
+
+    #[tracing::instrument(name = "Transactional Fetching group", skip(self))]
+    async fn get_group(self, tournament_id: i32, group_id: i32) -> Result<Option<Group>, Error> {
+        get_group(self, tournament_id, group_id).await
+    }
+
+    #[tracing::instrument(name = "Transactional Fetching groups", skip(self))]
+    async fn get_groups(self, tournament_id: i32) -> Result<Vec<Group>, Error> {
+        get_groups(self, tournament_id).await
+    }
+
+    #[tracing::instrument(name = "Transactional Fetching group players", skip(self))]
+    async fn get_group_players(self, group_id: i32) -> Result<Vec<Player>, Error> {
+        get_group_players(self, group_id).await
+    }
+
+    #[tracing::instrument(name = "Transactional Inserting group match", skip(self))]
+    async fn insert_group_match(self, match_data: &Match, group_id: i32) -> Result<i64, Error> {
+        insert_group_match(self, match_data, group_id).await
+    }
+
+    #[tracing::instrument(name = "Transactional Checking for group matches", skip(self))]
+    async fn has_group_matches(self, group_id: i32) -> Result<bool, Error> {
+        has_group_matches(self, group_id).await
+    }
+
+    #[tracing::instrument(name = "Transactional Fetching group results", skip(self))]
+    async fn get_group_results(self, group_id: i32) -> Result<Vec<GroupMatchResult>, Error> {
+        get_group_results(self, group_id).await
+    }
+
+    #[tracing::instrument(name = "Transactional Counting unfinished group matches", skip(self))]
+    async fn count_unfinished_group_matches(self, group_id: i32) -> Result<i64, Error> {
+        count_unfinished_group_matches(self, group_id).await
+    }
+}
@@ -3,31 +3,156 @@
 use async_trait::async_trait;
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use std::str::FromStr;
 use tracing::error;
+use uuid::Uuid;
 
-#[derive(Debug, PartialEq, sqlx::FromRow, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, Deserialize, Serialize)]
 pub struct Match {
     // Id isn't expected in the incoming messages
     // so it should still be serializable
     #[serde(default)]
     pub id: i64,
-    pub player_one: i64,
-    pub player_two: i64,
+    // Players can be missing for matches in a draw
+    // that are waiting on the result of an earlier round
+    pub player_one: Option<i64>,
+    pub player_two: Option<i64>,
     pub tournament_id: i32,
     pub class: String,
     pub start_time: NaiveDateTime,
 }
 
-#[derive(Debug, PartialEq, sqlx::FromRow, Deserialize, Serialize)]
+// How the match gets a court, the defaults let it play on any court in queue order
+#[derive(Debug, Clone, PartialEq, Default, sqlx::FromRow, Deserialize, Serialize)]
+pub struct MatchScheduling {
+    // Matches with a higher priority, ex finals, are placed ahead of lower ones in the court queue
+    #[serde(default)]
+    pub priority: i32,
+    // Used if it's free when the match gets a court
+    #[serde(default)]
+    pub preferred_court: Option<String>,
+    // Only the preferred court may be used
+    #[serde(default)]
+    pub court_required: bool,
+    // What the court must be like, ex "clay"
+    #[serde(default)]
+    pub surface: Option<String>,
+    #[serde(default)]
+    pub indoor: Option<bool>,
+    #[serde(default)]
+    pub lighting: bool,
+}
+
+// How a match ended
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchOutcome {
+    #[default]
+    Completed,
+    // The opponent never showed up, there is no score
+    Walkover,
+    // The opponent retired during the match, the score is partial
+    Retired,
+    // The opponent was disqualified, the score may be partial
+    Disqualified,
+}
+
+impl MatchOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MatchOutcome::Completed => "completed",
+            MatchOutcome::Walkover => "walkover",
+            MatchOutcome::Retired => "retired",
+            MatchOutcome::Disqualified => "disqualified",
+        }
+    }
+}
+
+impl FromStr for MatchOutcome {
+    type Err = String;
+
+    fn from_str(outcome: &str) -> Result<Self, Self::Err> {
+        match outcome {
+            "completed" => Ok(MatchOutcome::Completed),
+            "walkover" => Ok(MatchOutcome::Walkover),
+            "retired" => Ok(MatchOutcome::Retired),
+            "disqualified" => Ok(MatchOutcome::Disqualified),
+            _ => Err(format!("Unknown match outcome: {}", outcome)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
 pub struct MatchResult {
     pub result: String,
     pub winner: i64,
+    #[serde(default)]
+    pub outcome: MatchOutcome,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct MatchResultRow {
+    result: String,
+    winner: i64,
+    outcome: String,
+}
+
+impl From<MatchResultRow> for MatchResult {
+    fn from(row: MatchResultRow) -> Self {
+        let outcome = row.outcome.parse().unwrap_or_else(|err| {
+            error!("Invalid stored match outcome: {}", err);
+            MatchOutcome::Completed
+        });
+        MatchResult {
+            result: row.result,
+            winner: row.winner,
+            outcome,
+        }
+    }
+}
+
+// A replaced match result together with who replaced it and when
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct MatchResultCorrection {
+    pub match_id: i64,
+    pub previous_result: String,
+    pub previous_winner: i64,
+    pub previous_outcome: MatchOutcome,
+    // None if the user has been removed
+    pub corrected_by: Option<Uuid>,
+    pub corrected_at: NaiveDateTime,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct MatchResultCorrectionRow {
+    match_id: i64,
+    previous_result: String,
+    previous_winner: i64,
+    previous_outcome: String,
+    corrected_by: Option<Uuid>,
+    corrected_at: NaiveDateTime,
+}
+
+impl From<MatchResultCorrectionRow> for MatchResultCorrection {
+    fn from(row: MatchResultCorrectionRow) -> Self {
+        let previous_outcome = row.previous_outcome.parse().unwrap_or_else(|err| {
+            error!("Invalid stored match outcome: {}", err);
+            MatchOutcome::Completed
+        });
+        MatchResultCorrection {
+            match_id: row.match_id,
+            previous_result: row.previous_result,
+            previous_winner: row.previous_winner,
+            previous_outcome,
+            corrected_by: row.corrected_by,
+            corrected_at: row.corrected_at,
+        }
+    }
 }
 
 #[async_trait]
 pub trait MatchStore {
-    async fn insert_match(&self, match_data: Match) -> Result<i64, sqlx::Error>;
     async fn get_match(&self, match_id: i64) -> Result<Option<Match>, sqlx::Error>;
     async fn get_tournament_matches(&self, tournament_id: i32) -> Result<Vec<Match>, sqlx::Error>;
     async fn get_match_result(&self, match_id: i64) -> Option<MatchResult>;
@@ -36,36 +161,186 @@ pub trait MatchStore {
         match_id: i64,
         match_result: &MatchResult,
     ) -> Result<(), sqlx::Error>;
+    async fn get_match_result_corrections(
+        &self,
+        match_id: i64,
+    ) -> Result<Vec<MatchResultCorrection>, sqlx::Error>;
+    async fn get_match_scheduling(
+        &self,
+        match_id: i64,
+    ) -> Result<Option<MatchScheduling>, sqlx::Error>;
 }
 
-#[async_trait]
-impl MatchStore for PgPool {
-    #[tracing::instrument(name = "Inserting match", skip(self))]
-    async fn insert_match(&self, match_data: Match) -> Result<i64, sqlx::Error> {
-        let row = sqlx::query!(
-            "INSERT INTO matches (tournament_id, player_one, player_two, class, start_time) 
+// Can be used together with a transaction, unlike the MatchStore method
+pub async fn insert_match_result(
+    executor: impl Executor<'_, Database = Postgres>,
+    match_id: i64,
+    match_result: &MatchResult,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO match_result (match_id, result, winner, outcome) VALUES ($1, $2, $3, $4)",
+        match_id,
+        match_result.result,
+        match_result.winner,
+        match_result.outcome.as_str(),
+    )
+    .execute(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to insert match result {}", err);
+        err
+    })?;
+    Ok(())
+}
+
+// Keeps anyone else from finishing the match or checking in to it until the transaction is
+// done, used since there might not be a result row to lock yet
+#[tracing::instrument(name = "Transactional Locking match", skip(executor))]
+pub async fn lock_match(
+    executor: &mut Transaction<'_, Postgres>,
+    match_id: i64,
+) -> Result<Option<Match>, sqlx::Error> {
+    sqlx::query_as!(
+        Match,
+        "SELECT id, player_one, player_two, tournament_id, class, start_time \
+            FROM matches WHERE id = $1 FOR UPDATE",
+        match_id
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to lock match {}", err);
+        err
+    })
+}
+
+// Keeps anyone else from changing the result until the transaction is done
+#[tracing::instrument(name = "Transactional Locking match result", skip(executor))]
+pub async fn lock_match_result(
+    executor: &mut Transaction<'_, Postgres>,
+    match_id: i64,
+) -> Result<Option<MatchResult>, sqlx::Error> {
+    let row = sqlx::query_as!(
+        MatchResultRow,
+        "SELECT result, winner, outcome FROM match_result WHERE match_id = $1 FOR UPDATE",
+        match_id
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to lock match result {}", err);
+        err
+    })?;
+    Ok(row.map(MatchResult::from))
+}
+
+// Replaces the result of a finished match, should be used together with
+// insert_match_result_correction in a transaction
+pub async fn update_match_result(
+    executor: impl Executor<'_, Database = Postgres>,
+    match_id: i64,
+    match_result: &MatchResult,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE match_result SET result = $2, winner = $3, outcome = $4 WHERE match_id = $1",
+        match_id,
+        match_result.result,
+        match_result.winner,
+        match_result.outcome.as_str(),
+    )
+    .execute(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to update match result {}", err);
+        err
+    })?;
+    Ok(())
+}
+
+// Should be done while the court queue is locked since the queue order depends on it
+pub async fn set_match_scheduling(
+    executor: impl Executor<'_, Database = Postgres>,
+    match_id: i64,
+    scheduling: &MatchScheduling,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE matches SET priority = $1, preferred_court = $2, court_required = $3,
+            required_surface = $4, required_indoor = $5, requires_lighting = $6
+        WHERE id = $7",
+        scheduling.priority,
+        scheduling.preferred_court,
+        scheduling.court_required,
+        scheduling.surface,
+        scheduling.indoor,
+        scheduling.lighting,
+        match_id
+    )
+    .execute(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to update match scheduling {}", err);
+        err
+    })?;
+    Ok(())
+}
+
+pub async fn insert_match_result_correction(
+    executor: impl Executor<'_, Database = Postgres>,
+    correction: &MatchResultCorrection,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO match_result_corrections
+            (match_id, previous_result, previous_winner, previous_outcome, corrected_by, corrected_at)
+            VALUES ($1, $2, $3, $4, $5, $6)",
+        correction.match_id,
+        correction.previous_result,
+        correction.previous_winner,
+        correction.previous_outcome.as_str(),
+        correction.corrected_by,
+        correction.corrected_at,
+    )
+    .execute(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to insert match result correction {}", err);
+        err
+    })?;
+    Ok(())
+}
+
+// Can be used together with a transaction, unlike the MatchStore methods
+#[tracing::instrument(name = "Transactional Inserting match", skip(executor))]
+pub async fn insert_match(
+    executor: impl Executor<'_, Database = Postgres>,
+    match_data: &Match,
+) -> Result<i64, sqlx::Error> {
+    let row = sqlx::query!(
+        "INSERT INTO matches (tournament_id, player_one, player_two, class, start_time) 
                     VALUES ($1,$2,$3,$4,$5)
                     RETURNING id",
-            match_data.tournament_id,
-            match_data.player_one,
-            match_data.player_two,
-            match_data.class,
-            match_data.start_time,
-        )
-        .fetch_one(self)
-        .await
-        .map_err(|err| {
-            error!("Failed to insert match {}", err);
-            err
-        })?;
-        Ok(row.id)
-    }
+        match_data.tournament_id,
+        match_data.player_one,
+        match_data.player_two,
+        match_data.class,
+        match_data.start_time,
+    )
+    .fetch_one(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to insert match {}", err);
+        err
+    })?;
+    Ok(row.id)
+}
 
+#[async_trait]
+impl MatchStore for PgPool {
     #[tracing::instrument(name = "Fetching tournament matches", skip(self))]
     async fn get_tournament_matches(&self, tournament_id: i32) -> Result<Vec<Match>, sqlx::Error> {
         let matches = sqlx::query_as!(
             Match,
-            "SELECT * FROM matches WHERE tournament_id = $1",
+            "SELECT id, player_one, player_two, tournament_id, class, start_time \
+                FROM matches WHERE tournament_id = $1",
             tournament_id
         )
         .fetch_all(self)
@@ -79,21 +354,26 @@ impl MatchStore for PgPool {
 
     #[tracing::instrument(name = "Fetching match", skip(self))]
     async fn get_match(&self, match_id: i64) -> Result<Option<Match>, sqlx::Error> {
-        let match_row = sqlx::query_as!(Match, "SELECT * FROM matches WHERE id = $1", match_id)
-            .fetch_optional(self)
-            .await
-            .map_err(|err| {
-                error!("Failed to fetch match {}", err);
-                err
-            })?;
+        let match_row = sqlx::query_as!(
+            Match,
+            "SELECT id, player_one, player_two, tournament_id, class, start_time \
+                FROM matches WHERE id = $1",
+            match_id
+        )
+        .fetch_optional(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to fetch match {}", err);
+            err
+        })?;
         Ok(match_row)
     }
 
     #[tracing::instrument(name = "Fetching match result", skip(self))]
     async fn get_match_result(&self, match_id: i64) -> Option<MatchResult> {
         sqlx::query_as!(
-            MatchResult,
-            "SELECT result, winner FROM match_result WHERE match_id = $1",
+            MatchResultRow,
+            "SELECT result, winner, outcome FROM match_result WHERE match_id = $1",
             match_id
         )
         .fetch_optional(self)
@@ -103,6 +383,7 @@ impl MatchStore for PgPool {
         })
         .ok()
         .flatten()
+        .map(MatchResult::from)
     }
 
     #[tracing::instrument(name = "Insert match result", skip(self))]
@@ -111,18 +392,47 @@ impl MatchStore for PgPool {
         match_id: i64,
         match_result: &MatchResult,
     ) -> Result<(), sqlx::Error> {
-        sqlx::query!(
-            "INSERT INTO match_result (match_id, result, winner) VALUES ($1, $2, $3)",
-            match_id,
-            match_result.result,
-            match_result.winner,
+        insert_match_result(self, match_id, match_result).await
+    }
+
+    #[tracing::instrument(name = "Fetching match result corrections", skip(self))]
+    async fn get_match_result_corrections(
+        &self,
+        match_id: i64,
+    ) -> Result<Vec<MatchResultCorrection>, sqlx::Error> {
+        let rows = sqlx::query_as!(
+            MatchResultCorrectionRow,
+            "SELECT match_id, previous_result, previous_winner, previous_outcome,
+                corrected_by, corrected_at
+            FROM match_result_corrections WHERE match_id = $1 ORDER BY id ASC",
+            match_id
         )
-        .execute(self)
+        .fetch_all(self)
         .await
         .map_err(|err| {
-            error!("Failed to insert match result {}", err);
+            error!("Failed to fetch match result corrections {}", err);
             err
         })?;
-        Ok(())
+        Ok(rows.into_iter().map(MatchResultCorrection::from).collect())
+    }
+
+    #[tracing::instrument(name = "Fetching match scheduling", skip(self))]
+    async fn get_match_scheduling(
+        &self,
+        match_id: i64,
+    ) -> Result<Option<MatchScheduling>, sqlx::Error> {
+        sqlx::query_as!(
+            MatchScheduling,
+            "SELECT priority, preferred_court, court_required, required_surface AS surface,
+                required_indoor AS indoor, requires_lighting AS lighting
+            FROM matches WHERE id = $1",
+            match_id
+        )
+        .fetch_optional(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to fetch match scheduling {}", err);
+            err
+        })
     }
 }
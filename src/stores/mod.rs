@@ -1,6 +1,17 @@
+pub mod api_key_store;
+pub mod audit_log_store;
+pub mod bracket_store;
 pub mod court_store;
+pub mod email_verification_store;
+pub mod failed_login_store;
+pub mod group_store;
 pub mod match_store;
+pub mod password_reset_store;
 pub mod player_registration_store;
 pub mod player_store;
+pub mod session_store;
+pub mod team_store;
+pub mod tournament_role_store;
 pub mod tournament_store;
 pub mod user_store;
+pub mod webhook_store;
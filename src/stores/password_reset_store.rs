@@ -0,0 +1,67 @@
+#![allow(clippy::toplevel_ref_arg)]
+use async_trait::async_trait;
+use chrono::{Local, NaiveDateTime};
+use sqlx::PgPool;
+use tracing::error;
+use uuid::Uuid;
+
+#[async_trait]
+pub trait PasswordResetStore {
+    async fn insert_password_reset_token(
+        &self,
+        user_id: Uuid,
+        token_hash: &str,
+        expires_at: NaiveDateTime,
+    ) -> Result<(), sqlx::Error>;
+    // Marks the token as used and returns the user it was issued for,
+    // returns None if the token is unknown, expired or already used
+    async fn use_password_reset_token(&self, token_hash: &str)
+        -> Result<Option<Uuid>, sqlx::Error>;
+}
+
+#[async_trait]
+impl PasswordResetStore for PgPool {
+    #[tracing::instrument(name = "Inserting password reset token", skip(self, token_hash))]
+    async fn insert_password_reset_token(
+        &self,
+        user_id: Uuid,
+        token_hash: &str,
+        expires_at: NaiveDateTime,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO password_reset_tokens (token_hash, user_id, expires_at) VALUES ($1, $2, $3)",
+            token_hash,
+            user_id,
+            expires_at
+        )
+        .execute(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to insert password reset token {}", err);
+            err
+        })?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Using password reset token", skip(self, token_hash))]
+    async fn use_password_reset_token(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<Uuid>, sqlx::Error> {
+        let now = Local::now().naive_local();
+        let row = sqlx::query!(
+            "UPDATE password_reset_tokens SET used_at = $1
+            WHERE token_hash = $2 AND used_at IS NULL AND expires_at > $1
+            RETURNING user_id",
+            now,
+            token_hash
+        )
+        .fetch_optional(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to use password reset token {}", err);
+            err
+        })?;
+        Ok(row.map(|row| row.user_id))
+    }
+}
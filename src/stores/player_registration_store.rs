@@ -3,60 +3,75 @@
 use async_trait::async_trait;
 use chrono::{Local, NaiveDateTime};
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sqlx::{Executor, PgPool, Postgres};
 use tracing::error;
+use uuid::Uuid;
+
+// registerd_by is the display name of who checked the player in,
+// registrations made before users were tracked only have the name
 #[derive(Debug, sqlx::FromRow, Deserialize, Serialize)]
 pub struct PlayerMatchRegistration {
     pub player_id: i64,
     pub match_id: i64,
     pub time_registerd: NaiveDateTime,
     pub registerd_by: String,
+    pub registerd_by_user: Option<Uuid>,
+    pub registerd_by_api_key: Option<Uuid>,
+}
+
+// Who checked a player in
+#[derive(Debug, Clone, PartialEq)]
+pub enum Registrar {
+    User { id: Uuid, email: String },
+    ApiKey { id: Uuid, name: String },
 }
 
 #[async_trait]
 pub trait PlayerRegistrationStore {
-    async fn insert_player_registration(
-        &self,
-        player_id: i64,
-        match_id: i64,
-        registerd_by: String,
-    ) -> Result<PlayerMatchRegistration, sqlx::Error>;
-
     async fn get_registered_players(
         &self,
         match_id: i64,
     ) -> Result<Vec<PlayerMatchRegistration>, sqlx::Error>;
 }
 
-#[async_trait]
-impl PlayerRegistrationStore for PgPool {
-    #[tracing::instrument(name = "Inserting player registration", skip(self))]
-    async fn insert_player_registration(
-        &self,
-        player_id: i64,
-        match_id: i64,
-        registerd_by: String,
-    ) -> Result<PlayerMatchRegistration, sqlx::Error> {
-        let match_registration = PlayerMatchRegistration {
-            player_id,
-            match_id,
-            time_registerd: Local::now().naive_local(),
-            registerd_by,
-        };
-        sqlx::query!("INSERT INTO register (player_id, match_id, time_registerd, registerd_by) VALUES ($1, $2, $3, $4)",
-            match_registration.player_id,
-            match_registration.match_id,
-            match_registration.time_registerd,
-            match_registration.registerd_by,
-        ).execute(self).await
-        .map_err(|err| {
-            error!("Failed to register player {}", err);
-            err
-        })?;
+// Can be used together with a transaction, unlike the PlayerRegistrationStore methods
+#[tracing::instrument(name = "Transactional Inserting player registration", skip(executor))]
+pub async fn insert_player_registration(
+    executor: impl Executor<'_, Database = Postgres>,
+    player_id: i64,
+    match_id: i64,
+    registrar: Registrar,
+) -> Result<PlayerMatchRegistration, sqlx::Error> {
+    let (registerd_by, registerd_by_user, registerd_by_api_key) = match registrar {
+        Registrar::User { id, email } => (email, Some(id), None),
+        Registrar::ApiKey { id, name } => (name, None, Some(id)),
+    };
+    let match_registration = PlayerMatchRegistration {
+        player_id,
+        match_id,
+        time_registerd: Local::now().naive_local(),
+        registerd_by,
+        registerd_by_user,
+        registerd_by_api_key,
+    };
+    sqlx::query!("INSERT INTO register (player_id, match_id, time_registerd, registerd_by, registerd_by_user, registerd_by_api_key) VALUES ($1, $2, $3, $4, $5, $6)",
+        match_registration.player_id,
+        match_registration.match_id,
+        match_registration.time_registerd,
+        match_registration.registerd_by,
+        match_registration.registerd_by_user,
+        match_registration.registerd_by_api_key,
+    ).execute(executor).await
+    .map_err(|err| {
+        error!("Failed to register player {}", err);
+        err
+    })?;
 
-        Ok(match_registration)
-    }
+    Ok(match_registration)
+}
 
+#[async_trait]
+impl PlayerRegistrationStore for PgPool {
     #[tracing::instrument(name = "Fetching registerad players", skip(self))]
     async fn get_registered_players(
         &self,
@@ -64,7 +79,8 @@ impl PlayerRegistrationStore for PgPool {
     ) -> Result<Vec<PlayerMatchRegistration>, sqlx::Error> {
         Ok(sqlx::query_as!(
             PlayerMatchRegistration,
-            "SELECT * FROM register WHERE match_id = $1",
+            "SELECT player_id, match_id, time_registerd, registerd_by, registerd_by_user, registerd_by_api_key
+            FROM register WHERE match_id = $1",
             match_id,
         )
         .fetch_all(self)
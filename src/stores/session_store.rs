@@ -0,0 +1,164 @@
+#![allow(clippy::toplevel_ref_arg)]
+use async_trait::async_trait;
+use chrono::{Local, NaiveDateTime};
+use sqlx::{Done, PgPool};
+use tracing::error;
+use uuid::Uuid;
+
+#[derive(Debug, PartialEq, sqlx::FromRow)]
+pub struct RefreshToken {
+    pub session_id: Uuid,
+    pub user_id: Uuid,
+    pub expires_at: NaiveDateTime,
+    pub used_at: Option<NaiveDateTime>,
+    pub session_revoked: bool,
+}
+
+#[async_trait]
+pub trait SessionStore {
+    async fn insert_session(&self, user_id: Uuid) -> Result<Uuid, sqlx::Error>;
+    async fn insert_refresh_token(
+        &self,
+        session_id: Uuid,
+        token_hash: &str,
+        expires_at: NaiveDateTime,
+    ) -> Result<(), sqlx::Error>;
+    async fn get_refresh_token(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<RefreshToken>, sqlx::Error>;
+    // Returns false if the token already has been used
+    async fn use_refresh_token(&self, token_hash: &str) -> Result<bool, sqlx::Error>;
+    async fn is_session_active(&self, session_id: Uuid) -> Result<bool, sqlx::Error>;
+    // Returns false if the session doesn't exist or already was revoked
+    async fn revoke_session(&self, session_id: Uuid) -> Result<bool, sqlx::Error>;
+    // Returns the number of revoked sessions
+    async fn revoke_user_sessions(&self, user_id: Uuid) -> Result<u64, sqlx::Error>;
+}
+
+#[async_trait]
+impl SessionStore for PgPool {
+    #[tracing::instrument(name = "Inserting session", skip(self))]
+    async fn insert_session(&self, user_id: Uuid) -> Result<Uuid, sqlx::Error> {
+        let row = sqlx::query!(
+            "INSERT INTO sessions (id, user_id, created_at) VALUES ($1, $2, $3) RETURNING id",
+            Uuid::new_v4(),
+            user_id,
+            Local::now().naive_local()
+        )
+        .fetch_one(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to insert session {}", err);
+            err
+        })?;
+        Ok(row.id)
+    }
+
+    #[tracing::instrument(name = "Inserting refresh token", skip(self, token_hash))]
+    async fn insert_refresh_token(
+        &self,
+        session_id: Uuid,
+        token_hash: &str,
+        expires_at: NaiveDateTime,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO refresh_tokens (token_hash, session_id, expires_at) VALUES ($1, $2, $3)",
+            token_hash,
+            session_id,
+            expires_at
+        )
+        .execute(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to insert refresh token {}", err);
+            err
+        })?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Fetching refresh token", skip(self, token_hash))]
+    async fn get_refresh_token(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<RefreshToken>, sqlx::Error> {
+        sqlx::query_as!(
+            RefreshToken,
+            r#"SELECT tokens.session_id, sessions.user_id, tokens.expires_at, tokens.used_at,
+            sessions.revoked_at IS NOT NULL as "session_revoked!"
+            FROM refresh_tokens tokens
+            JOIN sessions ON sessions.id = tokens.session_id
+            WHERE tokens.token_hash = $1"#,
+            token_hash
+        )
+        .fetch_optional(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to fetch refresh token {}", err);
+            err
+        })
+    }
+
+    #[tracing::instrument(name = "Using refresh token", skip(self, token_hash))]
+    async fn use_refresh_token(&self, token_hash: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE refresh_tokens SET used_at = $1 WHERE token_hash = $2 AND used_at IS NULL",
+            Local::now().naive_local(),
+            token_hash
+        )
+        .execute(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to use refresh token {}", err);
+            err
+        })?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "Checking session", skip(self))]
+    async fn is_session_active(&self, session_id: Uuid) -> Result<bool, sqlx::Error> {
+        let row = sqlx::query!(
+            "SELECT id FROM sessions WHERE id = $1 AND revoked_at IS NULL",
+            session_id
+        )
+        .fetch_optional(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to check session {}", err);
+            err
+        })?;
+        Ok(row.is_some())
+    }
+
+    #[tracing::instrument(name = "Revoking session", skip(self))]
+    async fn revoke_session(&self, session_id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE sessions SET revoked_at = $1 WHERE id = $2 AND revoked_at IS NULL",
+            Local::now().naive_local(),
+            session_id
+        )
+        .execute(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to revoke session {}", err);
+            err
+        })?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "Revoking user sessions", skip(self))]
+    async fn revoke_user_sessions(&self, user_id: Uuid) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE sessions SET revoked_at = $1 WHERE user_id = $2 AND revoked_at IS NULL",
+            Local::now().naive_local(),
+            user_id
+        )
+        .execute(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to revoke user sessions {}", err);
+            err
+        })?;
+        Ok(result.rows_affected())
+    }
+}
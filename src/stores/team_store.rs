@@ -0,0 +1,75 @@
+#![allow(clippy::toplevel_ref_arg)]
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::error;
+
+// A doubles pair, the team takes part in matches as a player with the same id
+#[derive(Debug, sqlx::FromRow, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Team {
+    pub id: i64,
+    pub name: String,
+    pub player_one: i64,
+    pub player_two: i64,
+}
+
+impl Team {
+    pub fn players(&self) -> [i64; 2] {
+        [self.player_one, self.player_two]
+    }
+}
+
+#[async_trait]
+pub trait TeamStore {
+    async fn insert_team(&self, team: &Team) -> Result<(), sqlx::Error>;
+    async fn get_team(&self, id: i64) -> Result<Option<Team>, sqlx::Error>;
+}
+
+#[async_trait]
+impl TeamStore for PgPool {
+    #[tracing::instrument(name = "Inserting new team", skip(self))]
+    async fn insert_team(&self, team: &Team) -> Result<(), sqlx::Error> {
+        let mut transaction = self.begin().await?;
+        sqlx::query!(
+            "INSERT INTO players (id, name) VALUES ($1, $2)",
+            team.id,
+            team.name
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|err| {
+            error!("Failed to insert team as player {}", err);
+            err
+        })?;
+        sqlx::query!(
+            "INSERT INTO teams (id, player_one, player_two) VALUES ($1, $2, $3)",
+            team.id,
+            team.player_one,
+            team.player_two
+        )
+        .execute(&mut transaction)
+        .await
+        .map_err(|err| {
+            error!("Failed to insert team {}", err);
+            err
+        })?;
+        transaction.commit().await
+    }
+
+    #[tracing::instrument(name = "Fetching team", skip(self))]
+    async fn get_team(&self, id: i64) -> Result<Option<Team>, sqlx::Error> {
+        let team = sqlx::query_as!(
+            Team,
+            "SELECT teams.id, players.name, teams.player_one, teams.player_two
+            FROM teams JOIN players ON players.id = teams.id WHERE teams.id = $1",
+            id
+        )
+        .fetch_optional(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to get team {}", err);
+            err
+        })?;
+        Ok(team)
+    }
+}
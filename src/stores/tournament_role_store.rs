@@ -0,0 +1,202 @@
+#![allow(clippy::toplevel_ref_arg)]
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::{Done, PgPool};
+use std::str::FromStr;
+use tracing::error;
+use uuid::Uuid;
+
+// Roles the owner can give other users in a tournament, the owner can always do everything
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TournamentRole {
+    // Manages the tournament itself, courts, matches, draws and groups
+    Organizer,
+    // Reports and corrects match results
+    Referee,
+    // Checks in arriving players
+    Desk,
+}
+
+impl TournamentRole {
+    // The roles allowed to change the tournament setup
+    pub const MANAGE: &'static [TournamentRole] = &[TournamentRole::Organizer];
+    // The roles allowed to report match results
+    pub const REPORT_RESULTS: &'static [TournamentRole] =
+        &[TournamentRole::Organizer, TournamentRole::Referee];
+    // The roles allowed to reorder the court queue
+    pub const MANAGE_QUEUE: &'static [TournamentRole] =
+        &[TournamentRole::Organizer, TournamentRole::Desk];
+    // The roles allowed to check in players
+    pub const CHECK_IN: &'static [TournamentRole] = &[
+        TournamentRole::Organizer,
+        TournamentRole::Referee,
+        TournamentRole::Desk,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TournamentRole::Organizer => "organizer",
+            TournamentRole::Referee => "referee",
+            TournamentRole::Desk => "desk",
+        }
+    }
+}
+
+impl FromStr for TournamentRole {
+    type Err = String;
+
+    fn from_str(role: &str) -> Result<Self, Self::Err> {
+        match role {
+            "organizer" => Ok(TournamentRole::Organizer),
+            "referee" => Ok(TournamentRole::Referee),
+            "desk" => Ok(TournamentRole::Desk),
+            _ => Err(format!("Unknown tournament role: {}", role)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct TournamentRoleGrant {
+    pub user_id: Uuid,
+    pub email: String,
+    pub role: TournamentRole,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct TournamentRoleGrantRow {
+    user_id: Uuid,
+    email: String,
+    role: String,
+}
+
+#[async_trait]
+pub trait TournamentRoleStore {
+    // Replaces any previous role of the user in the tournament
+    async fn grant_tournament_role(
+        &self,
+        tournament_id: i32,
+        user_id: Uuid,
+        role: TournamentRole,
+    ) -> Result<(), sqlx::Error>;
+    // Returns false if the user didn't have a role in the tournament
+    async fn revoke_tournament_role(
+        &self,
+        tournament_id: i32,
+        user_id: Uuid,
+    ) -> Result<bool, sqlx::Error>;
+    async fn get_tournament_role(
+        &self,
+        tournament_id: i32,
+        user_id: Uuid,
+    ) -> Result<Option<TournamentRole>, sqlx::Error>;
+    async fn get_tournament_roles(
+        &self,
+        tournament_id: i32,
+    ) -> Result<Vec<TournamentRoleGrant>, sqlx::Error>;
+}
+
+#[async_trait]
+impl TournamentRoleStore for PgPool {
+    #[tracing::instrument(name = "Granting tournament role", skip(self))]
+    async fn grant_tournament_role(
+        &self,
+        tournament_id: i32,
+        user_id: Uuid,
+        role: TournamentRole,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO tournament_roles (tournament_id, user_id, role) VALUES ($1, $2, $3)
+            ON CONFLICT (tournament_id, user_id) DO UPDATE SET role = EXCLUDED.role",
+            tournament_id,
+            user_id,
+            role.as_str()
+        )
+        .execute(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to grant tournament role {}", err);
+            err
+        })?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Revoking tournament role", skip(self))]
+    async fn revoke_tournament_role(
+        &self,
+        tournament_id: i32,
+        user_id: Uuid,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "DELETE FROM tournament_roles WHERE tournament_id = $1 AND user_id = $2",
+            tournament_id,
+            user_id
+        )
+        .execute(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to revoke tournament role {}", err);
+            err
+        })?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "Fetching tournament role", skip(self))]
+    async fn get_tournament_role(
+        &self,
+        tournament_id: i32,
+        user_id: Uuid,
+    ) -> Result<Option<TournamentRole>, sqlx::Error> {
+        let row = sqlx::query!(
+            "SELECT role FROM tournament_roles WHERE tournament_id = $1 AND user_id = $2",
+            tournament_id,
+            user_id
+        )
+        .fetch_optional(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to fetch tournament role {}", err);
+            err
+        })?;
+        Ok(row.and_then(|row| {
+            row.role
+                .parse()
+                .map_err(|err| error!("Invalid stored tournament role: {}", err))
+                .ok()
+        }))
+    }
+
+    #[tracing::instrument(name = "Fetching tournament roles", skip(self))]
+    async fn get_tournament_roles(
+        &self,
+        tournament_id: i32,
+    ) -> Result<Vec<TournamentRoleGrant>, sqlx::Error> {
+        let rows = sqlx::query_as!(
+            TournamentRoleGrantRow,
+            "SELECT roles.user_id, users.email, roles.role FROM tournament_roles roles
+            JOIN users ON users.id = roles.user_id
+            WHERE roles.tournament_id = $1 ORDER BY users.email ASC",
+            tournament_id
+        )
+        .fetch_all(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to fetch tournament roles {}", err);
+            err
+        })?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                row.role
+                    .parse()
+                    .map_err(|err| error!("Invalid stored tournament role: {}", err))
+                    .ok()
+                    .map(|role| TournamentRoleGrant {
+                        user_id: row.user_id,
+                        email: row.email,
+                        role,
+                    })
+            })
+            .collect())
+    }
+}
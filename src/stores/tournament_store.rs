@@ -3,8 +3,9 @@ use crate::ServerError;
 use async_trait::async_trait;
 use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sqlx::{Done, Executor, PgPool, Postgres};
 use tracing::error;
+use uuid::Uuid;
 #[derive(Debug, Serialize, Deserialize, sqlx::FromRow, Clone, Eq)]
 pub struct Tournament {
     #[serde(default)]
@@ -14,6 +15,24 @@ pub struct Tournament {
     pub end_date: NaiveDate,
 }
 
+// How matches in the tournament are played
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct MatchFormat {
+    // 3 or 5 sets
+    pub best_of: i16,
+    // A match tiebreak to 10 is played instead of the deciding set
+    pub match_tiebreak: bool,
+}
+
+impl Default for MatchFormat {
+    fn default() -> Self {
+        MatchFormat {
+            best_of: 3,
+            match_tiebreak: false,
+        }
+    }
+}
+
 impl PartialEq for Tournament {
     fn eq(&self, other: &Self) -> bool {
         self.id == other.id
@@ -22,43 +41,213 @@ impl PartialEq for Tournament {
 
 #[async_trait]
 pub trait TournamentStore {
-    async fn insert_tournament(&self, tournament: Tournament) -> Result<i32, ServerError>;
     async fn get_tournaments(&self) -> Result<Vec<Tournament>, ServerError>;
+    async fn get_tournament(&self, tournament_id: i32) -> Result<Option<Tournament>, ServerError>;
+    async fn get_tournament_owner(&self, tournament_id: i32) -> Result<Option<Uuid>, ServerError>;
+    async fn delete_tournament(&self, tournament_id: i32) -> Result<bool, ServerError>;
+    async fn get_match_format(
+        &self,
+        tournament_id: i32,
+    ) -> Result<Option<MatchFormat>, ServerError>;
+    async fn update_match_format(
+        &self,
+        tournament_id: i32,
+        match_format: &MatchFormat,
+    ) -> Result<bool, ServerError>;
+}
+
+// Can be used together with a transaction, unlike the TournamentStore methods
+#[tracing::instrument(name = "Transactional Inserting new tournament", skip(executor))]
+pub async fn insert_tournament(
+    executor: impl Executor<'_, Database = Postgres>,
+    tournament: Tournament,
+    owner: Uuid,
+) -> Result<i32, ServerError> {
+    let row = sqlx::query!(
+        "INSERT INTO tournaments (name, start_date, end_date, owner) VALUES ($1, $2, $3, $4)
+        RETURNING id",
+        tournament.name,
+        tournament.start_date,
+        tournament.end_date,
+        owner
+    )
+    .fetch_one(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to insert match {}", err);
+        err
+    })?;
+    Ok(row.id)
+}
+
+// Locks the tournament until the transaction ends so its dates can't change while
+// matches are checked against them
+#[tracing::instrument(name = "Transactional Locking tournament", skip(executor))]
+pub async fn lock_tournament(
+    executor: impl Executor<'_, Database = Postgres>,
+    tournament_id: i32,
+) -> Result<Option<Tournament>, ServerError> {
+    let tournament = sqlx::query_as!(
+        Tournament,
+        "SELECT id, name, start_date, end_date FROM tournaments WHERE id = $1 FOR UPDATE",
+        tournament_id
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to lock tournament {}", err);
+        err
+    })?;
+    Ok(tournament)
+}
+
+// Returns false if the tournament doesn't exist
+#[tracing::instrument(name = "Transactional Updating tournament", skip(executor))]
+pub async fn update_tournament(
+    executor: impl Executor<'_, Database = Postgres>,
+    tournament: &Tournament,
+) -> Result<bool, ServerError> {
+    let result = sqlx::query!(
+        "UPDATE tournaments SET name = $1, start_date = $2, end_date = $3 WHERE id = $4",
+        tournament.name,
+        tournament.start_date,
+        tournament.end_date,
+        tournament.id
+    )
+    .execute(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to update tournament {}", err);
+        err
+    })?;
+    Ok(result.rows_affected() > 0)
+}
+
+#[tracing::instrument(
+    name = "Transactional Checking for matches outside of dates",
+    skip(executor)
+)]
+pub async fn has_matches_outside_dates(
+    executor: impl Executor<'_, Database = Postgres>,
+    tournament_id: i32,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+) -> Result<bool, ServerError> {
+    let row = sqlx::query!(
+        r#"SELECT EXISTS(SELECT 1 FROM matches WHERE tournament_id = $1
+            AND (start_time::date < $2 OR start_time::date > $3)) AS "exists!""#,
+        tournament_id,
+        start_date,
+        end_date
+    )
+    .fetch_one(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to check match start times {}", err);
+        err
+    })?;
+    Ok(row.exists)
 }
 
 #[async_trait]
 impl TournamentStore for PgPool {
-    #[tracing::instrument(name = "Inserting new tournament", skip(self))]
-    async fn insert_tournament(&self, tournament: Tournament) -> Result<i32, ServerError> {
-        let row = sqlx::query!(
-            "INSERT INTO tournaments (name, start_date, end_date) VALUES ($1, $2, $3)
-            RETURNING id",
-            tournament.name,
-            tournament.start_date,
-            tournament.end_date
+    #[tracing::instrument(name = "Fetching tournament list", skip(self))]
+    async fn get_tournaments(&self) -> Result<Vec<Tournament>, ServerError> {
+        let tournaments = sqlx::query_as!(
+            Tournament,
+            "SELECT id, name, start_date, end_date FROM tournaments WHERE end_date >= CURRENT_DATE"
         )
-        .fetch_one(self)
+        .fetch_all(self)
         .await
         .map_err(|err| {
-            error!("Failed to insert match {}", err);
+            error!("Failed to fetch matches {}", err);
             err
         })?;
-        Ok(row.id)
+
+        Ok(tournaments)
     }
 
-    #[tracing::instrument(name = "Fetching tournament list", skip(self))]
-    async fn get_tournaments(&self) -> Result<Vec<Tournament>, ServerError> {
-        let tournaments = sqlx::query_as!(
+    #[tracing::instrument(name = "Fetching tournament", skip(self))]
+    async fn get_tournament(&self, tournament_id: i32) -> Result<Option<Tournament>, ServerError> {
+        let tournament = sqlx::query_as!(
             Tournament,
-            "SELECT * FROM tournaments WHERE end_date >= CURRENT_DATE"
+            "SELECT id, name, start_date, end_date FROM tournaments WHERE id = $1",
+            tournament_id
         )
-        .fetch_all(self)
+        .fetch_optional(self)
         .await
         .map_err(|err| {
-            error!("Failed to fetch matches {}", err);
+            error!("Failed to fetch tournament {}", err);
             err
         })?;
+        Ok(tournament)
+    }
 
-        Ok(tournaments)
+    // None if the tournament doesn't exist or doesn't have an owner
+    #[tracing::instrument(name = "Fetching tournament owner", skip(self))]
+    async fn get_tournament_owner(&self, tournament_id: i32) -> Result<Option<Uuid>, ServerError> {
+        let row = sqlx::query!("SELECT owner FROM tournaments WHERE id = $1", tournament_id)
+            .fetch_optional(self)
+            .await
+            .map_err(|err| {
+                error!("Failed to fetch tournament owner {}", err);
+                err
+            })?;
+        Ok(row.and_then(|row| row.owner))
+    }
+
+    // Matches, courts, draws etc are removed together with the tournament.
+    // Returns false if the tournament doesn't exist
+    #[tracing::instrument(name = "Deleting tournament", skip(self))]
+    async fn delete_tournament(&self, tournament_id: i32) -> Result<bool, ServerError> {
+        let result = sqlx::query!("DELETE FROM tournaments WHERE id = $1", tournament_id)
+            .execute(self)
+            .await
+            .map_err(|err| {
+                error!("Failed to delete tournament {}", err);
+                err
+            })?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "Fetching match format", skip(self))]
+    async fn get_match_format(
+        &self,
+        tournament_id: i32,
+    ) -> Result<Option<MatchFormat>, ServerError> {
+        let match_format = sqlx::query_as!(
+            MatchFormat,
+            "SELECT best_of, match_tiebreak FROM tournaments WHERE id = $1",
+            tournament_id
+        )
+        .fetch_optional(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to fetch match format {}", err);
+            err
+        })?;
+        Ok(match_format)
+    }
+
+    // Returns false if the tournament doesn't exist
+    #[tracing::instrument(name = "Updating match format", skip(self))]
+    async fn update_match_format(
+        &self,
+        tournament_id: i32,
+        match_format: &MatchFormat,
+    ) -> Result<bool, ServerError> {
+        let result = sqlx::query!(
+            "UPDATE tournaments SET best_of = $1, match_tiebreak = $2 WHERE id = $3",
+            match_format.best_of,
+            match_format.match_tiebreak,
+            tournament_id
+        )
+        .execute(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to update match format {}", err);
+            err
+        })?;
+        Ok(result.rows_affected() > 0)
     }
 }
@@ -1,19 +1,113 @@
+#![allow(clippy::toplevel_ref_arg)]
 use crate::ServerError;
 use async_trait::async_trait;
 use bcrypt::{hash, DEFAULT_COST};
 use chrono::{Local, NaiveDateTime};
-use serde::Serialize;
-use sqlx::PgPool;
+use serde::{Deserialize, Serialize};
+use sqlx::{Done, Executor, PgPool, Postgres};
+use std::str::FromStr;
 use tracing::error;
 use uuid::Uuid;
 
-#[derive(Debug, PartialEq, sqlx::FromRow, Serialize)]
+// Global role of the user, unlike the tournament roles it applies to the whole site
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UserRole {
+    #[default]
+    User,
+    // Can manage all tournaments and users
+    Admin,
+}
+
+impl UserRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserRole::User => "user",
+            UserRole::Admin => "admin",
+        }
+    }
+}
+
+impl FromStr for UserRole {
+    type Err = String;
+
+    fn from_str(role: &str) -> Result<Self, Self::Err> {
+        match role {
+            "user" => Ok(UserRole::User),
+            "admin" => Ok(UserRole::Admin),
+            _ => Err(format!("Unknown user role: {}", role)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
 pub struct UserInfoRow {
     pub id: Uuid,
     pub email: String,
     // (hashed)
     pub password: String,
     pub created_at: NaiveDateTime,
+    pub role: UserRole,
+    // Has the user confirmed that they own the email
+    pub verified: bool,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct UserRow {
+    id: Uuid,
+    email: String,
+    password: String,
+    created_at: NaiveDateTime,
+    role: String,
+    verified: bool,
+}
+
+fn parse_role(role: &str) -> UserRole {
+    role.parse()
+        .map_err(|err| error!("Invalid stored user role: {}", err))
+        .unwrap_or_default()
+}
+
+impl From<UserRow> for UserInfoRow {
+    fn from(row: UserRow) -> Self {
+        UserInfoRow {
+            role: parse_role(&row.role),
+            id: row.id,
+            email: row.email,
+            password: row.password,
+            created_at: row.created_at,
+            verified: row.verified,
+        }
+    }
+}
+
+// What admins get to see about other users, never includes the password hash
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct User {
+    pub id: Uuid,
+    pub email: String,
+    pub role: UserRole,
+    pub verified: bool,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<UserRow> for User {
+    fn from(row: UserRow) -> Self {
+        User {
+            role: parse_role(&row.role),
+            id: row.id,
+            email: row.email,
+            verified: row.verified,
+            created_at: row.created_at,
+        }
+    }
+}
+
+fn hash_password(password: &str) -> Result<String, ServerError> {
+    hash(password, DEFAULT_COST).map_err(|err| {
+        error!("Failed to hash password {}", err);
+        ServerError::InvalidPassword
+    })
 }
 
 #[async_trait]
@@ -21,7 +115,25 @@ pub trait UserStore {
     async fn insert_user(&self, email: &str, password: &str) -> Result<Uuid, ServerError>;
     async fn find_user(&self, email: &str) -> Option<UserInfoRow>;
     async fn get_user(&self, id: Uuid) -> Option<UserInfoRow>;
-    async fn delete_user(&self, id: Uuid) -> Result<(), ServerError>;
+    async fn update_password(&self, id: Uuid, password: &str) -> Result<(), ServerError>;
+    async fn get_users(&self) -> Result<Vec<User>, sqlx::Error>;
+    // Returns false if the user doesn't exist
+    async fn set_user_role(&self, id: Uuid, role: UserRole) -> Result<bool, sqlx::Error>;
+}
+
+// Can be used together with a transaction, unlike the UserStore methods
+pub async fn delete_user(
+    executor: impl Executor<'_, Database = Postgres>,
+    id: Uuid,
+) -> Result<(), ServerError> {
+    match sqlx::query!("DELETE FROM users WHERE id = $1", id)
+        .execute(executor)
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(sqlx::Error::RowNotFound) => Err(ServerError::UserNotFound),
+        Err(err) => Err(err.into()),
+    }
 }
 
 #[async_trait]
@@ -29,10 +141,7 @@ impl UserStore for PgPool {
     async fn insert_user(&self, email: &str, password: &str) -> Result<Uuid, ServerError> {
         let id = Uuid::new_v4();
         let created_at = Local::now().naive_local();
-        let hashed_password = hash(password, DEFAULT_COST).map_err(|err| {
-            error!("Failed to hash password {}", err);
-            ServerError::InvalidPassword
-        })?;
+        let hashed_password = hash_password(password)?;
         let row = sqlx::query!("INSERT INTO users (id, email, password, created_at) VALUES ($1, $2, $3, $4) RETURNING id",
          id,
          email,
@@ -49,7 +158,7 @@ impl UserStore for PgPool {
     }
 
     async fn find_user(&self, email: &str) -> Option<UserInfoRow> {
-        sqlx::query_as!(UserInfoRow, "SELECT * FROM users WHERE email = $1", email)
+        sqlx::query_as!(UserRow, "SELECT * FROM users WHERE email = $1", email)
             .fetch_optional(self)
             .await
             .map_err(|err| {
@@ -58,10 +167,11 @@ impl UserStore for PgPool {
             })
             .ok()
             .flatten()
+            .map(UserInfoRow::from)
     }
 
     async fn get_user(&self, id: Uuid) -> Option<UserInfoRow> {
-        sqlx::query_as!(UserInfoRow, "SELECT * FROM users WHERE id = $1", id)
+        sqlx::query_as!(UserRow, "SELECT * FROM users WHERE id = $1", id)
             .fetch_optional(self)
             .await
             .map_err(|err| {
@@ -70,16 +180,51 @@ impl UserStore for PgPool {
             })
             .ok()
             .flatten()
+            .map(UserInfoRow::from)
     }
 
-    async fn delete_user(&self, id: Uuid) -> Result<(), ServerError> {
-        match sqlx::query!("DELETE FROM users WHERE id = $1", id)
-            .execute(self)
-            .await
-        {
-            Ok(_) => Ok(()),
-            Err(sqlx::Error::RowNotFound) => Err(ServerError::UserNotFound),
-            Err(err) => Err(err.into()),
+    async fn update_password(&self, id: Uuid, password: &str) -> Result<(), ServerError> {
+        let hashed_password = hash_password(password)?;
+        let result = sqlx::query!(
+            "UPDATE users SET password = $1 WHERE id = $2",
+            hashed_password,
+            id
+        )
+        .execute(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to update password {}", err);
+            err
+        })?;
+        if result.rows_affected() == 0 {
+            return Err(ServerError::UserNotFound);
         }
+        Ok(())
+    }
+
+    async fn get_users(&self) -> Result<Vec<User>, sqlx::Error> {
+        let rows = sqlx::query_as!(UserRow, "SELECT * FROM users ORDER BY email ASC")
+            .fetch_all(self)
+            .await
+            .map_err(|err| {
+                error!("Failed to fetch users {}", err);
+                err
+            })?;
+        Ok(rows.into_iter().map(User::from).collect())
+    }
+
+    async fn set_user_role(&self, id: Uuid, role: UserRole) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE users SET role = $1 WHERE id = $2",
+            role.as_str(),
+            id
+        )
+        .execute(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to set user role {}", err);
+            err
+        })?;
+        Ok(result.rows_affected() > 0)
     }
 }
@@ -0,0 +1,373 @@
+#![allow(clippy::toplevel_ref_arg)]
+use crate::events::TournamentEventType;
+use async_trait::async_trait;
+use chrono::{Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use sqlx::{Done, Executor, PgPool, Postgres};
+use std::str::FromStr;
+use tracing::error;
+use uuid::Uuid;
+
+// Never contains the secret, it's only shown once when the webhook is created
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub tournament_id: i32,
+    pub url: String,
+    pub event_types: Vec<TournamentEventType>,
+    pub created_by: Option<Uuid>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct WebhookRow {
+    id: Uuid,
+    tournament_id: i32,
+    url: String,
+    event_types: Vec<String>,
+    created_by: Option<Uuid>,
+    created_at: NaiveDateTime,
+}
+
+impl From<WebhookRow> for Webhook {
+    fn from(row: WebhookRow) -> Self {
+        Webhook {
+            id: row.id,
+            tournament_id: row.tournament_id,
+            url: row.url,
+            event_types: row
+                .event_types
+                .iter()
+                .filter_map(|event_type| {
+                    event_type
+                        .parse()
+                        .map_err(|err| error!("Invalid stored webhook event type: {}", err))
+                        .ok()
+                })
+                .collect(),
+            created_by: row.created_by,
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookDeliveryStatus {
+    // Waiting for the first attempt or a retry
+    Pending,
+    Delivered,
+    // Gave up after too many failed attempts
+    Failed,
+}
+
+impl WebhookDeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookDeliveryStatus::Pending => "pending",
+            WebhookDeliveryStatus::Delivered => "delivered",
+            WebhookDeliveryStatus::Failed => "failed",
+        }
+    }
+}
+
+impl FromStr for WebhookDeliveryStatus {
+    type Err = String;
+
+    fn from_str(status: &str) -> Result<Self, Self::Err> {
+        match status {
+            "pending" => Ok(WebhookDeliveryStatus::Pending),
+            "delivered" => Ok(WebhookDeliveryStatus::Delivered),
+            "failed" => Ok(WebhookDeliveryStatus::Failed),
+            _ => Err(format!("Unknown webhook delivery status: {}", status)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub webhook_id: Uuid,
+    pub event_type: String,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: i32,
+    // The status code of the last attempt, missing if no response was received
+    pub status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub next_attempt_at: NaiveDateTime,
+    pub last_attempt_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct WebhookDeliveryRow {
+    id: i64,
+    webhook_id: Uuid,
+    event_type: String,
+    status: String,
+    attempts: i32,
+    status_code: Option<i32>,
+    last_error: Option<String>,
+    next_attempt_at: NaiveDateTime,
+    last_attempt_at: Option<NaiveDateTime>,
+    created_at: NaiveDateTime,
+}
+
+impl From<WebhookDeliveryRow> for WebhookDelivery {
+    fn from(row: WebhookDeliveryRow) -> Self {
+        WebhookDelivery {
+            id: row.id,
+            webhook_id: row.webhook_id,
+            event_type: row.event_type,
+            status: row.status.parse().unwrap_or_else(|err| {
+                error!("Invalid stored webhook delivery status: {}", err);
+                WebhookDeliveryStatus::Failed
+            }),
+            attempts: row.attempts,
+            status_code: row.status_code,
+            last_error: row.last_error,
+            next_attempt_at: row.next_attempt_at,
+            last_attempt_at: row.last_attempt_at,
+            created_at: row.created_at,
+        }
+    }
+}
+
+// A delivery that's due together with what's needed to send it
+#[derive(Debug)]
+pub struct PendingWebhookDelivery {
+    pub id: i64,
+    pub url: String,
+    pub secret: String,
+    pub event_type: String,
+    pub payload: String,
+    pub attempts: i32,
+}
+
+// The result of an attempt to deliver
+#[derive(Debug)]
+pub struct WebhookDeliveryAttempt {
+    pub status: WebhookDeliveryStatus,
+    pub attempts: i32,
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub next_attempt_at: NaiveDateTime,
+}
+
+#[async_trait]
+pub trait WebhookStore {
+    async fn insert_webhook(
+        &self,
+        tournament_id: i32,
+        url: &str,
+        event_types: &[TournamentEventType],
+        secret: &str,
+        created_by: Uuid,
+    ) -> Result<Webhook, sqlx::Error>;
+    async fn get_webhooks(&self, tournament_id: i32) -> Result<Vec<Webhook>, sqlx::Error>;
+    // Returns false if the tournament has no webhook with the id
+    async fn delete_webhook(&self, tournament_id: i32, id: Uuid) -> Result<bool, sqlx::Error>;
+    // Newest deliveries first
+    async fn get_webhook_deliveries(
+        &self,
+        webhook_id: Uuid,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>, sqlx::Error>;
+    // Picks deliveries that are due and postpones them until lease_until so no other
+    // instance picks them up while they are being sent. If the instance dies before
+    // finishing they are retried after that.
+    async fn claim_webhook_deliveries(
+        &self,
+        limit: i64,
+        lease_until: NaiveDateTime,
+    ) -> Result<Vec<PendingWebhookDelivery>, sqlx::Error>;
+    async fn update_webhook_delivery(
+        &self,
+        id: i64,
+        attempt: WebhookDeliveryAttempt,
+    ) -> Result<(), sqlx::Error>;
+}
+
+// Adds a delivery for every webhook of the tournament that wants the event type. Done in
+// the transaction of the change that caused the event, unlike the WebhookStore methods.
+pub async fn insert_webhook_deliveries(
+    executor: impl Executor<'_, Database = Postgres>,
+    tournament_id: i32,
+    event_type: TournamentEventType,
+    payload: &str,
+) -> Result<u64, sqlx::Error> {
+    let now = Local::now().naive_local();
+    let result = sqlx::query!(
+        "INSERT INTO webhook_deliveries
+        (webhook_id, event_type, payload, status, next_attempt_at, created_at)
+        SELECT id, $1, $2, $3, $4, $4 FROM webhooks
+        WHERE tournament_id = $5 AND $1 = ANY(event_types)",
+        event_type.as_str(),
+        payload,
+        WebhookDeliveryStatus::Pending.as_str(),
+        now,
+        tournament_id
+    )
+    .execute(executor)
+    .await
+    .map_err(|err| {
+        error!("Failed to insert webhook deliveries {}", err);
+        err
+    })?;
+    Ok(result.rows_affected())
+}
+
+#[async_trait]
+impl WebhookStore for PgPool {
+    #[tracing::instrument(name = "Inserting webhook", skip(self, secret))]
+    async fn insert_webhook(
+        &self,
+        tournament_id: i32,
+        url: &str,
+        event_types: &[TournamentEventType],
+        secret: &str,
+        created_by: Uuid,
+    ) -> Result<Webhook, sqlx::Error> {
+        let event_types: Vec<String> = event_types
+            .iter()
+            .map(|event_type| event_type.as_str().to_string())
+            .collect();
+        let row = sqlx::query_as!(
+            WebhookRow,
+            "INSERT INTO webhooks (id, tournament_id, url, event_types, secret, created_by, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id, tournament_id, url, event_types, created_by, created_at",
+            Uuid::new_v4(),
+            tournament_id,
+            url,
+            &event_types,
+            secret,
+            created_by,
+            Local::now().naive_local()
+        )
+        .fetch_one(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to insert webhook {}", err);
+            err
+        })?;
+        Ok(row.into())
+    }
+
+    #[tracing::instrument(name = "Fetching webhooks", skip(self))]
+    async fn get_webhooks(&self, tournament_id: i32) -> Result<Vec<Webhook>, sqlx::Error> {
+        let rows = sqlx::query_as!(
+            WebhookRow,
+            "SELECT id, tournament_id, url, event_types, created_by, created_at
+            FROM webhooks WHERE tournament_id = $1 ORDER BY created_at ASC",
+            tournament_id
+        )
+        .fetch_all(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to fetch webhooks {}", err);
+            err
+        })?;
+        Ok(rows.into_iter().map(Webhook::from).collect())
+    }
+
+    #[tracing::instrument(name = "Deleting webhook", skip(self))]
+    async fn delete_webhook(&self, tournament_id: i32, id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "DELETE FROM webhooks WHERE tournament_id = $1 AND id = $2",
+            tournament_id,
+            id
+        )
+        .execute(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to delete webhook {}", err);
+            err
+        })?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "Fetching webhook deliveries", skip(self))]
+    async fn get_webhook_deliveries(
+        &self,
+        webhook_id: Uuid,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+        let rows = sqlx::query_as!(
+            WebhookDeliveryRow,
+            "SELECT id, webhook_id, event_type, status, attempts, status_code, last_error,
+            next_attempt_at, last_attempt_at, created_at
+            FROM webhook_deliveries WHERE webhook_id = $1
+            ORDER BY id DESC LIMIT $2",
+            webhook_id,
+            limit
+        )
+        .fetch_all(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to fetch webhook deliveries {}", err);
+            err
+        })?;
+        Ok(rows.into_iter().map(WebhookDelivery::from).collect())
+    }
+
+    #[tracing::instrument(name = "Claiming webhook deliveries", skip(self))]
+    async fn claim_webhook_deliveries(
+        &self,
+        limit: i64,
+        lease_until: NaiveDateTime,
+    ) -> Result<Vec<PendingWebhookDelivery>, sqlx::Error> {
+        sqlx::query_as!(
+            PendingWebhookDelivery,
+            r#"UPDATE webhook_deliveries SET next_attempt_at = $1
+            FROM webhooks
+            WHERE webhooks.id = webhook_deliveries.webhook_id
+            AND webhook_deliveries.id IN (
+                SELECT id FROM webhook_deliveries
+                WHERE status = $2 AND next_attempt_at <= $3
+                ORDER BY next_attempt_at, id
+                LIMIT $4
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING webhook_deliveries.id, webhooks.url, webhooks.secret,
+            webhook_deliveries.event_type, webhook_deliveries.payload, webhook_deliveries.attempts"#,
+            lease_until,
+            WebhookDeliveryStatus::Pending.as_str(),
+            Local::now().naive_local(),
+            limit
+        )
+        .fetch_all(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to claim webhook deliveries {}", err);
+            err
+        })
+    }
+
+    #[tracing::instrument(name = "Updating webhook delivery", skip(self))]
+    async fn update_webhook_delivery(
+        &self,
+        id: i64,
+        attempt: WebhookDeliveryAttempt,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE webhook_deliveries SET status = $1, attempts = $2, status_code = $3,
+            last_error = $4, next_attempt_at = $5, last_attempt_at = $6
+            WHERE id = $7",
+            attempt.status.as_str(),
+            attempt.attempts,
+            attempt.status_code,
+            attempt.error,
+            attempt.next_attempt_at,
+            Local::now().naive_local(),
+            id
+        )
+        .execute(self)
+        .await
+        .map_err(|err| {
+            error!("Failed to update webhook delivery {}", err);
+            err
+        })?;
+        Ok(())
+    }
+}
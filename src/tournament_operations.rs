@@ -0,0 +1,154 @@
+use crate::{
+    authentication::UserInfo,
+    stores::{
+        tournament_role_store::{TournamentRole, TournamentRoleStore},
+        tournament_store::{
+            has_matches_outside_dates, lock_tournament, Tournament, TournamentStore,
+        },
+        user_store::UserStore,
+    },
+    ServerError,
+};
+use chrono::{Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::{error, info};
+use uuid::Uuid;
+
+// Fields that are left out are kept as they are
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TournamentPatch {
+    pub name: Option<String>,
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RolePayload {
+    pub email: String,
+    pub role: TournamentRole,
+}
+
+pub fn check_valid_dates(start_date: NaiveDate, end_date: NaiveDate) -> Result<(), ServerError> {
+    if start_date > end_date || start_date < Local::today().naive_local() {
+        Err(ServerError::InvalidDate)
+    } else {
+        Ok(())
+    }
+}
+
+#[tracing::instrument(name = "Update tournament", skip(storage))]
+pub async fn update_tournament(
+    storage: &PgPool,
+    tournament_id: i32,
+    patch: TournamentPatch,
+) -> Result<Tournament, ServerError> {
+    let mut transaction = storage.begin().await?;
+    // Matches can't be added while the dates are checked against them
+    let current = lock_tournament(&mut transaction, tournament_id)
+        .await?
+        .ok_or(ServerError::TournamentNotFound)?;
+    let updated = Tournament {
+        id: tournament_id,
+        name: patch.name.unwrap_or_else(|| current.name.clone()),
+        start_date: patch.start_date.unwrap_or(current.start_date),
+        end_date: patch.end_date.unwrap_or(current.end_date),
+    };
+
+    // Ongoing tournaments have started in the past, they can still be renamed
+    // and have their end date moved as long as the start date stays the same
+    if updated.start_date != current.start_date {
+        check_valid_dates(updated.start_date, updated.end_date)?;
+    } else if updated.start_date > updated.end_date {
+        return Err(ServerError::InvalidDate);
+    }
+    if (updated.start_date != current.start_date || updated.end_date != current.end_date)
+        && has_matches_outside_dates(
+            &mut transaction,
+            tournament_id,
+            updated.start_date,
+            updated.end_date,
+        )
+        .await?
+    {
+        return Err(ServerError::MatchesOutsideTournamentDates);
+    }
+
+    if !crate::stores::tournament_store::update_tournament(&mut transaction, &updated).await? {
+        return Err(ServerError::TournamentNotFound);
+    }
+    transaction
+        .commit()
+        .await
+        .inspect_err(|_| error!("Transaction failed!"))?;
+    info!("Updated tournament: {}", tournament_id);
+    Ok(updated)
+}
+
+#[tracing::instrument(name = "Delete tournament", skip(storage))]
+pub async fn delete_tournament(
+    storage: &PgPool,
+    tournament_id: i32,
+    user: &UserInfo,
+) -> Result<(), ServerError> {
+    check_tournament_owner(storage, tournament_id, user).await?;
+    if !storage.delete_tournament(tournament_id).await? {
+        return Err(ServerError::TournamentNotFound);
+    }
+    info!("Deleted tournament: {}", tournament_id);
+    Ok(())
+}
+
+#[tracing::instrument(name = "Grant tournament role", skip(storage))]
+pub async fn grant_tournament_role(
+    storage: &PgPool,
+    tournament_id: i32,
+    owner: &UserInfo,
+    payload: RolePayload,
+) -> Result<(), ServerError> {
+    check_tournament_owner(storage, tournament_id, owner).await?;
+    let user = storage
+        .find_user(&payload.email)
+        .await
+        .ok_or(ServerError::UserNotFound)?;
+    storage
+        .grant_tournament_role(tournament_id, user.id, payload.role)
+        .await?;
+    info!(
+        "User {} is now {:?} in tournament: {}",
+        user.id, payload.role, tournament_id
+    );
+    Ok(())
+}
+
+#[tracing::instrument(name = "Revoke tournament role", skip(storage))]
+pub async fn revoke_tournament_role(
+    storage: &PgPool,
+    tournament_id: i32,
+    owner: &UserInfo,
+    user_id: Uuid,
+) -> Result<(), ServerError> {
+    check_tournament_owner(storage, tournament_id, owner).await?;
+    if !storage
+        .revoke_tournament_role(tournament_id, user_id)
+        .await?
+    {
+        return Err(ServerError::UserNotFound);
+    }
+    Ok(())
+}
+
+// Admins are treated as owners of every tournament
+async fn check_tournament_owner(
+    storage: &PgPool,
+    tournament_id: i32,
+    user: &UserInfo,
+) -> Result<(), ServerError> {
+    if storage.get_tournament(tournament_id).await?.is_none() {
+        return Err(ServerError::TournamentNotFound);
+    }
+    if !user.is_admin() && storage.get_tournament_owner(tournament_id).await? != Some(user.id) {
+        return Err(ServerError::NotTournamentOwner);
+    }
+    Ok(())
+}
@@ -0,0 +1,311 @@
+use crate::{
+    authentication::{authorize_tournament_action, generate_token, UserInfo},
+    configuration::WebhookSettings,
+    events::{TournamentEvent, TournamentEventType},
+    stores::{
+        tournament_role_store::TournamentRole,
+        webhook_store::{
+            insert_webhook_deliveries, PendingWebhookDelivery, Webhook, WebhookDeliveryAttempt,
+            WebhookDeliveryStatus, WebhookStore,
+        },
+    },
+    ServerError,
+};
+use actix_web::{
+    rt::{spawn, time::delay_for},
+    web,
+};
+use chrono::{Duration, Local};
+use futures::future::join_all;
+use reqwest::{header, redirect::Policy, Client, Url};
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, ToSocketAddrs};
+use tracing::{error, info, warn};
+
+// The hex encoded HMAC-SHA256 of the body using the secret of the webhook, ex "sha256=4f2a..."
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+// Stays the same when a delivery is retried so receivers can ignore duplicates
+pub const DELIVERY_HEADER: &str = "X-Webhook-Delivery";
+
+// Deliveries sent at a time by each instance
+const DELIVERY_BATCH_SIZE: i64 = 10;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookPayload {
+    pub url: String,
+    pub event_types: Vec<TournamentEventType>,
+}
+
+// The secret is only returned here
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewWebhook {
+    pub webhook: Webhook,
+    pub secret: String,
+}
+
+// The body that's posted to the webhooks
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct WebhookEvent {
+    pub tournament_id: i32,
+    pub event: TournamentEvent,
+}
+
+fn is_public_ipv4(address: Ipv4Addr) -> bool {
+    let [first, second, ..] = address.octets();
+    !(address.is_unspecified()
+        || address.is_loopback()
+        || address.is_private()
+        // Includes the cloud metadata address 169.254.169.254
+        || address.is_link_local()
+        || address.is_broadcast()
+        || address.is_documentation()
+        || address.is_multicast()
+        // 0.0.0.0/8
+        || first == 0
+        // Carrier grade NAT 100.64.0.0/10
+        || (first == 100 && (64..128).contains(&second))
+        // Benchmarking 198.18.0.0/15
+        || (first == 198 && (18..20).contains(&second))
+        // Reserved 240.0.0.0/4
+        || first >= 240)
+}
+
+fn is_public_ipv6(address: Ipv6Addr) -> bool {
+    // IPv4 mapped and compatible addresses, includes ::1 and ::
+    if let Some(address) = address.to_ipv4() {
+        return is_public_ipv4(address);
+    }
+    let first_segment = address.segments()[0];
+    !(address.is_multicast()
+        // Unique local fc00::/7
+        || (first_segment & 0xfe00) == 0xfc00
+        // Link local fe80::/10
+        || (first_segment & 0xffc0) == 0xfe80
+        // Documentation 2001:db8::/32
+        || (first_segment == 0x2001 && address.segments()[1] == 0x0db8))
+}
+
+fn is_public_address(address: IpAddr) -> bool {
+    match address {
+        IpAddr::V4(address) => is_public_ipv4(address),
+        IpAddr::V6(address) => is_public_ipv6(address),
+    }
+}
+
+// Webhooks are only sent to http(s) urls whose host resolves to public addresses, so they
+// can't be used to reach the internal network of the server. Checked again before every
+// delivery since the host may resolve to something else by then.
+async fn check_webhook_url(url: &str, settings: &WebhookSettings) -> Result<(), String> {
+    let url = Url::parse(url).map_err(|err| format!("Invalid url: {}", err))?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(format!("Unsupported url scheme: {}", url.scheme()));
+    }
+    let host = url.host_str().unwrap_or_default();
+    if settings
+        .allowed_private_hosts
+        .iter()
+        .any(|allowed| allowed == host)
+    {
+        return Ok(());
+    }
+    // The url parser normalizes addresses, ex 0x7f.1 -> 127.0.0.1, and brackets IPv6 ones
+    let addresses: Vec<IpAddr> = match host.trim_start_matches('[').trim_end_matches(']').parse() {
+        Ok(address) => vec![address],
+        Err(_) => {
+            let domain = host.to_string();
+            let port = url.port_or_known_default().unwrap_or(80);
+            web::block(move || {
+                (domain.as_str(), port)
+                    .to_socket_addrs()
+                    .map(|addresses| addresses.map(|address| address.ip()).collect())
+            })
+            .await
+            .map_err(|err| format!("Failed to resolve {}: {}", host, err))?
+        }
+    };
+    if addresses.is_empty() {
+        return Err(format!("No addresses found for {}", host));
+    }
+    if let Some(address) = addresses
+        .iter()
+        .find(|address| !is_public_address(**address))
+    {
+        return Err(format!(
+            "{} resolves to non public address {}",
+            host, address
+        ));
+    }
+    Ok(())
+}
+
+pub async fn create_webhook(
+    storage: &PgPool,
+    settings: &WebhookSettings,
+    tournament_id: i32,
+    user: &UserInfo,
+    payload: WebhookPayload,
+) -> Result<NewWebhook, ServerError> {
+    authorize_tournament_action(storage, tournament_id, user, TournamentRole::MANAGE).await?;
+    if payload.event_types.is_empty() {
+        return Err(ServerError::InvalidWebhook);
+    }
+    if let Err(err) = check_webhook_url(&payload.url, settings).await {
+        info!("Rejected webhook url: {}", err);
+        return Err(ServerError::InvalidWebhook);
+    }
+    let secret = generate_token().map_err(|err| {
+        error!("Failed to generate webhook secret: {}", err);
+        ServerError::InvalidWebhook
+    })?;
+    let webhook = storage
+        .insert_webhook(
+            tournament_id,
+            &payload.url,
+            &payload.event_types,
+            &secret,
+            user.id,
+        )
+        .await?;
+    info!(
+        "Created webhook {} for tournament: {}",
+        webhook.id, tournament_id
+    );
+    Ok(NewWebhook { webhook, secret })
+}
+
+// Adds the event to the outbox of the webhooks that want it, in the same transaction
+// as the change that caused the event so neither is kept without the other
+pub async fn queue_webhooks(
+    transaction: &mut Transaction<'_, Postgres>,
+    tournament_id: i32,
+    event: &TournamentEvent,
+) -> Result<(), sqlx::Error> {
+    let payload = WebhookEvent {
+        tournament_id,
+        event: event.clone(),
+    };
+    let payload = match serde_json::to_string(&payload) {
+        Ok(payload) => payload,
+        Err(err) => {
+            error!("Failed to serialize webhook event {}", err);
+            return Ok(());
+        }
+    };
+    insert_webhook_deliveries(transaction, tournament_id, event.event_type(), &payload).await?;
+    Ok(())
+}
+
+pub fn sign_payload(secret: &str, payload: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let signature = hmac::sign(&key, payload.as_bytes());
+    let hex: String = signature
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", hex)
+}
+
+// Starts sending the deliveries in the outbox. Every instance runs one, a delivery
+// is only claimed by one of them at a time.
+pub fn start_webhook_dispatcher(storage: PgPool, settings: WebhookSettings) {
+    let client = Client::builder()
+        .timeout(std::time::Duration::from_secs(
+            settings.request_timeout_seconds,
+        ))
+        // A public url could otherwise redirect to the internal network
+        .redirect(Policy::none())
+        .build()
+        .expect("Failed to build webhook http client");
+    let poll_interval = std::time::Duration::from_millis(settings.poll_interval_milliseconds);
+    spawn(async move {
+        loop {
+            // Long enough for every request in the batch to time out
+            let lease_until = Local::now().naive_local()
+                + Duration::seconds(2 * settings.request_timeout_seconds as i64);
+            match storage
+                .claim_webhook_deliveries(DELIVERY_BATCH_SIZE, lease_until)
+                .await
+            {
+                Ok(deliveries) if !deliveries.is_empty() => {
+                    join_all(deliveries.into_iter().map(|delivery| {
+                        send_webhook_delivery(&storage, &client, &settings, delivery)
+                    }))
+                    .await;
+                }
+                // Errors are logged by the store
+                _ => delay_for(poll_interval).await,
+            }
+        }
+    });
+}
+
+// Returns if the delivery succeeded, the status code of the response and the request error
+async fn post_webhook_delivery(
+    client: &Client,
+    delivery: &PendingWebhookDelivery,
+) -> (bool, Option<i32>, Option<String>) {
+    let response = client
+        .post(&delivery.url)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, &delivery.event_type)
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .header(
+            SIGNATURE_HEADER,
+            sign_payload(&delivery.secret, &delivery.payload),
+        )
+        .body(delivery.payload.clone())
+        .send()
+        .await;
+    match response {
+        Ok(response) => (
+            response.status().is_success(),
+            Some(i32::from(response.status().as_u16())),
+            None,
+        ),
+        Err(err) => (false, None, Some(err.to_string())),
+    }
+}
+
+async fn send_webhook_delivery(
+    storage: &PgPool,
+    client: &Client,
+    settings: &WebhookSettings,
+    delivery: PendingWebhookDelivery,
+) {
+    let attempts = delivery.attempts + 1;
+    let (delivered, status_code, error) = match check_webhook_url(&delivery.url, settings).await {
+        Ok(()) => post_webhook_delivery(client, &delivery).await,
+        Err(err) => {
+            warn!("Not sending webhook delivery {}: {}", delivery.id, err);
+            (false, None, Some(err))
+        }
+    };
+    let status = if delivered {
+        WebhookDeliveryStatus::Delivered
+    } else if attempts >= settings.max_attempts {
+        warn!(
+            "Giving up on webhook delivery {} after {} attempts",
+            delivery.id, attempts
+        );
+        WebhookDeliveryStatus::Failed
+    } else {
+        WebhookDeliveryStatus::Pending
+    };
+    // Waits retry_backoff_seconds after the first failure and twice as long after each one after that
+    let backoff = settings
+        .retry_backoff_seconds
+        .saturating_mul(1 << (attempts - 1).clamp(0, 16));
+    let attempt = WebhookDeliveryAttempt {
+        status,
+        attempts,
+        status_code,
+        error,
+        next_attempt_at: Local::now().naive_local() + Duration::seconds(backoff as i64),
+    };
+    // Errors are logged by the store, the delivery is retried once the lease runs out
+    let _ = storage.update_webhook_delivery(delivery.id, attempt).await;
+}
@@ -0,0 +1,94 @@
+use chrono::{Duration, Local};
+use common::spawn_server_and_authenticate;
+use reqwest::StatusCode;
+use tournament_tracker_backend::{
+    endpoints::CredentialsPayload,
+    stores::{
+        tournament_store::Tournament,
+        user_store::{User, UserRole},
+    },
+    tournament_operations::TournamentPatch,
+};
+use uuid::Uuid;
+
+mod common;
+
+#[actix_rt::test]
+async fn should_only_allow_admins_to_manage_users() {
+    let client = spawn_server_and_authenticate().await;
+    let admin = client.new_admin("admin@test.se").await;
+
+    let response = client.get_users().await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = admin.get_users().await;
+    assert!(response.status().is_success());
+    let users = response.json::<Vec<User>>().await.unwrap();
+    let roles: Vec<(&str, UserRole)> = users
+        .iter()
+        .map(|user| (user.email.as_str(), user.role))
+        .collect();
+    assert_eq!(
+        roles,
+        vec![
+            ("admin@test.se", UserRole::Admin),
+            ("dummy@test.se", UserRole::User)
+        ]
+    );
+
+    let dummy_id = users[1].id;
+    let response = client.set_user_role(dummy_id, UserRole::Admin).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = admin.set_user_role(Uuid::new_v4(), UserRole::Admin).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = admin.set_user_role(dummy_id, UserRole::Admin).await;
+    assert!(response.status().is_success());
+
+    // The old token still says user so it has to log in again
+    let response = client.get_users().await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let client = client
+        .unauthenticated_client
+        .authenticate(&CredentialsPayload {
+            email: "dummy@test.se".to_string(),
+            password: "some-secure-password".to_string(),
+        })
+        .await;
+    let response = client.get_users().await;
+    assert!(response.status().is_success());
+}
+
+#[actix_rt::test]
+async fn should_allow_admins_to_manage_all_tournaments() {
+    let client = spawn_server_and_authenticate().await;
+    let admin = client.new_admin("admin@test.se").await;
+    let other_user = client.new_user("other@test.se").await;
+
+    let start_date = Local::today().naive_local();
+    let tournament = Tournament {
+        id: 0, // doesn't matter
+        name: "Södertälje open".into(),
+        start_date,
+        end_date: start_date + Duration::days(1),
+    };
+    let response = client.insert_tournament(&tournament).await;
+    assert!(response.status().is_success());
+    let tournament_id = response.text().await.unwrap().parse::<i32>().unwrap();
+
+    let patch = TournamentPatch {
+        name: Some("Stockholm open".to_string()),
+        start_date: None,
+        end_date: None,
+    };
+    let response = other_user.patch_tournament(tournament_id, &patch).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = admin.patch_tournament(tournament_id, &patch).await;
+    assert!(response.status().is_success());
+
+    let response = other_user.delete_tournament(tournament_id).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = admin.delete_tournament(tournament_id).await;
+    assert!(response.status().is_success());
+    let response = client.get_tournament(tournament_id).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,161 @@
+use chrono::{Duration, Local};
+use common::{spawn_server_and_authenticate, AuthenticatedClient};
+use reqwest::StatusCode;
+use tournament_tracker_backend::{
+    authentication::{ApiKeyPayload, NewApiKey},
+    endpoints::PlayerMatchRegistrationPayload,
+    stores::{
+        api_key_store::{ApiKey, ApiKeyScope},
+        match_store::{Match, MatchOutcome, MatchResult},
+        player_registration_store::PlayerMatchRegistration,
+        player_store::Player,
+        tournament_store::Tournament,
+    },
+};
+
+mod common;
+
+// Inserts a tournament owned by the client with a court and a match between player 0 and 1
+async fn insert_tournament_and_match(client: &AuthenticatedClient) -> (i32, i64) {
+    let start_date = Local::today().naive_local();
+    let tournament = Tournament {
+        id: 0, // doesn't matter
+        name: "Södertälje open".into(),
+        start_date,
+        end_date: start_date + Duration::days(1),
+    };
+    let response = client.insert_tournament(&tournament).await;
+    assert!(response.status().is_success());
+    let tournament_id = response.text().await.unwrap().parse::<i32>().unwrap();
+
+    let response = client
+        .add_court_to_tournament(tournament_id, "Bana 1".to_string())
+        .await;
+    assert!(response.status().is_success());
+
+    for id in 0..2 {
+        let player = Player {
+            id,
+            name: format!("Spelare {}", id),
+        };
+        let response = client.insert_player(&player).await;
+        assert!(response.status().is_success());
+    }
+    let match_data = Match {
+        id: 0, // not important
+        player_one: Some(0),
+        player_two: Some(1),
+        tournament_id,
+        class: "p96".to_string(),
+        start_time: Local::now().naive_local() + Duration::hours(2),
+    };
+    let response = client.insert_match(&match_data).await;
+    assert!(response.status().is_success());
+    let match_id = response.text().await.unwrap().parse::<i64>().unwrap();
+    (tournament_id, match_id)
+}
+
+fn register_payload(player_id: i64) -> PlayerMatchRegistrationPayload {
+    PlayerMatchRegistrationPayload { player_id }
+}
+
+fn kiosk_key() -> ApiKeyPayload {
+    ApiKeyPayload {
+        name: "Kiosk 1".to_string(),
+        scopes: vec![ApiKeyScope::RegisterPlayer],
+    }
+}
+
+#[actix_rt::test]
+async fn should_register_players_with_api_key() {
+    let client = spawn_server_and_authenticate().await;
+    let (tournament_id, match_id) = insert_tournament_and_match(&client).await;
+
+    let response = client.create_api_key(tournament_id, &kiosk_key()).await;
+    assert!(response.status().is_success());
+    let new_api_key = response.json::<NewApiKey>().await.unwrap();
+    let kiosk = client.with_token(&new_api_key.key);
+
+    // The key name is recorded instead of the name in the payload
+    let response = kiosk.register_player(match_id, &register_payload(0)).await;
+    assert!(response.status().is_success());
+    let registration = response.json::<PlayerMatchRegistration>().await.unwrap();
+    assert_eq!(registration.registerd_by, "Kiosk 1");
+    assert_eq!(
+        registration.registerd_by_api_key,
+        Some(new_api_key.api_key.id)
+    );
+    assert_eq!(registration.registerd_by_user, None);
+
+    // Only the actions in the scopes are allowed
+    let result = MatchResult {
+        result: "6-0 6-0".to_string(),
+        winner: 0,
+        outcome: MatchOutcome::Walkover,
+    };
+    let response = kiosk.finish_match(match_id, &result).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = kiosk.get_api_keys(tournament_id).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = client.get_api_keys(tournament_id).await;
+    assert!(response.status().is_success());
+    let api_keys = response.json::<Vec<ApiKey>>().await.unwrap();
+    assert_eq!(api_keys, vec![new_api_key.api_key.clone()]);
+
+    let response = client
+        .revoke_api_key(tournament_id, new_api_key.api_key.id)
+        .await;
+    assert!(response.status().is_success());
+    let response = kiosk.register_player(match_id, &register_payload(1)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = client
+        .revoke_api_key(tournament_id, new_api_key.api_key.id)
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn should_only_allow_api_keys_in_their_tournament() {
+    let client = spawn_server_and_authenticate().await;
+    let other_user = client.new_user("other@test.se").await;
+    let (tournament_id, _) = insert_tournament_and_match(&client).await;
+    let response = other_user.create_api_key(tournament_id, &kiosk_key()).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let no_scopes = ApiKeyPayload {
+        name: "Kiosk 1".to_string(),
+        scopes: Vec::new(),
+    };
+    let response = client.create_api_key(tournament_id, &no_scopes).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = client.create_api_key(tournament_id, &kiosk_key()).await;
+    let kiosk = client.with_token(&response.json::<NewApiKey>().await.unwrap().key);
+
+    // A match in another tournament
+    let start_date = Local::today().naive_local();
+    let tournament = Tournament {
+        id: 0, // doesn't matter
+        name: "Stockholm open".into(),
+        start_date,
+        end_date: start_date + Duration::days(1),
+    };
+    let response = client.insert_tournament(&tournament).await;
+    let other_tournament_id = response.text().await.unwrap().parse::<i32>().unwrap();
+    let match_data = Match {
+        id: 0, // not important
+        player_one: Some(0),
+        player_two: Some(1),
+        tournament_id: other_tournament_id,
+        class: "p96".to_string(),
+        start_time: Local::now().naive_local() + Duration::hours(2),
+    };
+    let response = client.insert_match(&match_data).await;
+    let other_match_id = response.text().await.unwrap().parse::<i64>().unwrap();
+
+    let response = kiosk
+        .register_player(other_match_id, &register_payload(0))
+        .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
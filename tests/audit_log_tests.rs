@@ -0,0 +1,180 @@
+use chrono::{Duration, Local};
+use common::{spawn_server_and_authenticate, AuthenticatedClient};
+use reqwest::StatusCode;
+use tournament_tracker_backend::{
+    endpoints::{AuditLogQuery, PlayerMatchRegistrationPayload},
+    stores::{
+        audit_log_store::AuditLogEntry,
+        match_store::{Match, MatchOutcome, MatchResult},
+        player_store::Player,
+        tournament_role_store::TournamentRole,
+        tournament_store::Tournament,
+    },
+    tournament_operations::RolePayload,
+};
+use uuid::Uuid;
+
+mod common;
+
+// Inserts a tournament owned by the client with a court and a match between player 0 and 1
+async fn insert_tournament_and_match(client: &AuthenticatedClient) -> (i32, i64) {
+    let start_date = Local::today().naive_local();
+    let tournament = Tournament {
+        id: 0, // doesn't matter
+        name: "Södertälje open".into(),
+        start_date,
+        end_date: start_date + Duration::days(1),
+    };
+    let response = client.insert_tournament(&tournament).await;
+    assert!(response.status().is_success());
+    let tournament_id = response.text().await.unwrap().parse::<i32>().unwrap();
+
+    let response = client
+        .add_court_to_tournament(tournament_id, "Bana 1".to_string())
+        .await;
+    assert!(response.status().is_success());
+
+    for id in 0..2 {
+        let player = Player {
+            id,
+            name: format!("Spelare {}", id),
+        };
+        let response = client.insert_player(&player).await;
+        assert!(response.status().is_success());
+    }
+    let match_data = Match {
+        id: 0, // not important
+        player_one: Some(0),
+        player_two: Some(1),
+        tournament_id,
+        class: "p96".to_string(),
+        start_time: Local::now().naive_local() + Duration::hours(2),
+    };
+    let response = client.insert_match(&match_data).await;
+    assert!(response.status().is_success());
+    let match_id = response.text().await.unwrap().parse::<i64>().unwrap();
+    (tournament_id, match_id)
+}
+
+async fn get_audit_log(
+    client: &AuthenticatedClient,
+    tournament_id: i32,
+    query: &AuditLogQuery,
+) -> Vec<AuditLogEntry> {
+    let response = client.get_audit_log(tournament_id, query).await;
+    assert!(response.status().is_success());
+    response.json::<Vec<AuditLogEntry>>().await.unwrap()
+}
+
+async fn get_user_id(client: &AuthenticatedClient, email: &str) -> Uuid {
+    let row: (Uuid,) = sqlx::query_as("SELECT id FROM users WHERE email = $1")
+        .bind(email)
+        .fetch_one(&client.unauthenticated_client.db_pool)
+        .await
+        .unwrap();
+    row.0
+}
+
+#[actix_rt::test]
+async fn should_record_mutating_endpoints() {
+    let client = spawn_server_and_authenticate().await;
+    let (tournament_id, match_id) = insert_tournament_and_match(&client).await;
+    for player_id in 0..2 {
+        let response = client
+            .register_player(match_id, &PlayerMatchRegistrationPayload { player_id })
+            .await;
+        assert!(response.status().is_success());
+    }
+    let result = MatchResult {
+        result: "6-0 6-0".to_string(),
+        winner: 0,
+        outcome: MatchOutcome::Completed,
+    };
+    let response = client.finish_match(match_id, &result).await;
+    assert!(response.status().is_success());
+
+    // Newest first
+    let entries = get_audit_log(&client, tournament_id, &AuditLogQuery::default()).await;
+    let endpoints: Vec<&str> = entries
+        .iter()
+        .map(|entry| entry.endpoint.as_str())
+        .collect();
+    assert_eq!(
+        endpoints,
+        vec![
+            "finish_match",
+            "register_player",
+            "register_player",
+            "insert_match",
+            "add_court_to_tournament",
+            "insert_tournament"
+        ]
+    );
+    let user_id = get_user_id(&client, "dummy@test.se").await;
+    assert!(entries
+        .iter()
+        .all(|entry| entry.user_id == Some(user_id) && entry.tournament_id == Some(tournament_id)));
+
+    let finish_entry = &entries[0];
+    assert_eq!(finish_entry.match_id, Some(match_id));
+    assert_eq!(finish_entry.before.as_ref().unwrap()["id"], match_id);
+    assert_eq!(finish_entry.after.as_ref().unwrap()["winner"], 0);
+    assert_eq!(finish_entry.after.as_ref().unwrap()["result"], "6-0 6-0");
+
+    let query = AuditLogQuery {
+        endpoint: Some("register_player".to_string()),
+        player_id: Some(1),
+        ..Default::default()
+    };
+    let entries = get_audit_log(&client, tournament_id, &query).await;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].player_id, Some(1));
+    assert_eq!(entries[0].match_id, Some(match_id));
+
+    let query = AuditLogQuery {
+        page: Some(1),
+        per_page: Some(4),
+        ..Default::default()
+    };
+    let entries = get_audit_log(&client, tournament_id, &query).await;
+    let endpoints: Vec<&str> = entries
+        .iter()
+        .map(|entry| entry.endpoint.as_str())
+        .collect();
+    assert_eq!(
+        endpoints,
+        vec!["add_court_to_tournament", "insert_tournament"]
+    );
+
+    // Entries can't be changed afterwards
+    let result = sqlx::query("DELETE FROM audit_log")
+        .execute(&client.unauthenticated_client.db_pool)
+        .await;
+    assert!(result.is_err());
+}
+
+#[actix_rt::test]
+async fn should_only_show_audit_log_to_owners() {
+    let client = spawn_server_and_authenticate().await;
+    let organizer = client.new_user("organizer@test.se").await;
+    let (tournament_id, _) = insert_tournament_and_match(&client).await;
+    let response = client
+        .grant_tournament_role(
+            tournament_id,
+            &RolePayload {
+                email: "organizer@test.se".to_string(),
+                role: TournamentRole::Organizer,
+            },
+        )
+        .await;
+    assert!(response.status().is_success());
+
+    let response = organizer
+        .get_audit_log(tournament_id, &AuditLogQuery::default())
+        .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let admin = client.new_admin("admin@test.se").await;
+    let entries = get_audit_log(&admin, tournament_id, &AuditLogQuery::default()).await;
+    assert_eq!(entries.len(), 3);
+}
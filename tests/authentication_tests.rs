@@ -1,11 +1,15 @@
 mod common;
 
 use chrono::{Duration, Local};
-use common::spawn_server_and_authenticate;
+use common::{spawn_server_and_authenticate, AuthenticatedClient};
 use reqwest::StatusCode;
 use tournament_tracker_backend::{
+    authentication::AuthTokens,
     endpoints::{CredentialsPayload, PlayerMatchRegistrationPayload},
-    stores::{match_store::MatchResult, tournament_store::Tournament},
+    stores::{
+        match_store::{MatchOutcome, MatchResult},
+        tournament_store::Tournament,
+    },
 };
 
 #[actix_rt::test]
@@ -47,19 +51,14 @@ async fn should_not_allow_unauthenticated_requests_to_protected_endpoints() {
             &MatchResult {
                 result: "1-2 3-4 5-3".into(),
                 winner: 1,
+                outcome: MatchOutcome::Completed,
             },
         )
         .await;
     assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
 
     let response = client
-        .register_player(
-            0,
-            &PlayerMatchRegistrationPayload {
-                player_id: 0,
-                registered_by: "Gösta".into(),
-            },
-        )
+        .register_player(0, &PlayerMatchRegistrationPayload { player_id: 0 })
         .await;
     assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
 }
@@ -95,4 +94,244 @@ async fn should_block_deleted_users() {
     );
 }
 
+async fn insert_tournament_status(client: &AuthenticatedClient) -> StatusCode {
+    client
+        .insert_tournament(&Tournament {
+            id: 0,
+            name: "Dummy".to_string(),
+            start_date: Local::today().naive_local(),
+            end_date: Local::today().naive_local(),
+        })
+        .await
+        .status()
+}
+
+#[actix_rt::test]
+async fn should_rotate_refresh_tokens() {
+    let client = spawn_server_and_authenticate().await;
+    let unauthenticated_client = &client.unauthenticated_client;
+
+    let response = unauthenticated_client.refresh(&client.refresh_token).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let tokens = response.json::<AuthTokens>().await.unwrap();
+    assert_ne!(tokens.refresh_token, client.refresh_token);
+
+    let refreshed_client = AuthenticatedClient {
+        unauthenticated_client: unauthenticated_client.new_client(),
+        token: tokens.access_token,
+        refresh_token: tokens.refresh_token,
+    };
+    assert_eq!(
+        insert_tournament_status(&refreshed_client).await,
+        StatusCode::OK
+    );
+
+    // Reusing the old refresh token revokes the whole session
+    let response = unauthenticated_client.refresh(&client.refresh_token).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = unauthenticated_client
+        .refresh(&refreshed_client.refresh_token)
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        insert_tournament_status(&refreshed_client).await,
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[actix_rt::test]
+async fn should_revoke_session_on_logout() {
+    let client = spawn_server_and_authenticate().await;
+    let other_session = client
+        .unauthenticated_client
+        .new_client()
+        .authenticate(&CredentialsPayload {
+            email: "dummy@test.se".into(),
+            password: "some-secure-password".into(),
+        })
+        .await;
+
+    let response = client
+        .unauthenticated_client
+        .logout(&client.refresh_token)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        insert_tournament_status(&client).await,
+        StatusCode::UNAUTHORIZED
+    );
+    let response = client
+        .unauthenticated_client
+        .refresh(&client.refresh_token)
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Other devices are still logged in until all of them are logged out
+    assert_eq!(
+        insert_tournament_status(&other_session).await,
+        StatusCode::OK
+    );
+    let response = other_session.logout_all().await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        insert_tournament_status(&other_session).await,
+        StatusCode::UNAUTHORIZED
+    );
+}
+
 // TODO: Add tests for token expiration
+
+#[actix_rt::test]
+async fn should_refuse_unverified_users() {
+    let client = spawn_server_and_authenticate().await;
+    let unauthenticated_client = &client.unauthenticated_client;
+    let credentials = CredentialsPayload {
+        email: "unverified@test.se".into(),
+        password: "some-secure-password".into(),
+    };
+    let response = unauthenticated_client.create_user(&credentials).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = unauthenticated_client.login(&credentials).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = unauthenticated_client
+        .resend_verification_email("unverified@test.se")
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let emails = unauthenticated_client.mailer.sent_emails();
+    assert_eq!(
+        emails
+            .iter()
+            .filter(|email| email.to == "unverified@test.se")
+            .count(),
+        2
+    );
+
+    let response = unauthenticated_client
+        .verify_email("unverified@test.se")
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    // The link can only be used once
+    let response = unauthenticated_client
+        .verify_email("unverified@test.se")
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = unauthenticated_client.login(&credentials).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+fn dummy_credentials(password: &str) -> CredentialsPayload {
+    CredentialsPayload {
+        email: "dummy@test.se".into(),
+        password: password.into(),
+    }
+}
+
+#[actix_rt::test]
+async fn should_lock_out_email_after_failed_logins() {
+    let client = spawn_server_and_authenticate().await;
+    let other_user = client.new_user("other@test.se").await;
+    let unauthenticated_client = &client.unauthenticated_client;
+
+    // The threshold is 5 attempts in base.yaml
+    for _ in 0..5 {
+        let response = unauthenticated_client
+            .login(&dummy_credentials("wrong-password"))
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+    // Even the right password is refused during the lockout
+    let response = unauthenticated_client
+        .login(&dummy_credentials("some-secure-password"))
+        .await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after = response.headers()["Retry-After"]
+        .to_str()
+        .unwrap()
+        .parse::<u64>()
+        .unwrap();
+    assert!(0 < retry_after && retry_after <= 30);
+
+    // Other accounts aren't affected
+    let response = other_user
+        .unauthenticated_client
+        .login(&CredentialsPayload {
+            email: "other@test.se".into(),
+            password: "some-secure-password".into(),
+        })
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn should_lock_out_ip_after_failed_logins() {
+    let client = spawn_server_and_authenticate().await;
+    let unauthenticated_client = &client.unauthenticated_client;
+
+    // The threshold is 20 attempts in base.yaml. Changing the forwarded address doesn't
+    // help since the test client isn't a trusted proxy.
+    for attempt in 0..20 {
+        let response = unauthenticated_client
+            .login_forwarded_for(
+                &CredentialsPayload {
+                    email: format!("unknown{}@test.se", attempt),
+                    password: "some-secure-password".into(),
+                },
+                &format!("10.0.0.{}", attempt),
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+    let response = unauthenticated_client
+        .login_forwarded_for(&dummy_credentials("some-secure-password"), "10.0.1.1")
+        .await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().contains_key("Retry-After"));
+}
+
+#[actix_rt::test]
+async fn should_forgive_ip_failures_of_successful_login() {
+    let client = spawn_server_and_authenticate().await;
+    let unauthenticated_client = &client.unauthenticated_client;
+
+    // The user eventually remembers their password
+    for _ in 0..4 {
+        let response = unauthenticated_client
+            .login(&dummy_credentials("wrong-password"))
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+    let response = unauthenticated_client
+        .login(&dummy_credentials("some-secure-password"))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Their failures no longer count towards the threshold of 20 for the IP
+    for attempt in 0..19 {
+        let response = unauthenticated_client
+            .login(&CredentialsPayload {
+                email: format!("unknown{}@test.se", attempt),
+                password: "some-secure-password".into(),
+            })
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+    let response = unauthenticated_client
+        .login(&dummy_credentials("some-secure-password"))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Failures for other accounts aren't taken back by logging in
+    let response = unauthenticated_client
+        .login(&CredentialsPayload {
+            email: "unknown@test.se".into(),
+            password: "some-secure-password".into(),
+        })
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = unauthenticated_client
+        .login(&dummy_credentials("some-secure-password"))
+        .await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
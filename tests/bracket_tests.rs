@@ -0,0 +1,466 @@
+use chrono::{Duration, Local};
+use common::{spawn_server_and_authenticate, AuthenticatedClient};
+use reqwest::StatusCode;
+use tournament_tracker_backend::{
+    bracket_operations::DrawFormat,
+    endpoints::{DrawPayload, PlayerMatchRegistrationPayload},
+    match_operations::{MatchInfo, TournamentMatchList},
+    stores::{
+        bracket_store::{Advancing, BracketMatch, Draw},
+        match_store::{MatchOutcome, MatchResult},
+        player_store::Player,
+        tournament_store::Tournament,
+    },
+};
+
+mod common;
+
+async fn insert_tournament_and_players(client: &AuthenticatedClient, player_count: i64) -> i32 {
+    let start_date = Local::today().naive_local();
+    let tournament = Tournament {
+        id: 0, // doesn't matter
+        name: "Södertälje open".into(),
+        start_date,
+        end_date: start_date + Duration::days(1),
+    };
+
+    let response = client.insert_tournament(&tournament).await;
+    assert!(response.status().is_success());
+    let tournament_id = response.text().await.unwrap().parse::<i32>().unwrap();
+
+    for id in 1..=player_count {
+        let player = Player {
+            id,
+            name: format!("Spelare {}", id),
+        };
+        let response = client.insert_player(&player).await;
+        assert!(response.status().is_success());
+    }
+    tournament_id
+}
+
+fn draw_payload(seeds: Vec<i64>) -> DrawPayload {
+    DrawPayload {
+        class: "p96".to_string(),
+        start_time: Local::now().naive_local() + Duration::hours(2),
+        seeds,
+        format: DrawFormat::SingleElimination,
+    }
+}
+
+async fn play_match(client: &AuthenticatedClient, match_data: &BracketMatch, winner: i64) {
+    for player in [&match_data.player_one, &match_data.player_two].iter() {
+        let player_registration = PlayerMatchRegistrationPayload {
+            player_id: player.as_ref().unwrap().id,
+        };
+        let response = client
+            .register_player(match_data.id, &player_registration)
+            .await;
+        assert!(response.status().is_success());
+    }
+    // The score is written from player one's perspective
+    let result = if match_data.player_one.as_ref().unwrap().id == winner {
+        "6-3 6-4"
+    } else {
+        "3-6 4-6"
+    };
+    let response = client
+        .finish_match(
+            match_data.id,
+            &MatchResult {
+                result: result.to_string(),
+                winner,
+                outcome: MatchOutcome::Completed,
+            },
+        )
+        .await;
+    assert!(response.status().is_success());
+    let match_info = response.json::<MatchInfo>().await.unwrap();
+    assert_eq!(match_info.winner, Some(winner));
+}
+
+#[actix_rt::test]
+async fn should_generate_full_draw() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament_and_players(&client, 4).await;
+
+    let response = client
+        .generate_draw(tournament_id, &draw_payload(vec![1, 2, 3, 4]))
+        .await;
+    assert!(response.status().is_success());
+    let draw = response.json::<Vec<BracketMatch>>().await.unwrap();
+
+    assert_eq!(draw.len(), 3);
+    // Top seeds are placed in different halves of the draw
+    assert_eq!(draw[0].round, 1);
+    assert_eq!(draw[0].player_one.as_ref().unwrap().id, 1);
+    assert_eq!(draw[0].player_two.as_ref().unwrap().id, 4);
+    assert_eq!(draw[1].round, 1);
+    assert_eq!(draw[1].player_one.as_ref().unwrap().id, 2);
+    assert_eq!(draw[1].player_two.as_ref().unwrap().id, 3);
+    // The final is a placeholder waiting on the semi finals
+    let final_match = &draw[2];
+    assert_eq!(final_match.round, 2);
+    assert_eq!(final_match.player_one, None);
+    assert_eq!(final_match.player_two, None);
+    assert_eq!(final_match.player_one_from, Some(draw[0].id));
+    assert_eq!(final_match.player_two_from, Some(draw[1].id));
+
+    // The same draw can be fetched afterwards
+    let response = client.get_draw(tournament_id, "p96").await;
+    assert!(response.status().is_success());
+    assert_eq!(draw, response.json::<Vec<BracketMatch>>().await.unwrap());
+}
+
+#[actix_rt::test]
+async fn should_give_top_seeds_byes() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament_and_players(&client, 5).await;
+
+    let response = client
+        .generate_draw(tournament_id, &draw_payload(vec![1, 2, 3, 4, 5]))
+        .await;
+    assert!(response.status().is_success());
+    let draw = response.json::<Vec<BracketMatch>>().await.unwrap();
+
+    // 5 players need 4 matches to decide a winner
+    assert_eq!(draw.len(), 4);
+    // Only seed 4 and 5 play in the first round
+    assert_eq!(draw[0].round, 1);
+    assert_eq!(draw[0].player_one.as_ref().unwrap().id, 4);
+    assert_eq!(draw[0].player_two.as_ref().unwrap().id, 5);
+    // Seed 1 waits on the winner of the first round match
+    assert_eq!(draw[1].round, 2);
+    assert_eq!(draw[1].player_one.as_ref().unwrap().id, 1);
+    assert_eq!(draw[1].player_two, None);
+    assert_eq!(draw[1].player_two_from, Some(draw[0].id));
+    // Seed 2 and 3 both got byes
+    assert_eq!(draw[2].round, 2);
+    assert_eq!(draw[2].player_one.as_ref().unwrap().id, 2);
+    assert_eq!(draw[2].player_two.as_ref().unwrap().id, 3);
+    assert_eq!(draw[3].round, 3);
+    assert_eq!(draw[3].player_one_from, Some(draw[1].id));
+    assert_eq!(draw[3].player_two_from, Some(draw[2].id));
+}
+
+#[actix_rt::test]
+async fn should_not_generate_invalid_draws() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament_and_players(&client, 3).await;
+
+    // Not enough players
+    let response = client
+        .generate_draw(tournament_id, &draw_payload(vec![1]))
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Same player twice
+    let response = client
+        .generate_draw(tournament_id, &draw_payload(vec![1, 2, 1]))
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Unknown player
+    let response = client
+        .generate_draw(tournament_id, &draw_payload(vec![1, 2, 1337]))
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // Unknown tournament
+    let response = client
+        .generate_draw(tournament_id + 1, &draw_payload(vec![1, 2, 3]))
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = client
+        .generate_draw(tournament_id, &draw_payload(vec![1, 2, 3]))
+        .await;
+    assert!(response.status().is_success());
+
+    // Only one draw per class
+    let response = client
+        .generate_draw(tournament_id, &draw_payload(vec![3, 2, 1]))
+        .await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
+
+#[actix_rt::test]
+async fn should_generate_one_draw_per_class_concurrently() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament_and_players(&client, 4).await;
+
+    let single_elimination = draw_payload(vec![1, 2, 3, 4]);
+    let consolation = DrawPayload {
+        format: DrawFormat::Consolation,
+        ..draw_payload(vec![1, 2, 3, 4])
+    };
+    let (first, second) = futures::future::join(
+        client.generate_draw(tournament_id, &single_elimination),
+        client.generate_draw(tournament_id, &consolation),
+    )
+    .await;
+    let mut statuses = vec![first.status(), second.status()];
+    statuses.sort();
+    assert_eq!(statuses, vec![StatusCode::OK, StatusCode::CONFLICT]);
+
+    let response = client.get_draw(tournament_id, "p96").await;
+    let draw = response.json::<Vec<BracketMatch>>().await.unwrap();
+    let created: Vec<BracketMatch> = if first.status().is_success() {
+        first.json().await.unwrap()
+    } else {
+        second.json().await.unwrap()
+    };
+    assert_eq!(draw.len(), created.len());
+}
+
+#[actix_rt::test]
+async fn should_404_on_missing_draw() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament_and_players(&client, 0).await;
+    let response = client.get_draw(tournament_id, "p96").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn should_advance_winners_to_next_match() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament_and_players(&client, 4).await;
+    let response = client
+        .add_court_to_tournament(tournament_id, "Bana 1".to_string())
+        .await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .generate_draw(tournament_id, &draw_payload(vec![1, 2, 3, 4]))
+        .await;
+    assert!(response.status().is_success());
+    let draw = response.json::<Vec<BracketMatch>>().await.unwrap();
+    let final_id = draw[2].id;
+
+    // The final can't be played before the semi finals are finished
+    let player_registration = PlayerMatchRegistrationPayload { player_id: 1 };
+    let response = client.register_player(final_id, &player_registration).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Seed 4 wins the first semi final
+    play_match(&client, &draw[0], 4).await;
+    let response = client.get_draw(tournament_id, "p96").await;
+    let draw = response.json::<Vec<BracketMatch>>().await.unwrap();
+    assert_eq!(draw[0].winner, Some(4));
+    assert_eq!(draw[2].player_one.as_ref().unwrap().id, 4);
+    assert_eq!(draw[2].player_two, None);
+
+    // Seed 2 wins the second semi final
+    play_match(&client, &draw[1], 2).await;
+    let response = client.get_draw(tournament_id, "p96").await;
+    let draw = response.json::<Vec<BracketMatch>>().await.unwrap();
+    assert_eq!(draw[2].player_one.as_ref().unwrap().id, 4);
+    assert_eq!(draw[2].player_two.as_ref().unwrap().id, 2);
+
+    // Both finalists are known so the final can be played
+    play_match(&client, &draw[2], 2).await;
+    let response = client.get_draw(tournament_id, "p96").await;
+    let draw = response.json::<Vec<BracketMatch>>().await.unwrap();
+    assert_eq!(draw[2].winner, Some(2));
+}
+
+#[actix_rt::test]
+async fn should_advance_walkover_winner() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament_and_players(&client, 4).await;
+
+    let response = client
+        .generate_draw(tournament_id, &draw_payload(vec![1, 2, 3, 4]))
+        .await;
+    assert!(response.status().is_success());
+    let draw = response.json::<Vec<BracketMatch>>().await.unwrap();
+
+    // Seed 4 never showed up, no court is needed to give a walkover
+    let response = client
+        .finish_match(
+            draw[0].id,
+            &MatchResult {
+                result: "".to_string(),
+                winner: 1,
+                outcome: MatchOutcome::Walkover,
+            },
+        )
+        .await;
+    assert!(response.status().is_success());
+
+    let response = client.get_draw(tournament_id, "p96").await;
+    let draw = response.json::<Vec<BracketMatch>>().await.unwrap();
+    assert_eq!(draw[0].winner, Some(1));
+    assert_eq!(draw[2].player_one.as_ref().unwrap().id, 1);
+}
+
+#[actix_rt::test]
+async fn should_correct_advanced_player() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament_and_players(&client, 4).await;
+    let response = client
+        .add_court_to_tournament(tournament_id, "Bana 1".to_string())
+        .await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .generate_draw(tournament_id, &draw_payload(vec![1, 2, 3, 4]))
+        .await;
+    assert!(response.status().is_success());
+    let draw = response.json::<Vec<BracketMatch>>().await.unwrap();
+
+    // The result was entered with the wrong winner
+    play_match(&client, &draw[0], 4).await;
+    let correction = MatchResult {
+        result: "6-3 6-4".to_string(),
+        winner: 1,
+        outcome: MatchOutcome::Completed,
+    };
+    let response = client.correct_match_result(draw[0].id, &correction).await;
+    assert!(response.status().is_success());
+
+    let response = client.get_draw(tournament_id, "p96").await;
+    let draw = response.json::<Vec<BracketMatch>>().await.unwrap();
+    assert_eq!(draw[0].winner, Some(1));
+    assert_eq!(draw[2].player_one.as_ref().unwrap().id, 1);
+
+    // Once the final has started the semi final can't change winner anymore
+    play_match(&client, &draw[1], 2).await;
+    let player_registration = PlayerMatchRegistrationPayload { player_id: 1 };
+    let response = client
+        .register_player(draw[2].id, &player_registration)
+        .await;
+    assert!(response.status().is_success());
+    let correction = MatchResult {
+        result: "3-6 4-6".to_string(),
+        winner: 4,
+        outcome: MatchOutcome::Completed,
+    };
+    let response = client.correct_match_result(draw[0].id, &correction).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    // Correcting the score alone doesn't affect the final
+    let correction = MatchResult {
+        result: "6-3 6-3".to_string(),
+        winner: 1,
+        outcome: MatchOutcome::Completed,
+    };
+    let response = client.correct_match_result(draw[0].id, &correction).await;
+    assert!(response.status().is_success());
+}
+
+#[actix_rt::test]
+async fn should_route_first_round_losers_to_consolation_draw() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament_and_players(&client, 4).await;
+    let response = client
+        .add_court_to_tournament(tournament_id, "Bana 1".to_string())
+        .await;
+    assert!(response.status().is_success());
+
+    let payload = DrawPayload {
+        format: DrawFormat::Consolation,
+        ..draw_payload(vec![1, 2, 3, 4])
+    };
+    let response = client.generate_draw(tournament_id, &payload).await;
+    assert!(response.status().is_success());
+    let draw = response.json::<Vec<BracketMatch>>().await.unwrap();
+
+    assert_eq!(draw.len(), 4);
+    assert!(draw[..3]
+        .iter()
+        .all(|match_data| match_data.draw == Draw::Main));
+    let consolation_match = &draw[3];
+    assert_eq!(consolation_match.draw, Draw::Consolation);
+    assert_eq!(consolation_match.round, 1);
+    assert_eq!(consolation_match.player_one_from, Some(draw[0].id));
+    assert_eq!(consolation_match.player_two_from, Some(draw[1].id));
+    assert_eq!(
+        consolation_match.player_one_advancing,
+        Some(Advancing::Loser)
+    );
+    assert_eq!(
+        consolation_match.player_two_advancing,
+        Some(Advancing::Loser)
+    );
+
+    play_match(&client, &draw[0], 1).await;
+    play_match(&client, &draw[1], 3).await;
+    let response = client.get_draw(tournament_id, "p96").await;
+    let draw = response.json::<Vec<BracketMatch>>().await.unwrap();
+    // Winners advance in the main draw and losers get another match
+    assert_eq!(draw[2].player_one.as_ref().unwrap().id, 1);
+    assert_eq!(draw[2].player_two.as_ref().unwrap().id, 3);
+    assert_eq!(draw[3].player_one.as_ref().unwrap().id, 4);
+    assert_eq!(draw[3].player_two.as_ref().unwrap().id, 2);
+
+    play_match(&client, &draw[3], 2).await;
+    let response = client.get_tournaments_matches(tournament_id).await;
+    assert!(response.status().is_success());
+    let match_list = response.json::<TournamentMatchList>().await.unwrap();
+    assert_eq!(match_list.finished.len(), 3);
+    for match_info in match_list.finished.iter() {
+        let expected_draw = if match_info.id == draw[3].id {
+            Draw::Consolation
+        } else {
+            Draw::Main
+        };
+        assert_eq!(match_info.draw, Some(expected_draw));
+    }
+}
+
+#[actix_rt::test]
+async fn should_generate_double_elimination_draw() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament_and_players(&client, 4).await;
+    let response = client
+        .add_court_to_tournament(tournament_id, "Bana 1".to_string())
+        .await;
+    assert!(response.status().is_success());
+
+    let payload = DrawPayload {
+        format: DrawFormat::DoubleElimination,
+        ..draw_payload(vec![1, 2, 3, 4])
+    };
+    let response = client.generate_draw(tournament_id, &payload).await;
+    assert!(response.status().is_success());
+    let draw = response.json::<Vec<BracketMatch>>().await.unwrap();
+
+    // 3 matches in the main draw, 2 in the losers draw and the final
+    assert_eq!(draw.len(), 6);
+    let draws: Vec<Draw> = draw.iter().map(|match_data| match_data.draw).collect();
+    assert_eq!(
+        draws,
+        vec![
+            Draw::Main,
+            Draw::Main,
+            Draw::Main,
+            Draw::Losers,
+            Draw::Losers,
+            Draw::Main
+        ]
+    );
+    // The loser of the main draw semi final meets the survivor of the losers draw
+    assert_eq!(draw[4].player_one_from, Some(draw[3].id));
+    assert_eq!(draw[4].player_one_advancing, Some(Advancing::Winner));
+    assert_eq!(draw[4].player_two_from, Some(draw[2].id));
+    assert_eq!(draw[4].player_two_advancing, Some(Advancing::Loser));
+    // The winners of both draws meet in the final
+    assert_eq!(draw[5].round, 3);
+    assert_eq!(draw[5].player_one_from, Some(draw[2].id));
+    assert_eq!(draw[5].player_two_from, Some(draw[4].id));
+
+    play_match(&client, &draw[0], 1).await;
+    play_match(&client, &draw[1], 2).await;
+    let response = client.get_draw(tournament_id, "p96").await;
+    let draw = response.json::<Vec<BracketMatch>>().await.unwrap();
+    play_match(&client, &draw[2], 1).await;
+    play_match(&client, &draw[3], 3).await;
+
+    let response = client.get_draw(tournament_id, "p96").await;
+    let draw = response.json::<Vec<BracketMatch>>().await.unwrap();
+    assert_eq!(draw[4].player_one.as_ref().unwrap().id, 3);
+    assert_eq!(draw[4].player_two.as_ref().unwrap().id, 2);
+    assert_eq!(draw[5].player_one.as_ref().unwrap().id, 1);
+    assert_eq!(draw[5].player_two, None);
+}
@@ -1,18 +1,37 @@
 #![allow(dead_code)]
 
 use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
 use reqwest::{Client, RequestBuilder, Response};
 use sqlx::{Connection, Executor};
 use sqlx::{PgConnection, PgPool};
 use tokio::runtime::Runtime;
-use tournament_tracker_backend::authentication::set_keys;
+use tournament_tracker_backend::authentication::{set_keys, ApiKeyPayload, AuthTokens};
+use tournament_tracker_backend::mailer::{Email, InMemoryMailer};
 use tournament_tracker_backend::{
     configuration::{get_configuration, DatabaseSettings},
-    endpoints::{CourtForm, PlayerMatchRegistrationPayload},
+    endpoints::{
+        AuditLogQuery, ChangePasswordPayload, CourtForm, DrawPayload, EmailPayload,
+        GroupMatchesPayload, GroupPayload, KnockoutPayload, PlayerMatchRegistrationPayload,
+        PutBackPayload, QueuePositionPayload, RefreshTokenPayload, ResetPasswordPayload,
+        UserRolePayload,
+    },
+    events::start_event_listener,
     get_trace_subscriber, init_subscriber,
-    stores::match_store::Match,
-    stores::{player_store::Player, tournament_store::Tournament},
+    stores::match_store::{Match, MatchScheduling},
+    stores::{
+        player_store::Player,
+        team_store::Team,
+        tournament_store::{MatchFormat, Tournament},
+        user_store::UserRole,
+    },
+    tournament_operations::{RolePayload, TournamentPatch},
+    webhooks::{
+        start_webhook_dispatcher, WebhookPayload, DELIVERY_HEADER, EVENT_HEADER, SIGNATURE_HEADER,
+    },
 };
 use tournament_tracker_backend::{endpoints::CredentialsPayload, stores::match_store::MatchResult};
 use uuid::Uuid;
@@ -20,11 +39,16 @@ use uuid::Uuid;
 pub struct UnauthenticatedClient {
     pub client: Client,
     pub server_addr: String,
+    // The database of the server, for setting up state there is no endpoint for
+    pub db_pool: PgPool,
+    // Every email the server has sent
+    pub mailer: InMemoryMailer,
 }
 
 pub struct AuthenticatedClient {
     pub unauthenticated_client: UnauthenticatedClient,
     pub token: String,
+    pub refresh_token: String,
 }
 
 pub fn insert_tournament(
@@ -41,6 +65,199 @@ pub fn get_tournaments(client: &Client, server_addr: &str) -> RequestBuilder {
     client.get(&format!("{}/tournaments", server_addr))
 }
 
+pub fn get_tournament(client: &Client, server_addr: &str, tournament_id: i32) -> RequestBuilder {
+    client.get(&format!("{}/tournaments/{}", server_addr, tournament_id))
+}
+
+pub fn update_tournament(
+    client: &Client,
+    server_addr: &str,
+    tournament_id: i32,
+    tournament: &Tournament,
+) -> RequestBuilder {
+    client
+        .put(&format!(
+            "{}/authenticated/tournaments/{}",
+            server_addr, tournament_id
+        ))
+        .json(&tournament)
+}
+
+pub fn patch_tournament(
+    client: &Client,
+    server_addr: &str,
+    tournament_id: i32,
+    patch: &TournamentPatch,
+) -> RequestBuilder {
+    client
+        .patch(&format!(
+            "{}/authenticated/tournaments/{}",
+            server_addr, tournament_id
+        ))
+        .json(&patch)
+}
+
+pub fn delete_tournament(client: &Client, server_addr: &str, tournament_id: i32) -> RequestBuilder {
+    client.delete(&format!(
+        "{}/authenticated/tournaments/{}",
+        server_addr, tournament_id
+    ))
+}
+
+pub fn grant_tournament_role(
+    client: &Client,
+    server_addr: &str,
+    tournament_id: i32,
+    payload: &RolePayload,
+) -> RequestBuilder {
+    client
+        .post(&format!(
+            "{}/authenticated/tournaments/{}/roles",
+            server_addr, tournament_id
+        ))
+        .json(&payload)
+}
+
+pub fn get_tournament_roles(
+    client: &Client,
+    server_addr: &str,
+    tournament_id: i32,
+) -> RequestBuilder {
+    client.get(&format!(
+        "{}/authenticated/tournaments/{}/roles",
+        server_addr, tournament_id
+    ))
+}
+
+pub fn revoke_tournament_role(
+    client: &Client,
+    server_addr: &str,
+    tournament_id: i32,
+    user_id: Uuid,
+) -> RequestBuilder {
+    client.delete(&format!(
+        "{}/authenticated/tournaments/{}/roles/{}",
+        server_addr, tournament_id, user_id
+    ))
+}
+
+pub fn create_api_key(
+    client: &Client,
+    server_addr: &str,
+    tournament_id: i32,
+    payload: &ApiKeyPayload,
+) -> RequestBuilder {
+    client
+        .post(&format!(
+            "{}/authenticated/tournaments/{}/api_keys",
+            server_addr, tournament_id
+        ))
+        .json(&payload)
+}
+
+pub fn get_api_keys(client: &Client, server_addr: &str, tournament_id: i32) -> RequestBuilder {
+    client.get(&format!(
+        "{}/authenticated/tournaments/{}/api_keys",
+        server_addr, tournament_id
+    ))
+}
+
+pub fn get_audit_log(
+    client: &Client,
+    server_addr: &str,
+    tournament_id: i32,
+    query: &AuditLogQuery,
+) -> RequestBuilder {
+    client
+        .get(&format!(
+            "{}/authenticated/tournaments/{}/audit_log",
+            server_addr, tournament_id
+        ))
+        .query(&query)
+}
+
+pub fn revoke_api_key(
+    client: &Client,
+    server_addr: &str,
+    tournament_id: i32,
+    key_id: Uuid,
+) -> RequestBuilder {
+    client.delete(&format!(
+        "{}/authenticated/tournaments/{}/api_keys/{}",
+        server_addr, tournament_id, key_id
+    ))
+}
+
+pub fn create_webhook(
+    client: &Client,
+    server_addr: &str,
+    tournament_id: i32,
+    payload: &WebhookPayload,
+) -> RequestBuilder {
+    client
+        .post(&format!(
+            "{}/authenticated/tournaments/{}/webhooks",
+            server_addr, tournament_id
+        ))
+        .json(&payload)
+}
+
+pub fn get_webhooks(client: &Client, server_addr: &str, tournament_id: i32) -> RequestBuilder {
+    client.get(&format!(
+        "{}/authenticated/tournaments/{}/webhooks",
+        server_addr, tournament_id
+    ))
+}
+
+pub fn delete_webhook(
+    client: &Client,
+    server_addr: &str,
+    tournament_id: i32,
+    webhook_id: Uuid,
+) -> RequestBuilder {
+    client.delete(&format!(
+        "{}/authenticated/tournaments/{}/webhooks/{}",
+        server_addr, tournament_id, webhook_id
+    ))
+}
+
+pub fn get_webhook_deliveries(
+    client: &Client,
+    server_addr: &str,
+    tournament_id: i32,
+    webhook_id: Uuid,
+) -> RequestBuilder {
+    client.get(&format!(
+        "{}/authenticated/tournaments/{}/webhooks/{}/deliveries",
+        server_addr, tournament_id, webhook_id
+    ))
+}
+
+pub fn get_users(client: &Client, server_addr: &str) -> RequestBuilder {
+    client.get(&format!("{}/authenticated/admin/users", server_addr))
+}
+
+pub fn set_user_role(
+    client: &Client,
+    server_addr: &str,
+    user_id: Uuid,
+    role: UserRole,
+) -> RequestBuilder {
+    client
+        .put(&format!(
+            "{}/authenticated/admin/users/{}/role",
+            server_addr, user_id
+        ))
+        .json(&UserRolePayload { role })
+}
+
+pub fn reload_signing_keys(client: &Client, server_addr: &str) -> RequestBuilder {
+    client.post(&format!(
+        "{}/authenticated/admin/signing_keys/reload",
+        server_addr
+    ))
+}
+
 pub fn add_court_to_tournament(
     client: &Client,
     server_addr: &str,
@@ -52,7 +269,33 @@ pub fn add_court_to_tournament(
             "{}/authenticated/tournaments/{}/courts",
             server_addr, tournament_id
         ))
-        .form(&CourtForm { name: court_name })
+        .form(&CourtForm {
+            name: court_name,
+            surface: None,
+            indoor: false,
+            lighting: false,
+        })
+}
+
+pub fn add_court_with_attributes(
+    client: &Client,
+    server_addr: &str,
+    tournament_id: i32,
+    court_form: &CourtForm,
+) -> RequestBuilder {
+    client
+        .post(&format!(
+            "{}/authenticated/tournaments/{}/courts",
+            server_addr, tournament_id
+        ))
+        .form(court_form)
+}
+
+pub fn get_courts(client: &Client, server_addr: &str, tournament_id: i32) -> RequestBuilder {
+    client.get(&format!(
+        "{}/tournaments/{}/courts",
+        server_addr, tournament_id
+    ))
 }
 
 pub fn get_tournaments_matches(
@@ -66,6 +309,76 @@ pub fn get_tournaments_matches(
     ))
 }
 
+pub fn get_court_queue(client: &Client, server_addr: &str, tournament_id: i32) -> RequestBuilder {
+    client.get(&format!(
+        "{}/tournaments/{}/queue",
+        server_addr, tournament_id
+    ))
+}
+
+pub fn move_in_court_queue(
+    client: &Client,
+    server_addr: &str,
+    tournament_id: i32,
+    match_id: i64,
+    position: usize,
+) -> RequestBuilder {
+    client
+        .put(&format!(
+            "{}/authenticated/tournaments/{}/queue/{}",
+            server_addr, tournament_id, match_id
+        ))
+        .json(&QueuePositionPayload { position })
+}
+
+pub fn pull_from_court_queue(
+    client: &Client,
+    server_addr: &str,
+    tournament_id: i32,
+    match_id: i64,
+) -> RequestBuilder {
+    client.post(&format!(
+        "{}/authenticated/tournaments/{}/queue/{}/pull",
+        server_addr, tournament_id, match_id
+    ))
+}
+
+pub fn put_back_in_court_queue(
+    client: &Client,
+    server_addr: &str,
+    tournament_id: i32,
+    match_id: i64,
+    position: Option<usize>,
+) -> RequestBuilder {
+    client
+        .post(&format!(
+            "{}/authenticated/tournaments/{}/queue/{}/put_back",
+            server_addr, tournament_id, match_id
+        ))
+        .json(&PutBackPayload { position })
+}
+
+pub fn get_match_format(client: &Client, server_addr: &str, tournament_id: i32) -> RequestBuilder {
+    client.get(&format!(
+        "{}/tournaments/{}/format",
+        server_addr, tournament_id
+    ))
+}
+
+pub fn update_match_format(
+    client: &Client,
+    server_addr: &str,
+    tournament_id: i32,
+    match_format: &MatchFormat,
+) -> RequestBuilder {
+    client
+        .put(&format!(
+            "{}/authenticated/tournaments/{}/format",
+            server_addr, tournament_id
+        ))
+        .json(&match_format)
+}
+
 pub fn insert_player(client: &Client, server_addr: &str, player: &Player) -> RequestBuilder {
     client
         .post(&format!("{}/authenticated/players", server_addr))
@@ -76,6 +389,16 @@ pub fn get_player(client: &Client, server_addr: &str, player_id: i64) -> Request
     client.get(&format!("{}/players/{}", server_addr, player_id))
 }
 
+pub fn insert_team(client: &Client, server_addr: &str, team: &Team) -> RequestBuilder {
+    client
+        .post(&format!("{}/authenticated/teams", server_addr))
+        .json(&team)
+}
+
+pub fn get_team(client: &Client, server_addr: &str, team_id: i64) -> RequestBuilder {
+    client.get(&format!("{}/teams/{}", server_addr, team_id))
+}
+
 pub fn insert_match(client: &Client, server_addr: &str, match_data: &Match) -> RequestBuilder {
     client
         .post(&format!("{}/authenticated/matches", server_addr))
@@ -96,6 +419,49 @@ pub fn finish_match(
         .json(&match_result)
 }
 
+pub fn correct_match_result(
+    client: &Client,
+    server_addr: &str,
+    match_id: i64,
+    match_result: &MatchResult,
+) -> RequestBuilder {
+    client
+        .put(&format!(
+            "{}/authenticated/matches/{}/result",
+            server_addr, match_id
+        ))
+        .json(&match_result)
+}
+
+pub fn get_match_scheduling(client: &Client, server_addr: &str, match_id: i64) -> RequestBuilder {
+    client.get(&format!("{}/matches/{}/scheduling", server_addr, match_id))
+}
+
+pub fn update_match_scheduling(
+    client: &Client,
+    server_addr: &str,
+    match_id: i64,
+    scheduling: &MatchScheduling,
+) -> RequestBuilder {
+    client
+        .put(&format!(
+            "{}/authenticated/matches/{}/scheduling",
+            server_addr, match_id
+        ))
+        .json(scheduling)
+}
+
+pub fn get_match_result_corrections(
+    client: &Client,
+    server_addr: &str,
+    match_id: i64,
+) -> RequestBuilder {
+    client.get(&format!(
+        "{}/authenticated/matches/{}/corrections",
+        server_addr, match_id
+    ))
+}
+
 pub fn register_player(
     client: &Client,
     server_addr: &str,
@@ -110,39 +476,127 @@ pub fn register_player(
         .json(&player_registration_req)
 }
 
-impl UnauthenticatedClient {
-    pub async fn insert_tournament(&self, tournament: &Tournament) -> Response {
-        insert_tournament(&self.client, &self.server_addr, tournament)
-            .send()
-            .await
-            .expect("Request failed")
-    }
-
-    pub async fn get_tournaments(&self) -> Response {
-        get_tournaments(&self.client, &self.server_addr)
-            .send()
-            .await
-            .expect("Request failed")
-    }
+pub fn generate_draw(
+    client: &Client,
+    server_addr: &str,
+    tournament_id: i32,
+    draw_payload: &DrawPayload,
+) -> RequestBuilder {
+    client
+        .post(&format!(
+            "{}/authenticated/tournaments/{}/draws",
+            server_addr, tournament_id
+        ))
+        .json(&draw_payload)
+}
 
-    pub async fn add_court_to_tournament(
-        &self,
-        tournament_id: i32,
-        court_name: String,
-    ) -> Response {
-        add_court_to_tournament(&self.client, &self.server_addr, tournament_id, court_name)
-            .send()
-            .await
-            .expect("Request failed")
-    }
+pub fn get_draw(
+    client: &Client,
+    server_addr: &str,
+    tournament_id: i32,
+    class: &str,
+) -> RequestBuilder {
+    client.get(&format!(
+        "{}/tournaments/{}/draws/{}",
+        server_addr, tournament_id, class
+    ))
+}
 
-    pub async fn get_tournaments_matches(&self, tournament_id: i32) -> Response {
+pub fn insert_group(
+    client: &Client,
+    server_addr: &str,
+    tournament_id: i32,
+    group_payload: &GroupPayload,
+) -> RequestBuilder {
+    client
+        .post(&format!(
+            "{}/authenticated/tournaments/{}/groups",
+            server_addr, tournament_id
+        ))
+        .json(&group_payload)
+}
+
+pub fn generate_group_matches(
+    client: &Client,
+    server_addr: &str,
+    tournament_id: i32,
+    group_id: i32,
+    payload: &GroupMatchesPayload,
+) -> RequestBuilder {
+    client
+        .post(&format!(
+            "{}/authenticated/tournaments/{}/groups/{}/matches",
+            server_addr, tournament_id, group_id
+        ))
+        .json(&payload)
+}
+
+pub fn generate_knockout_draw(
+    client: &Client,
+    server_addr: &str,
+    tournament_id: i32,
+    payload: &KnockoutPayload,
+) -> RequestBuilder {
+    client
+        .post(&format!(
+            "{}/authenticated/tournaments/{}/groups/knockout",
+            server_addr, tournament_id
+        ))
+        .json(&payload)
+}
+
+pub fn get_group_standings(
+    client: &Client,
+    server_addr: &str,
+    tournament_id: i32,
+    group_id: i32,
+) -> RequestBuilder {
+    client.get(&format!(
+        "{}/tournaments/{}/groups/{}/standings",
+        server_addr, tournament_id, group_id
+    ))
+}
+
+impl UnauthenticatedClient {
+    pub async fn insert_tournament(&self, tournament: &Tournament) -> Response {
+        insert_tournament(&self.client, &self.server_addr, tournament)
+            .send()
+            .await
+            .expect("Request failed")
+    }
+
+    pub async fn get_tournaments(&self) -> Response {
+        get_tournaments(&self.client, &self.server_addr)
+            .send()
+            .await
+            .expect("Request failed")
+    }
+
+    pub async fn add_court_to_tournament(
+        &self,
+        tournament_id: i32,
+        court_name: String,
+    ) -> Response {
+        add_court_to_tournament(&self.client, &self.server_addr, tournament_id, court_name)
+            .send()
+            .await
+            .expect("Request failed")
+    }
+
+    pub async fn get_tournaments_matches(&self, tournament_id: i32) -> Response {
         get_tournaments_matches(&self.client, &self.server_addr, tournament_id)
             .send()
             .await
             .expect("Request failed")
     }
 
+    pub async fn get_match_format(&self, tournament_id: i32) -> Response {
+        get_match_format(&self.client, &self.server_addr, tournament_id)
+            .send()
+            .await
+            .expect("Request failed")
+    }
+
     pub async fn insert_player(&self, player: &Player) -> Response {
         insert_player(&self.client, &self.server_addr, player)
             .send()
@@ -150,91 +604,759 @@ impl UnauthenticatedClient {
             .expect("Request failed")
     }
 
-    pub async fn get_player(&self, player_id: i64) -> Response {
-        get_player(&self.client, &self.server_addr, player_id)
-            .send()
-            .await
-            .expect("Request failed")
+    pub async fn get_tournament_events(&self, tournament_id: i32) -> Response {
+        self.client
+            .get(&format!(
+                "{}/tournaments/{}/events",
+                &self.server_addr, tournament_id
+            ))
+            .send()
+            .await
+            .expect("Request failed")
+    }
+
+    pub async fn get_courts(&self, tournament_id: i32) -> Response {
+        get_courts(&self.client, &self.server_addr, tournament_id)
+            .send()
+            .await
+            .expect("Request failed")
+    }
+
+    pub async fn get_match_scheduling(&self, match_id: i64) -> Response {
+        get_match_scheduling(&self.client, &self.server_addr, match_id)
+            .send()
+            .await
+            .expect("Request failed")
+    }
+
+    pub async fn get_court_queue(&self, tournament_id: i32) -> Response {
+        get_court_queue(&self.client, &self.server_addr, tournament_id)
+            .send()
+            .await
+            .expect("Request failed")
+    }
+
+    pub async fn get_player(&self, player_id: i64) -> Response {
+        get_player(&self.client, &self.server_addr, player_id)
+            .send()
+            .await
+            .expect("Request failed")
+    }
+
+    pub async fn insert_match(&self, match_data: &Match) -> Response {
+        insert_match(&self.client, &self.server_addr, match_data)
+            .send()
+            .await
+            .expect("Request failed")
+    }
+
+    pub async fn finish_match(&self, match_id: i64, match_result: &MatchResult) -> Response {
+        finish_match(&self.client, &self.server_addr, match_id, match_result)
+            .send()
+            .await
+            .expect("Request failed")
+    }
+
+    pub async fn register_player(
+        &self,
+        match_id: i64,
+        player_registration_req: &PlayerMatchRegistrationPayload,
+    ) -> Response {
+        register_player(
+            &self.client,
+            &self.server_addr,
+            match_id,
+            player_registration_req,
+        )
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn get_draw(&self, tournament_id: i32, class: &str) -> Response {
+        get_draw(&self.client, &self.server_addr, tournament_id, class)
+            .send()
+            .await
+            .expect("Request failed")
+    }
+
+    pub async fn get_group_standings(&self, tournament_id: i32, group_id: i32) -> Response {
+        get_group_standings(&self.client, &self.server_addr, tournament_id, group_id)
+            .send()
+            .await
+            .expect("Request failed")
+    }
+
+    pub async fn create_user(&self, credentials: &CredentialsPayload) -> Response {
+        self.client
+            .post(&format!("{}/user", &self.server_addr))
+            .json(&credentials)
+            .send()
+            .await
+            .expect("Request failed")
+    }
+
+    // The last email the server sent to the address
+    pub fn last_email_to(&self, email: &str) -> Email {
+        self.mailer
+            .sent_emails()
+            .into_iter()
+            .rev()
+            .find(|sent| sent.to == email)
+            .expect("No email sent")
+    }
+
+    // Opens the link in the last verification email sent to the address
+    pub async fn verify_email(&self, email: &str) -> Response {
+        let body = self.last_email_to(email).body;
+        let link = body.split_whitespace().last().expect("Missing link");
+        self.client.get(link).send().await.expect("Request failed")
+    }
+
+    pub async fn resend_verification_email(&self, email: &str) -> Response {
+        self.client
+            .post(&format!("{}/user/verify/resend", &self.server_addr))
+            .json(&EmailPayload {
+                email: email.to_string(),
+            })
+            .send()
+            .await
+            .expect("Request failed")
+    }
+
+    pub async fn create_verified_user(&self, credentials: &CredentialsPayload) {
+        let response = self.create_user(credentials).await;
+        assert!(response.status().is_success());
+        let response = self.verify_email(&credentials.email).await;
+        assert!(response.status().is_success());
+    }
+
+    pub async fn login(&self, credentials: &CredentialsPayload) -> Response {
+        self.client
+            .post(&format!("{}/login", &self.server_addr))
+            .json(&credentials)
+            .send()
+            .await
+            .expect("Request failed")
+    }
+
+    pub async fn login_forwarded_for(
+        &self,
+        credentials: &CredentialsPayload,
+        forwarded_for: &str,
+    ) -> Response {
+        self.client
+            .post(&format!("{}/login", &self.server_addr))
+            .header("X-Forwarded-For", forwarded_for)
+            .json(&credentials)
+            .send()
+            .await
+            .expect("Request failed")
+    }
+
+    pub async fn new_authenticated_user_client(self) -> AuthenticatedClient {
+        let dummy_credentials = CredentialsPayload {
+            email: "dummy@test.se".to_string(),
+            password: "some-secure-password".to_string(),
+        };
+        self.create_verified_user(&dummy_credentials).await;
+        self.authenticate(&dummy_credentials).await
+    }
+
+    // Another client for the same server that doesn't share any connections
+    // Starts another instance of the server using the same database
+    pub async fn spawn_instance(&self) -> UnauthenticatedClient {
+        spawn_server(self.db_pool.clone()).await
+    }
+
+    pub fn new_client(&self) -> UnauthenticatedClient {
+        UnauthenticatedClient {
+            server_addr: self.server_addr.clone(),
+            client: http_client(),
+            db_pool: self.db_pool.clone(),
+            mailer: self.mailer.clone(),
+        }
+    }
+
+    pub async fn refresh(&self, refresh_token: &str) -> Response {
+        self.client
+            .post(&format!("{}/refresh", &self.server_addr))
+            .json(&RefreshTokenPayload {
+                refresh_token: refresh_token.to_string(),
+            })
+            .send()
+            .await
+            .expect("Request failed")
+    }
+
+    pub async fn logout(&self, refresh_token: &str) -> Response {
+        self.client
+            .post(&format!("{}/logout", &self.server_addr))
+            .json(&RefreshTokenPayload {
+                refresh_token: refresh_token.to_string(),
+            })
+            .send()
+            .await
+            .expect("Request failed")
+    }
+
+    pub async fn forgot_password(&self, email: &str) -> Response {
+        self.client
+            .post(&format!("{}/password/forgot", &self.server_addr))
+            .json(&EmailPayload {
+                email: email.to_string(),
+            })
+            .send()
+            .await
+            .expect("Request failed")
+    }
+
+    pub async fn reset_password(&self, token: &str, new_password: &str) -> Response {
+        self.client
+            .post(&format!("{}/password/reset", &self.server_addr))
+            .json(&ResetPasswordPayload {
+                token: token.to_string(),
+                new_password: new_password.to_string(),
+            })
+            .send()
+            .await
+            .expect("Request failed")
+    }
+
+    pub async fn authenticate(self, credentials: &CredentialsPayload) -> AuthenticatedClient {
+        let tokens = self
+            .login(&credentials)
+            .await
+            .json::<AuthTokens>()
+            .await
+            .unwrap();
+        AuthenticatedClient {
+            unauthenticated_client: self,
+            token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+        }
+    }
+}
+
+const AUTH_HEADER: &str = "Authorization";
+
+impl AuthenticatedClient {
+    fn auth_header_value(&self) -> String {
+        format!("Bearer {}", self.token)
+    }
+
+    // Creates and logs in another user of the same server
+    pub async fn new_user(&self, email: &str) -> AuthenticatedClient {
+        let client = self.unauthenticated_client.new_client();
+        let credentials = CredentialsPayload {
+            email: email.to_string(),
+            password: "some-secure-password".to_string(),
+        };
+        client.create_verified_user(&credentials).await;
+        client.authenticate(&credentials).await
+    }
+
+    // Creates another user and promotes it to admin in the database before logging in
+    pub async fn new_admin(&self, email: &str) -> AuthenticatedClient {
+        let client = self.unauthenticated_client.new_client();
+        let credentials = CredentialsPayload {
+            email: email.to_string(),
+            password: "some-secure-password".to_string(),
+        };
+        client.create_verified_user(&credentials).await;
+        sqlx::query("UPDATE users SET role = 'admin' WHERE email = $1")
+            .bind(email)
+            .execute(&client.db_pool)
+            .await
+            .expect("Failed to promote admin");
+        client.authenticate(&credentials).await
+    }
+
+    // A client for the same server that authenticates with the token, ex an api key
+    pub fn with_token(&self, token: &str) -> AuthenticatedClient {
+        AuthenticatedClient {
+            unauthenticated_client: self.unauthenticated_client.new_client(),
+            token: token.to_string(),
+            refresh_token: String::new(),
+        }
+    }
+
+    pub async fn create_api_key(&self, tournament_id: i32, payload: &ApiKeyPayload) -> Response {
+        create_api_key(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament_id,
+            payload,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn get_api_keys(&self, tournament_id: i32) -> Response {
+        get_api_keys(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament_id,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn get_audit_log(&self, tournament_id: i32, query: &AuditLogQuery) -> Response {
+        get_audit_log(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament_id,
+            query,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn revoke_api_key(&self, tournament_id: i32, key_id: Uuid) -> Response {
+        revoke_api_key(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament_id,
+            key_id,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn create_webhook(&self, tournament_id: i32, payload: &WebhookPayload) -> Response {
+        create_webhook(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament_id,
+            payload,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn get_webhooks(&self, tournament_id: i32) -> Response {
+        get_webhooks(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament_id,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn delete_webhook(&self, tournament_id: i32, webhook_id: Uuid) -> Response {
+        delete_webhook(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament_id,
+            webhook_id,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn get_webhook_deliveries(&self, tournament_id: i32, webhook_id: Uuid) -> Response {
+        get_webhook_deliveries(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament_id,
+            webhook_id,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn get_users(&self) -> Response {
+        get_users(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn reload_signing_keys(&self) -> Response {
+        reload_signing_keys(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn set_user_role(&self, user_id: Uuid, role: UserRole) -> Response {
+        set_user_role(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            user_id,
+            role,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn insert_tournament(&self, tournament: &Tournament) -> Response {
+        insert_tournament(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn get_tournaments(&self) -> Response {
+        get_tournaments(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn get_tournament(&self, tournament_id: i32) -> Response {
+        get_tournament(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament_id,
+        )
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn update_tournament(&self, tournament_id: i32, tournament: &Tournament) -> Response {
+        update_tournament(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament_id,
+            tournament,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn patch_tournament(&self, tournament_id: i32, patch: &TournamentPatch) -> Response {
+        patch_tournament(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament_id,
+            patch,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn delete_tournament(&self, tournament_id: i32) -> Response {
+        delete_tournament(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament_id,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn grant_tournament_role(
+        &self,
+        tournament_id: i32,
+        payload: &RolePayload,
+    ) -> Response {
+        grant_tournament_role(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament_id,
+            payload,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn get_tournament_roles(&self, tournament_id: i32) -> Response {
+        get_tournament_roles(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament_id,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn revoke_tournament_role(&self, tournament_id: i32, user_id: Uuid) -> Response {
+        revoke_tournament_role(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament_id,
+            user_id,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn add_court_to_tournament(
+        &self,
+        tournament_id: i32,
+        court_name: String,
+    ) -> Response {
+        add_court_to_tournament(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament_id,
+            court_name,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn get_tournaments_matches(&self, tournament_id: i32) -> Response {
+        get_tournaments_matches(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament_id,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn get_match_format(&self, tournament_id: i32) -> Response {
+        get_match_format(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament_id,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn update_match_format(
+        &self,
+        tournament_id: i32,
+        match_format: &MatchFormat,
+    ) -> Response {
+        update_match_format(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament_id,
+            match_format,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn insert_player(&self, player: &Player) -> Response {
+        insert_player(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            player,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn get_player(&self, player_id: i64) -> Response {
+        get_player(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            player_id,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
+    }
+
+    pub async fn insert_team(&self, team: &Team) -> Response {
+        insert_team(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            team,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
     }
 
-    pub async fn insert_match(&self, match_data: &Match) -> Response {
-        insert_match(&self.client, &self.server_addr, match_data)
-            .send()
-            .await
-            .expect("Request failed")
+    pub async fn get_team(&self, team_id: i64) -> Response {
+        get_team(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            team_id,
+        )
+        .send()
+        .await
+        .expect("Request failed")
     }
 
-    pub async fn finish_match(&self, match_id: i64, match_result: &MatchResult) -> Response {
-        finish_match(&self.client, &self.server_addr, match_id, match_result)
-            .send()
-            .await
-            .expect("Request failed")
+    pub async fn insert_match(&self, match_data: &Match) -> Response {
+        insert_match(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            match_data,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
     }
 
-    pub async fn register_player(
+    pub async fn move_in_court_queue(
         &self,
+        tournament_id: i32,
         match_id: i64,
-        player_registration_req: &PlayerMatchRegistrationPayload,
+        position: usize,
     ) -> Response {
-        register_player(
-            &self.client,
-            &self.server_addr,
+        move_in_court_queue(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament_id,
             match_id,
-            player_registration_req,
+            position,
         )
+        .header(AUTH_HEADER, self.auth_header_value())
         .send()
         .await
         .expect("Request failed")
     }
 
-    pub async fn create_user(&self, credentials: &CredentialsPayload) -> Response {
-        self.client
-            .post(&format!("{}/user", &self.server_addr))
-            .json(&credentials)
-            .send()
-            .await
-            .expect("Request failed")
+    pub async fn pull_from_court_queue(&self, tournament_id: i32, match_id: i64) -> Response {
+        pull_from_court_queue(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament_id,
+            match_id,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
     }
 
-    pub async fn login(&self, credentials: &CredentialsPayload) -> Response {
-        self.client
-            .post(&format!("{}/login", &self.server_addr))
-            .json(&credentials)
-            .send()
-            .await
-            .expect("Request failed")
+    pub async fn put_back_in_court_queue(
+        &self,
+        tournament_id: i32,
+        match_id: i64,
+        position: Option<usize>,
+    ) -> Response {
+        put_back_in_court_queue(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament_id,
+            match_id,
+            position,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
     }
 
-    pub async fn new_authenticated_user_client(self) -> AuthenticatedClient {
-        let dummy_credentials = CredentialsPayload {
-            email: "dummy@test.se".to_string(),
-            password: "some-secure-password".to_string(),
-        };
-        self.create_user(&dummy_credentials).await;
-        self.authenticate(&dummy_credentials).await
+    pub async fn finish_match(&self, match_id: i64, match_result: &MatchResult) -> Response {
+        finish_match(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            match_id,
+            match_result,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
     }
 
-    pub async fn authenticate(self, credentials: &CredentialsPayload) -> AuthenticatedClient {
-        let token = self.login(&credentials).await.text().await.unwrap();
-        AuthenticatedClient {
-            unauthenticated_client: self,
-            token,
-        }
+    pub async fn correct_match_result(
+        &self,
+        match_id: i64,
+        match_result: &MatchResult,
+    ) -> Response {
+        correct_match_result(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            match_id,
+            match_result,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
     }
-}
-
-const AUTH_HEADER: &str = "Authorization";
 
-impl AuthenticatedClient {
-    fn auth_header_value(&self) -> String {
-        format!("Bearer {}", self.token)
+    pub async fn add_court_with_attributes(
+        &self,
+        tournament_id: i32,
+        court_form: &CourtForm,
+    ) -> Response {
+        add_court_with_attributes(
+            &self.unauthenticated_client.client,
+            &self.unauthenticated_client.server_addr,
+            tournament_id,
+            court_form,
+        )
+        .header(AUTH_HEADER, self.auth_header_value())
+        .send()
+        .await
+        .expect("Request failed")
     }
 
-    pub async fn insert_tournament(&self, tournament: &Tournament) -> Response {
-        insert_tournament(
+    pub async fn update_match_scheduling(
+        &self,
+        match_id: i64,
+        scheduling: &MatchScheduling,
+    ) -> Response {
+        update_match_scheduling(
             &self.unauthenticated_client.client,
             &self.unauthenticated_client.server_addr,
-            tournament,
+            match_id,
+            scheduling,
         )
         .header(AUTH_HEADER, self.auth_header_value())
         .send()
@@ -242,10 +1364,11 @@ impl AuthenticatedClient {
         .expect("Request failed")
     }
 
-    pub async fn get_tournaments(&self) -> Response {
-        get_tournaments(
+    pub async fn get_match_result_corrections(&self, match_id: i64) -> Response {
+        get_match_result_corrections(
             &self.unauthenticated_client.client,
             &self.unauthenticated_client.server_addr,
+            match_id,
         )
         .header(AUTH_HEADER, self.auth_header_value())
         .send()
@@ -253,16 +1376,16 @@ impl AuthenticatedClient {
         .expect("Request failed")
     }
 
-    pub async fn add_court_to_tournament(
+    pub async fn register_player(
         &self,
-        tournament_id: i32,
-        court_name: String,
+        match_id: i64,
+        player_registration_req: &PlayerMatchRegistrationPayload,
     ) -> Response {
-        add_court_to_tournament(
+        register_player(
             &self.unauthenticated_client.client,
             &self.unauthenticated_client.server_addr,
-            tournament_id,
-            court_name,
+            match_id,
+            player_registration_req,
         )
         .header(AUTH_HEADER, self.auth_header_value())
         .send()
@@ -270,11 +1393,12 @@ impl AuthenticatedClient {
         .expect("Request failed")
     }
 
-    pub async fn get_tournaments_matches(&self, tournament_id: i32) -> Response {
-        get_tournaments_matches(
+    pub async fn generate_draw(&self, tournament_id: i32, draw_payload: &DrawPayload) -> Response {
+        generate_draw(
             &self.unauthenticated_client.client,
             &self.unauthenticated_client.server_addr,
             tournament_id,
+            draw_payload,
         )
         .header(AUTH_HEADER, self.auth_header_value())
         .send()
@@ -282,11 +1406,12 @@ impl AuthenticatedClient {
         .expect("Request failed")
     }
 
-    pub async fn insert_player(&self, player: &Player) -> Response {
-        insert_player(
+    pub async fn get_draw(&self, tournament_id: i32, class: &str) -> Response {
+        get_draw(
             &self.unauthenticated_client.client,
             &self.unauthenticated_client.server_addr,
-            player,
+            tournament_id,
+            class,
         )
         .header(AUTH_HEADER, self.auth_header_value())
         .send()
@@ -294,11 +1419,12 @@ impl AuthenticatedClient {
         .expect("Request failed")
     }
 
-    pub async fn get_player(&self, player_id: i64) -> Response {
-        get_player(
+    pub async fn insert_group(&self, tournament_id: i32, group_payload: &GroupPayload) -> Response {
+        insert_group(
             &self.unauthenticated_client.client,
             &self.unauthenticated_client.server_addr,
-            player_id,
+            tournament_id,
+            group_payload,
         )
         .header(AUTH_HEADER, self.auth_header_value())
         .send()
@@ -306,11 +1432,18 @@ impl AuthenticatedClient {
         .expect("Request failed")
     }
 
-    pub async fn insert_match(&self, match_data: &Match) -> Response {
-        insert_match(
+    pub async fn generate_group_matches(
+        &self,
+        tournament_id: i32,
+        group_id: i32,
+        payload: &GroupMatchesPayload,
+    ) -> Response {
+        generate_group_matches(
             &self.unauthenticated_client.client,
             &self.unauthenticated_client.server_addr,
-            match_data,
+            tournament_id,
+            group_id,
+            payload,
         )
         .header(AUTH_HEADER, self.auth_header_value())
         .send()
@@ -318,12 +1451,16 @@ impl AuthenticatedClient {
         .expect("Request failed")
     }
 
-    pub async fn finish_match(&self, match_id: i64, match_result: &MatchResult) -> Response {
-        finish_match(
+    pub async fn generate_knockout_draw(
+        &self,
+        tournament_id: i32,
+        payload: &KnockoutPayload,
+    ) -> Response {
+        generate_knockout_draw(
             &self.unauthenticated_client.client,
             &self.unauthenticated_client.server_addr,
-            match_id,
-            match_result,
+            tournament_id,
+            payload,
         )
         .header(AUTH_HEADER, self.auth_header_value())
         .send()
@@ -331,16 +1468,12 @@ impl AuthenticatedClient {
         .expect("Request failed")
     }
 
-    pub async fn register_player(
-        &self,
-        match_id: i64,
-        player_registration_req: &PlayerMatchRegistrationPayload,
-    ) -> Response {
-        register_player(
+    pub async fn get_group_standings(&self, tournament_id: i32, group_id: i32) -> Response {
+        get_group_standings(
             &self.unauthenticated_client.client,
             &self.unauthenticated_client.server_addr,
-            match_id,
-            player_registration_req,
+            tournament_id,
+            group_id,
         )
         .header(AUTH_HEADER, self.auth_header_value())
         .send()
@@ -348,6 +1481,36 @@ impl AuthenticatedClient {
         .expect("Request failed")
     }
 
+    pub async fn change_password(&self, old_password: &str, new_password: &str) -> Response {
+        self.unauthenticated_client
+            .client
+            .put(&format!(
+                "{}/authenticated/password",
+                &self.unauthenticated_client.server_addr
+            ))
+            .json(&ChangePasswordPayload {
+                old_password: old_password.to_string(),
+                new_password: new_password.to_string(),
+            })
+            .header(AUTH_HEADER, self.auth_header_value())
+            .send()
+            .await
+            .expect("Request failed")
+    }
+
+    pub async fn logout_all(&self) -> Response {
+        self.unauthenticated_client
+            .client
+            .post(&format!(
+                "{}/authenticated/logout_all",
+                &self.unauthenticated_client.server_addr
+            ))
+            .header(AUTH_HEADER, self.auth_header_value())
+            .send()
+            .await
+            .expect("Request failed")
+    }
+
     pub async fn delete_user(&self) -> Response {
         self.unauthenticated_client
             .client
@@ -374,13 +1537,19 @@ lazy_static::lazy_static! {
     };
 }
 
+// Connections aren't reused since the server closes idle connections after a few seconds,
+// which otherwise makes requests after slow steps like user creation fail randomly
+fn http_client() -> Client {
+    Client::builder()
+        .pool_max_idle_per_host(0)
+        .build()
+        .expect("Failed to build http client")
+}
+
 pub async fn spawn_server_and_authenticate() -> AuthenticatedClient {
     lazy_static::initialize(&TRACING);
     lazy_static::initialize(&PRIVATE_KEYS);
 
-    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind address");
-    let port = listener.local_addr().unwrap().port();
-
     let mut configuration = get_configuration().expect("Failed to read configuration.");
 
     // Randomise database name:
@@ -388,8 +1557,36 @@ pub async fn spawn_server_and_authenticate() -> AuthenticatedClient {
 
     let connection_pool = configure_database(&configuration.database).await;
 
-    let server = tournament_tracker_backend::run(listener, connection_pool)
-        .expect("Failed to create server");
+    spawn_server(connection_pool)
+        .await
+        .new_authenticated_user_client()
+        .await
+}
+
+async fn spawn_server(connection_pool: PgPool) -> UnauthenticatedClient {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind address");
+    let port = listener.local_addr().unwrap().port();
+
+    let mut configuration = get_configuration().expect("Failed to read configuration.");
+    let events = start_event_listener(&connection_pool)
+        .await
+        .expect("Failed to listen for tournament events");
+    // Don't keep the tests waiting for webhook deliveries and retries
+    configuration.webhooks.poll_interval_milliseconds = 100;
+    configuration.webhooks.retry_backoff_seconds = 1;
+    // The webhook receivers run locally
+    configuration.webhooks.allowed_private_hosts = vec!["127.0.0.1".to_string()];
+    start_webhook_dispatcher(connection_pool.clone(), configuration.webhooks.clone());
+    let mailer = InMemoryMailer::default();
+    let server = tournament_tracker_backend::run(
+        listener,
+        connection_pool.clone(),
+        Arc::new(mailer.clone()),
+        configuration.login,
+        configuration.webhooks,
+        events,
+    )
+    .expect("Failed to create server");
     let rt = Runtime::new().expect("Failed to start tokio runtime");
     // tokio, unlike smol detaches when task handle is droppped
     rt.block_on(async {
@@ -398,10 +1595,108 @@ pub async fn spawn_server_and_authenticate() -> AuthenticatedClient {
 
     UnauthenticatedClient {
         server_addr: format!("http://127.0.0.1:{}", port),
-        client: reqwest::Client::new(),
+        client: http_client(),
+        db_pool: connection_pool,
+        mailer,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReceivedWebhook {
+    pub event: String,
+    pub delivery: String,
+    pub signature: String,
+    pub body: String,
+}
+
+struct WebhookReceiverState {
+    received: Arc<Mutex<Vec<ReceivedWebhook>>>,
+    // Requests left to answer with an error
+    failures: AtomicUsize,
+}
+
+// Local stand-in for the servers the webhooks are sent to
+pub struct WebhookReceiver {
+    pub url: String,
+    received: Arc<Mutex<Vec<ReceivedWebhook>>>,
+}
+
+impl WebhookReceiver {
+    pub fn received(&self) -> Vec<ReceivedWebhook> {
+        self.received.lock().unwrap().clone()
+    }
+
+    // Waits until at least count requests have been received and returns all of them
+    pub async fn wait_for(&self, count: usize) -> Vec<ReceivedWebhook> {
+        for _ in 0..100 {
+            let received = self.received.lock().unwrap().clone();
+            if received.len() >= count {
+                return received;
+            }
+            actix_rt::time::delay_for(std::time::Duration::from_millis(100)).await;
+        }
+        panic!("Timed out waiting for webhooks");
+    }
+}
+
+async fn receive_webhook(
+    req: HttpRequest,
+    body: String,
+    state: web::Data<WebhookReceiverState>,
+) -> HttpResponse {
+    let header = |name: &str| {
+        req.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    };
+    state.received.lock().unwrap().push(ReceivedWebhook {
+        event: header(EVENT_HEADER),
+        delivery: header(DELIVERY_HEADER),
+        signature: header(SIGNATURE_HEADER),
+        body,
+    });
+    let failing = state
+        .failures
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |failures| {
+            failures.checked_sub(1)
+        })
+        .is_ok();
+    if failing {
+        HttpResponse::InternalServerError().finish()
+    } else {
+        HttpResponse::Ok().finish()
+    }
+}
+
+// The first `failures` requests are answered with 500
+pub fn spawn_webhook_receiver(failures: usize) -> WebhookReceiver {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind address");
+    let port = listener.local_addr().unwrap().port();
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let state = web::Data::new(WebhookReceiverState {
+        received: received.clone(),
+        failures: AtomicUsize::new(failures),
+    });
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(state.clone())
+            .route("/", web::post().to(receive_webhook))
+    })
+    .workers(1)
+    .listen(listener)
+    .expect("Failed to create webhook receiver")
+    .run();
+    let rt = Runtime::new().expect("Failed to start tokio runtime");
+    rt.block_on(async {
+        let _ = tokio::spawn(server);
+    });
+
+    WebhookReceiver {
+        url: format!("http://127.0.0.1:{}/", port),
+        received,
     }
-    .new_authenticated_user_client()
-    .await
 }
 
 pub async fn configure_database(config: &DatabaseSettings) -> PgPool {
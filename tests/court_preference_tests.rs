@@ -0,0 +1,308 @@
+use chrono::{Duration, Local};
+use common::{spawn_server_and_authenticate, AuthenticatedClient};
+use reqwest::StatusCode;
+use tournament_tracker_backend::{
+    endpoints::{CourtForm, PlayerMatchRegistrationPayload},
+    match_operations::{CourtQueueMatch, TournamentMatchList},
+    stores::{
+        court_store::TournamentCourtAllocation,
+        match_store::{Match, MatchOutcome, MatchResult, MatchScheduling},
+        player_store::Player,
+        tournament_store::Tournament,
+    },
+};
+
+mod common;
+
+async fn insert_tournament(client: &AuthenticatedClient, courts: &[CourtForm]) -> i32 {
+    let start_date = Local::today().naive_local();
+    let tournament = Tournament {
+        id: 0, // doesn't matter
+        name: "Södertälje open".into(),
+        start_date,
+        end_date: start_date + Duration::days(1),
+    };
+    let response = client.insert_tournament(&tournament).await;
+    assert!(response.status().is_success());
+    let tournament_id = response.text().await.unwrap().parse::<i32>().unwrap();
+    for court in courts.iter() {
+        let response = client.add_court_with_attributes(tournament_id, court).await;
+        assert!(response.status().is_success());
+    }
+    tournament_id
+}
+
+// The players of match number n have the ids 2n and 2n + 1
+async fn insert_match(client: &AuthenticatedClient, tournament_id: i32, number: i64) -> i64 {
+    for id in [number * 2, number * 2 + 1].iter() {
+        let player = Player {
+            id: *id,
+            name: format!("Spelare {}", id),
+        };
+        let response = client.insert_player(&player).await;
+        assert!(response.status().is_success());
+    }
+    let match_data = Match {
+        id: 0, // not important
+        player_one: Some(number * 2),
+        player_two: Some(number * 2 + 1),
+        tournament_id,
+        class: "p96".to_string(),
+        start_time: Local::now().naive_local() + Duration::hours(2),
+    };
+    let response = client.insert_match(&match_data).await;
+    assert!(response.status().is_success());
+    response.text().await.unwrap().parse::<i64>().unwrap()
+}
+
+async fn check_in(client: &AuthenticatedClient, match_id: i64, number: i64) {
+    for player_id in [number * 2, number * 2 + 1].iter() {
+        let response = client
+            .register_player(
+                match_id,
+                &PlayerMatchRegistrationPayload {
+                    player_id: *player_id,
+                },
+            )
+            .await;
+        assert!(response.status().is_success());
+    }
+}
+
+async fn update_scheduling(
+    client: &AuthenticatedClient,
+    match_id: i64,
+    scheduling: MatchScheduling,
+) {
+    let response = client.update_match_scheduling(match_id, &scheduling).await;
+    assert!(response.status().is_success());
+}
+
+async fn finish(client: &AuthenticatedClient, match_id: i64, number: i64) {
+    let result = MatchResult {
+        result: "6-0 6-0".to_string(),
+        winner: number * 2,
+        outcome: MatchOutcome::Completed,
+    };
+    let response = client.finish_match(match_id, &result).await;
+    assert!(response.status().is_success());
+}
+
+async fn get_queue(client: &AuthenticatedClient, tournament_id: i32) -> Vec<i64> {
+    let response = client
+        .unauthenticated_client
+        .get_court_queue(tournament_id)
+        .await;
+    assert!(response.status().is_success());
+    let queue: Vec<CourtQueueMatch> = response.json().await.unwrap();
+    queue.iter().map(|queued| queued.match_info.id).collect()
+}
+
+// The playing matches and their courts ordered by match id
+async fn get_playing(client: &AuthenticatedClient, tournament_id: i32) -> Vec<(i64, String)> {
+    let response = client.get_tournaments_matches(tournament_id).await;
+    assert!(response.status().is_success());
+    let match_list: TournamentMatchList = response.json().await.unwrap();
+    let mut playing: Vec<(i64, String)> = match_list
+        .playing
+        .into_iter()
+        .map(|playing| (playing.id, playing.court.unwrap()))
+        .collect();
+    playing.sort();
+    playing
+}
+
+fn court(name: &str) -> CourtForm {
+    CourtForm {
+        name: name.to_string(),
+        surface: None,
+        indoor: false,
+        lighting: false,
+    }
+}
+
+#[actix_rt::test]
+async fn should_let_priority_matches_jump_the_queue() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament(&client, &[court("Bana 1")]).await;
+    let mut matches = Vec::new();
+    for number in 0..4 {
+        matches.push(insert_match(&client, tournament_id, number).await);
+    }
+
+    let response = client
+        .unauthenticated_client
+        .get_match_scheduling(matches[3])
+        .await;
+    assert!(response.status().is_success());
+    let scheduling: MatchScheduling = response.json().await.unwrap();
+    assert_eq!(scheduling, MatchScheduling::default());
+
+    // The preferred court has to be one of the courts of the tournament
+    let invalid_schedulings = [
+        MatchScheduling {
+            court_required: true,
+            ..Default::default()
+        },
+        MatchScheduling {
+            preferred_court: Some("Bana 2".to_string()),
+            ..Default::default()
+        },
+    ];
+    for scheduling in invalid_schedulings.iter() {
+        let response = client.update_match_scheduling(matches[3], scheduling).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+    let other_client = client.new_user("other@test.se").await;
+    let response = other_client
+        .update_match_scheduling(matches[3], &MatchScheduling::default())
+        .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // The final is checked in last but gets the court first
+    let final_scheduling = MatchScheduling {
+        priority: 10,
+        ..Default::default()
+    };
+    update_scheduling(&client, matches[3], final_scheduling.clone()).await;
+    let response = client
+        .unauthenticated_client
+        .get_match_scheduling(matches[3])
+        .await;
+    let scheduling: MatchScheduling = response.json().await.unwrap();
+    assert_eq!(scheduling, final_scheduling);
+    for (number, match_id) in matches.iter().enumerate() {
+        check_in(&client, *match_id, number as i64).await;
+    }
+    assert_eq!(
+        get_queue(&client, tournament_id).await,
+        vec![matches[3], matches[1], matches[2]]
+    );
+
+    // Raising the priority of a waiting match places it after the matches with the same priority
+    update_scheduling(&client, matches[2], final_scheduling).await;
+    assert_eq!(
+        get_queue(&client, tournament_id).await,
+        vec![matches[3], matches[2], matches[1]]
+    );
+
+    finish(&client, matches[0], 0).await;
+    assert_eq!(
+        get_playing(&client, tournament_id).await,
+        vec![(matches[3], "Bana 1".to_string())]
+    );
+    assert_eq!(
+        get_queue(&client, tournament_id).await,
+        vec![matches[2], matches[1]]
+    );
+}
+
+#[actix_rt::test]
+async fn should_assign_courts_matching_the_requirements() {
+    let courts = [
+        CourtForm {
+            surface: Some("hard".to_string()),
+            indoor: true,
+            ..court("Bana 1")
+        },
+        CourtForm {
+            surface: Some("hard".to_string()),
+            indoor: true,
+            lighting: true,
+            ..court("Bana 2")
+        },
+        CourtForm {
+            surface: Some("clay".to_string()),
+            lighting: true,
+            ..court("Centercourt")
+        },
+    ];
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament(&client, &courts).await;
+
+    let response = client
+        .unauthenticated_client
+        .get_courts(tournament_id)
+        .await;
+    assert!(response.status().is_success());
+    let allocations: Vec<TournamentCourtAllocation> = response.json().await.unwrap();
+    assert_eq!(allocations.len(), 3);
+    assert_eq!(allocations[2].court_name, "Centercourt");
+    assert_eq!(allocations[2].surface, Some("clay".to_string()));
+    assert!(!allocations[2].indoor);
+    assert!(allocations[2].lighting);
+
+    let mut matches = Vec::new();
+    for number in 0..6 {
+        matches.push(insert_match(&client, tournament_id, number).await);
+    }
+    let schedulings = [
+        MatchScheduling {
+            preferred_court: Some("Centercourt".to_string()),
+            ..Default::default()
+        },
+        MatchScheduling {
+            lighting: true,
+            ..Default::default()
+        },
+        MatchScheduling {
+            preferred_court: Some("Centercourt".to_string()),
+            court_required: true,
+            ..Default::default()
+        },
+        MatchScheduling::default(),
+        MatchScheduling {
+            surface: Some("clay".to_string()),
+            ..Default::default()
+        },
+        MatchScheduling {
+            indoor: Some(false),
+            ..Default::default()
+        },
+    ];
+    for (match_id, scheduling) in matches.iter().zip(schedulings.iter()) {
+        update_scheduling(&client, *match_id, scheduling.clone()).await;
+    }
+
+    // The preferred court is used when it's free and the required one is waited for
+    for (number, match_id) in matches[..5].iter().enumerate() {
+        check_in(&client, *match_id, number as i64).await;
+    }
+    assert_eq!(
+        get_playing(&client, tournament_id).await,
+        vec![
+            (matches[0], "Centercourt".to_string()),
+            (matches[1], "Bana 2".to_string()),
+            (matches[3], "Bana 1".to_string())
+        ]
+    );
+    assert_eq!(
+        get_queue(&client, tournament_id).await,
+        vec![matches[2], matches[4]]
+    );
+
+    // No one waiting can use the freed up court
+    finish(&client, matches[1], 1).await;
+    check_in(&client, matches[5], 5).await;
+    assert_eq!(
+        get_queue(&client, tournament_id).await,
+        vec![matches[2], matches[4], matches[5]]
+    );
+
+    finish(&client, matches[0], 0).await;
+    assert_eq!(
+        get_queue(&client, tournament_id).await,
+        vec![matches[4], matches[5]]
+    );
+    // Dropping the requirement lets the match use the free court right away
+    update_scheduling(&client, matches[4], MatchScheduling::default()).await;
+    assert_eq!(get_queue(&client, tournament_id).await, vec![matches[5]]);
+    assert_eq!(
+        get_playing(&client, tournament_id).await,
+        vec![
+            (matches[2], "Centercourt".to_string()),
+            (matches[3], "Bana 1".to_string()),
+            (matches[4], "Bana 2".to_string())
+        ]
+    );
+}
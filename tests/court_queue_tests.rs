@@ -0,0 +1,256 @@
+use chrono::{Duration, Local};
+use common::{spawn_server_and_authenticate, AuthenticatedClient};
+use reqwest::StatusCode;
+use tournament_tracker_backend::{
+    endpoints::PlayerMatchRegistrationPayload,
+    match_operations::{CourtQueueMatch, TournamentMatchList},
+    stores::{
+        match_store::{Match, MatchOutcome, MatchResult},
+        player_store::Player,
+        tournament_role_store::TournamentRole,
+        tournament_store::Tournament,
+    },
+    tournament_operations::RolePayload,
+};
+
+mod common;
+
+// A tournament with one court where the first match is playing and the rest are queued
+async fn insert_tournament_with_queue(
+    client: &AuthenticatedClient,
+    number_of_matches: i64,
+) -> (i32, Vec<i64>) {
+    let start_date = Local::today().naive_local();
+    let tournament = Tournament {
+        id: 0, // doesn't matter
+        name: "Södertälje open".into(),
+        start_date,
+        end_date: start_date + Duration::days(1),
+    };
+    let response = client.insert_tournament(&tournament).await;
+    assert!(response.status().is_success());
+    let tournament_id = response.text().await.unwrap().parse::<i32>().unwrap();
+    let response = client
+        .add_court_to_tournament(tournament_id, "Bana 1".to_string())
+        .await;
+    assert!(response.status().is_success());
+
+    let mut match_ids = Vec::new();
+    for player_one in (0..number_of_matches * 2).step_by(2) {
+        for id in [player_one, player_one + 1].iter() {
+            let player = Player {
+                id: *id,
+                name: format!("Spelare {}", id),
+            };
+            let response = client.insert_player(&player).await;
+            assert!(response.status().is_success());
+        }
+        let match_data = Match {
+            id: 0, // not important
+            player_one: Some(player_one),
+            player_two: Some(player_one + 1),
+            tournament_id,
+            class: "p96".to_string(),
+            start_time: Local::now().naive_local() + Duration::hours(2),
+        };
+        let response = client.insert_match(&match_data).await;
+        assert!(response.status().is_success());
+        let match_id = response.text().await.unwrap().parse::<i64>().unwrap();
+        for player_id in [player_one, player_one + 1].iter() {
+            let response = client
+                .register_player(
+                    match_id,
+                    &PlayerMatchRegistrationPayload {
+                        player_id: *player_id,
+                    },
+                )
+                .await;
+            assert!(response.status().is_success());
+        }
+        match_ids.push(match_id);
+    }
+    (tournament_id, match_ids)
+}
+
+async fn get_queue(client: &AuthenticatedClient, tournament_id: i32) -> Vec<CourtQueueMatch> {
+    let response = client
+        .unauthenticated_client
+        .get_court_queue(tournament_id)
+        .await;
+    assert!(response.status().is_success());
+    response.json().await.unwrap()
+}
+
+// The match ids in queue order, with the ones on hold last
+fn queue_order(queue: &[CourtQueueMatch]) -> Vec<(i64, Option<usize>)> {
+    queue
+        .iter()
+        .map(|queued| (queued.match_info.id, queued.position))
+        .collect()
+}
+
+#[actix_rt::test]
+async fn should_reorder_the_court_queue() {
+    let client = spawn_server_and_authenticate().await;
+    let (tournament_id, matches) = insert_tournament_with_queue(&client, 4).await;
+
+    let queue = get_queue(&client, tournament_id).await;
+    assert_eq!(
+        queue_order(&queue),
+        vec![
+            (matches[1], Some(1)),
+            (matches[2], Some(2)),
+            (matches[3], Some(3))
+        ]
+    );
+    assert_eq!(queue[0].match_info.player_one.name, "Spelare 2");
+    assert!(queue.iter().all(|queued| !queued.on_hold));
+
+    // Bump the last match to the front
+    let response = client
+        .move_in_court_queue(tournament_id, matches[3], 1)
+        .await;
+    assert!(response.status().is_success());
+    let queue: Vec<CourtQueueMatch> = response.json().await.unwrap();
+    assert_eq!(
+        queue_order(&queue),
+        vec![
+            (matches[3], Some(1)),
+            (matches[1], Some(2)),
+            (matches[2], Some(3))
+        ]
+    );
+    for position in [0, 4].iter() {
+        let response = client
+            .move_in_court_queue(tournament_id, matches[3], *position)
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+    // The first match is already playing
+    let response = client
+        .move_in_court_queue(tournament_id, matches[0], 1)
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = client
+        .pull_from_court_queue(tournament_id, matches[1])
+        .await;
+    assert!(response.status().is_success());
+    let queue: Vec<CourtQueueMatch> = response.json().await.unwrap();
+    assert_eq!(
+        queue_order(&queue),
+        vec![
+            (matches[3], Some(1)),
+            (matches[2], Some(2)),
+            (matches[1], None)
+        ]
+    );
+    assert!(queue[2].on_hold);
+    let response = client
+        .pull_from_court_queue(tournament_id, matches[1])
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = client
+        .put_back_in_court_queue(tournament_id, matches[2], None)
+        .await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    let response = client.get_tournaments_matches(tournament_id).await;
+    assert!(response.status().is_success());
+    let match_list: TournamentMatchList = response.json().await.unwrap();
+    let pulled_match = match_list
+        .scheduled
+        .iter()
+        .find(|scheduled| scheduled.id == matches[1])
+        .unwrap();
+    assert_eq!(pulled_match.court, Some("Tillfälligt ur kön".to_string()));
+
+    let response = client
+        .put_back_in_court_queue(tournament_id, matches[1], Some(1))
+        .await;
+    assert!(response.status().is_success());
+    let queue: Vec<CourtQueueMatch> = response.json().await.unwrap();
+    assert_eq!(
+        queue_order(&queue),
+        vec![
+            (matches[1], Some(1)),
+            (matches[3], Some(2)),
+            (matches[2], Some(3))
+        ]
+    );
+
+    // The court goes to the first match in the new order
+    let result = MatchResult {
+        result: "6-0 6-0".to_string(),
+        winner: 0,
+        outcome: MatchOutcome::Completed,
+    };
+    let response = client.finish_match(matches[0], &result).await;
+    assert!(response.status().is_success());
+    let queue = get_queue(&client, tournament_id).await;
+    assert_eq!(
+        queue_order(&queue),
+        vec![(matches[3], Some(1)), (matches[2], Some(2))]
+    );
+    let response = client.get_tournaments_matches(tournament_id).await;
+    let match_list: TournamentMatchList = response.json().await.unwrap();
+    assert_eq!(match_list.playing.len(), 1);
+    assert_eq!(match_list.playing[0].id, matches[1]);
+}
+
+#[actix_rt::test]
+async fn should_start_match_put_back_when_court_is_free() {
+    let client = spawn_server_and_authenticate().await;
+    let (tournament_id, matches) = insert_tournament_with_queue(&client, 2).await;
+
+    // Only the desk and organizers can change the queue
+    let desk_client = client.new_user("desk@test.se").await;
+    let response = desk_client
+        .pull_from_court_queue(tournament_id, matches[1])
+        .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = client
+        .grant_tournament_role(
+            tournament_id,
+            &RolePayload {
+                email: "desk@test.se".to_string(),
+                role: TournamentRole::Desk,
+            },
+        )
+        .await;
+    assert!(response.status().is_success());
+    let response = desk_client
+        .pull_from_court_queue(tournament_id, matches[1])
+        .await;
+    assert!(response.status().is_success());
+
+    // The pulled match doesn't get the court that's freed up
+    let result = MatchResult {
+        result: "6-0 6-0".to_string(),
+        winner: 0,
+        outcome: MatchOutcome::Completed,
+    };
+    let response = client.finish_match(matches[0], &result).await;
+    assert!(response.status().is_success());
+    let response = client.get_tournaments_matches(tournament_id).await;
+    let match_list: TournamentMatchList = response.json().await.unwrap();
+    assert!(match_list.playing.is_empty());
+
+    let response = desk_client
+        .put_back_in_court_queue(tournament_id, matches[1], None)
+        .await;
+    assert!(response.status().is_success());
+    let queue: Vec<CourtQueueMatch> = response.json().await.unwrap();
+    assert!(queue.is_empty());
+    let response = client.get_tournaments_matches(tournament_id).await;
+    let match_list: TournamentMatchList = response.json().await.unwrap();
+    assert_eq!(match_list.playing.len(), 1);
+    assert_eq!(match_list.playing[0].id, matches[1]);
+    assert_eq!(match_list.playing[0].court, Some("Bana 1".to_string()));
+
+    let response = client
+        .unauthenticated_client
+        .get_court_queue(tournament_id + 1)
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
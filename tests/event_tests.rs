@@ -0,0 +1,335 @@
+use chrono::{Duration, Local};
+use common::{spawn_server_and_authenticate, AuthenticatedClient};
+use reqwest::{Response, StatusCode};
+use tournament_tracker_backend::{
+    bracket_operations::DrawFormat,
+    endpoints::{DrawPayload, GroupMatchesPayload, GroupPayload, PlayerMatchRegistrationPayload},
+    events::TournamentEvent,
+    stores::{
+        bracket_store::BracketMatch,
+        group_store::Group,
+        match_store::{Match, MatchOutcome, MatchResult},
+        player_store::Player,
+        tournament_store::Tournament,
+    },
+};
+
+mod common;
+
+// Reads the events from a Server-Sent Events response
+struct EventReader {
+    response: Response,
+    buffer: String,
+}
+
+impl EventReader {
+    async fn next_event(&mut self) -> TournamentEvent {
+        loop {
+            if let Some(end) = self.buffer.find("\n\n") {
+                let message: String = self.buffer.drain(..end + 2).collect();
+                // Comments start with ':'
+                if let Some(data) = message.trim_end().strip_prefix("data: ") {
+                    return serde_json::from_str(data).unwrap();
+                }
+                continue;
+            }
+            let chunk =
+                actix_rt::time::timeout(std::time::Duration::from_secs(5), self.response.chunk())
+                    .await
+                    .expect("Timed out waiting for event")
+                    .unwrap()
+                    .expect("Event stream ended");
+            self.buffer.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+    }
+}
+
+async fn insert_tournament(client: &AuthenticatedClient) -> i32 {
+    let start_date = Local::today().naive_local();
+    let tournament = Tournament {
+        id: 0, // doesn't matter
+        name: "Södertälje open".into(),
+        start_date,
+        end_date: start_date + Duration::days(1),
+    };
+    let response = client.insert_tournament(&tournament).await;
+    assert!(response.status().is_success());
+    let tournament_id = response.text().await.unwrap().parse::<i32>().unwrap();
+
+    let response = client
+        .add_court_to_tournament(tournament_id, "Bana 1".to_string())
+        .await;
+    assert!(response.status().is_success());
+    for id in 0..4 {
+        let player = Player {
+            id,
+            name: format!("Spelare {}", id),
+        };
+        let response = client.insert_player(&player).await;
+        assert!(response.status().is_success());
+    }
+    tournament_id
+}
+
+async fn insert_match(
+    client: &AuthenticatedClient,
+    tournament_id: i32,
+    player_one: i64,
+    player_two: i64,
+) -> Match {
+    let mut match_data = Match {
+        id: 0, // not important
+        player_one: Some(player_one),
+        player_two: Some(player_two),
+        tournament_id,
+        class: "p96".to_string(),
+        start_time: Local::now().naive_local() + Duration::hours(2),
+    };
+    let response = client.insert_match(&match_data).await;
+    assert!(response.status().is_success());
+    match_data.id = response.text().await.unwrap().parse::<i64>().unwrap();
+    match_data
+}
+
+async fn register_player(client: &AuthenticatedClient, match_id: i64, player_id: i64) {
+    let response = client
+        .register_player(match_id, &PlayerMatchRegistrationPayload { player_id })
+        .await;
+    assert!(response.status().is_success());
+}
+
+#[actix_rt::test]
+async fn should_stream_tournament_events() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament(&client).await;
+    let response = client
+        .unauthenticated_client
+        .get_tournament_events(tournament_id)
+        .await;
+    assert!(response.status().is_success());
+    let mut events = EventReader {
+        response,
+        buffer: String::new(),
+    };
+
+    let first_match = insert_match(&client, tournament_id, 0, 1).await;
+    assert_eq!(
+        events.next_event().await,
+        TournamentEvent::MatchCreated {
+            match_data: first_match.clone()
+        }
+    );
+    let second_match = insert_match(&client, tournament_id, 2, 3).await;
+    assert_eq!(
+        events.next_event().await,
+        TournamentEvent::MatchCreated {
+            match_data: second_match.clone()
+        }
+    );
+
+    // The first match gets the only court and the second one has to wait
+    for (match_id, player_id) in [(first_match.id, 0), (first_match.id, 1)].iter() {
+        register_player(&client, *match_id, *player_id).await;
+        assert_eq!(
+            events.next_event().await,
+            TournamentEvent::PlayerCheckedIn {
+                match_id: *match_id,
+                player_id: *player_id
+            }
+        );
+    }
+    assert_eq!(
+        events.next_event().await,
+        TournamentEvent::MatchStarted {
+            match_id: first_match.id,
+            court: "Bana 1".to_string()
+        }
+    );
+    for (match_id, player_id) in [(second_match.id, 2), (second_match.id, 3)].iter() {
+        register_player(&client, *match_id, *player_id).await;
+        assert_eq!(
+            events.next_event().await,
+            TournamentEvent::PlayerCheckedIn {
+                match_id: *match_id,
+                player_id: *player_id
+            }
+        );
+    }
+    assert_eq!(
+        events.next_event().await,
+        TournamentEvent::QueueChanged {
+            queue: vec![second_match.id]
+        }
+    );
+
+    let result = MatchResult {
+        result: "6-0 6-0".to_string(),
+        winner: 0,
+        outcome: MatchOutcome::Completed,
+    };
+    let response = client.finish_match(first_match.id, &result).await;
+    assert!(response.status().is_success());
+    match events.next_event().await {
+        TournamentEvent::MatchFinished { match_info } => {
+            assert_eq!(match_info.id, first_match.id);
+            assert_eq!(match_info.winner, Some(0));
+            assert_eq!(match_info.result, Some("6-0 6-0".to_string()));
+        }
+        event => panic!("Unexpected event: {:?}", event),
+    }
+    assert_eq!(
+        events.next_event().await,
+        TournamentEvent::MatchStarted {
+            match_id: second_match.id,
+            court: "Bana 1".to_string()
+        }
+    );
+    assert_eq!(
+        events.next_event().await,
+        TournamentEvent::QueueChanged { queue: Vec::new() }
+    );
+
+    let response = client
+        .unauthenticated_client
+        .get_tournament_events(tournament_id + 1)
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn should_send_events_to_clients_of_other_instances() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament(&client).await;
+    let other_instance = client.unauthenticated_client.spawn_instance().await;
+    let response = other_instance.get_tournament_events(tournament_id).await;
+    assert!(response.status().is_success());
+    let mut events = EventReader {
+        response,
+        buffer: String::new(),
+    };
+
+    let match_data = insert_match(&client, tournament_id, 0, 1).await;
+    assert_eq!(
+        events.next_event().await,
+        TournamentEvent::MatchCreated { match_data }
+    );
+}
+
+#[actix_rt::test]
+async fn should_publish_corrected_results() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament(&client).await;
+    let response = client
+        .unauthenticated_client
+        .get_tournament_events(tournament_id)
+        .await;
+    assert!(response.status().is_success());
+    let mut events = EventReader {
+        response,
+        buffer: String::new(),
+    };
+
+    let payload = DrawPayload {
+        class: "p96".to_string(),
+        start_time: Local::now().naive_local() + Duration::hours(2),
+        seeds: vec![0, 1, 2, 3],
+        format: DrawFormat::SingleElimination,
+    };
+    let response = client.generate_draw(tournament_id, &payload).await;
+    assert!(response.status().is_success());
+    let draw = response.json::<Vec<BracketMatch>>().await.unwrap();
+    let player_one = draw[0].player_one.as_ref().unwrap().id;
+    let player_two = draw[0].player_two.as_ref().unwrap().id;
+    for match_data in draw.iter() {
+        match events.next_event().await {
+            TournamentEvent::MatchCreated {
+                match_data: created,
+            } => assert_eq!(created.id, match_data.id),
+            event => panic!("Unexpected event: {:?}", event),
+        }
+    }
+
+    let walkover = |winner| MatchResult {
+        result: String::new(),
+        winner,
+        outcome: MatchOutcome::Walkover,
+    };
+    let response = client.finish_match(draw[0].id, &walkover(player_one)).await;
+    assert!(response.status().is_success());
+    // The winner advances into the final
+    match events.next_event().await {
+        TournamentEvent::MatchCreated { match_data } => {
+            assert_eq!(match_data.id, draw[2].id);
+            assert_eq!(match_data.player_one, Some(player_one));
+        }
+        event => panic!("Unexpected event: {:?}", event),
+    }
+    assert!(matches!(
+        events.next_event().await,
+        TournamentEvent::MatchFinished { .. }
+    ));
+    assert!(matches!(
+        events.next_event().await,
+        TournamentEvent::QueueChanged { .. }
+    ));
+
+    // The walkover was given to the wrong player
+    let response = client
+        .correct_match_result(draw[0].id, &walkover(player_two))
+        .await;
+    assert!(response.status().is_success());
+    match events.next_event().await {
+        TournamentEvent::MatchCreated { match_data } => {
+            assert_eq!(match_data.id, draw[2].id);
+            assert_eq!(match_data.player_one, Some(player_two));
+        }
+        event => panic!("Unexpected event: {:?}", event),
+    }
+    match events.next_event().await {
+        TournamentEvent::MatchFinished { match_info } => {
+            assert_eq!(match_info.id, draw[0].id);
+            assert_eq!(match_info.winner, Some(player_two));
+        }
+        event => panic!("Unexpected event: {:?}", event),
+    }
+}
+
+#[actix_rt::test]
+async fn should_publish_generated_group_matches() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament(&client).await;
+    let response = client
+        .unauthenticated_client
+        .get_tournament_events(tournament_id)
+        .await;
+    assert!(response.status().is_success());
+    let mut events = EventReader {
+        response,
+        buffer: String::new(),
+    };
+
+    let payload = GroupPayload {
+        class: "p96".to_string(),
+        name: "A".to_string(),
+        players: vec![0, 1, 2],
+        tiebreak_rules: Vec::new(),
+    };
+    let response = client.insert_group(tournament_id, &payload).await;
+    assert!(response.status().is_success());
+    let group = response.json::<Group>().await.unwrap();
+    let payload = GroupMatchesPayload {
+        start_time: Local::now().naive_local() + Duration::hours(2),
+    };
+    let response = client
+        .generate_group_matches(tournament_id, group.id, &payload)
+        .await;
+    assert!(response.status().is_success());
+    let matches = response.json::<Vec<Match>>().await.unwrap();
+    assert_eq!(matches.len(), 3);
+    for match_data in matches.into_iter() {
+        assert_eq!(
+            events.next_event().await,
+            TournamentEvent::MatchCreated { match_data }
+        );
+    }
+}
@@ -0,0 +1,430 @@
+use chrono::{Duration, Local};
+use common::{spawn_server_and_authenticate, AuthenticatedClient};
+use reqwest::StatusCode;
+use tournament_tracker_backend::{
+    endpoints::{
+        GroupMatchesPayload, GroupPayload, KnockoutPayload, PlayerMatchRegistrationPayload,
+    },
+    group_operations::Standing,
+    stores::{
+        bracket_store::BracketMatch,
+        group_store::{Group, TiebreakRule},
+        match_store::{Match, MatchOutcome, MatchResult},
+        player_store::Player,
+        tournament_store::Tournament,
+    },
+};
+
+mod common;
+
+async fn insert_tournament_and_players(client: &AuthenticatedClient, player_count: i64) -> i32 {
+    let start_date = Local::today().naive_local();
+    let tournament = Tournament {
+        id: 0, // doesn't matter
+        name: "Södertälje open".into(),
+        start_date,
+        end_date: start_date + Duration::days(1),
+    };
+
+    let response = client.insert_tournament(&tournament).await;
+    assert!(response.status().is_success());
+    let tournament_id = response.text().await.unwrap().parse::<i32>().unwrap();
+
+    for id in 1..=player_count {
+        let player = Player {
+            id,
+            name: format!("Spelare {}", id),
+        };
+        let response = client.insert_player(&player).await;
+        assert!(response.status().is_success());
+    }
+    tournament_id
+}
+
+fn group_payload(name: &str, players: Vec<i64>) -> GroupPayload {
+    GroupPayload {
+        class: "p96".to_string(),
+        name: name.to_string(),
+        players,
+        tiebreak_rules: Vec::new(),
+    }
+}
+
+fn matches_payload() -> GroupMatchesPayload {
+    GroupMatchesPayload {
+        start_time: Local::now().naive_local() + Duration::hours(2),
+    }
+}
+
+// The score is given from the winner's perspective
+async fn play_group_match(
+    client: &AuthenticatedClient,
+    matches: &[Match],
+    winner: i64,
+    loser: i64,
+    score: &str,
+) {
+    let match_data = matches
+        .iter()
+        .find(|match_data| {
+            let players = (
+                match_data.player_one.unwrap(),
+                match_data.player_two.unwrap(),
+            );
+            players == (winner, loser) || players == (loser, winner)
+        })
+        .unwrap();
+    for player_id in [winner, loser].iter() {
+        let player_registration = PlayerMatchRegistrationPayload {
+            player_id: *player_id,
+        };
+        let response = client
+            .register_player(match_data.id, &player_registration)
+            .await;
+        assert!(response.status().is_success());
+    }
+    let result = if match_data.player_one == Some(winner) {
+        score.to_string()
+    } else {
+        score
+            .split_whitespace()
+            .map(|set| {
+                let games: Vec<&str> = set.split('-').collect();
+                format!("{}-{}", games[1], games[0])
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    };
+    let response = client
+        .finish_match(
+            match_data.id,
+            &MatchResult {
+                result,
+                winner,
+                outcome: MatchOutcome::Completed,
+            },
+        )
+        .await;
+    assert!(response.status().is_success());
+}
+
+#[actix_rt::test]
+async fn should_generate_round_robin_matches() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament_and_players(&client, 4).await;
+
+    let response = client
+        .insert_group(tournament_id, &group_payload("A", vec![1, 2, 3, 4]))
+        .await;
+    assert!(response.status().is_success());
+    let group = response.json::<Group>().await.unwrap();
+    assert_eq!(group.tiebreak_rules, TiebreakRule::DEFAULT_RULES.to_vec());
+
+    let response = client
+        .generate_group_matches(tournament_id, group.id, &matches_payload())
+        .await;
+    assert!(response.status().is_success());
+    let matches = response.json::<Vec<Match>>().await.unwrap();
+
+    // Every player meets every other player exactly once
+    assert_eq!(matches.len(), 6);
+    for player_one in 1..=4 {
+        for player_two in (player_one + 1)..=4 {
+            let meetings = matches
+                .iter()
+                .filter(|match_data| {
+                    let players = (
+                        match_data.player_one.unwrap(),
+                        match_data.player_two.unwrap(),
+                    );
+                    players == (player_one, player_two) || players == (player_two, player_one)
+                })
+                .count();
+            assert_eq!(meetings, 1);
+        }
+    }
+
+    // Matches can only be generated once
+    let response = client
+        .generate_group_matches(tournament_id, group.id, &matches_payload())
+        .await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    let response = client
+        .generate_group_matches(tournament_id, group.id + 1, &matches_payload())
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn should_not_create_invalid_groups() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament_and_players(&client, 3).await;
+
+    // Not enough players
+    let response = client
+        .insert_group(tournament_id, &group_payload("A", vec![1]))
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Same player twice
+    let response = client
+        .insert_group(tournament_id, &group_payload("A", vec![1, 2, 1]))
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Unknown player
+    let response = client
+        .insert_group(tournament_id, &group_payload("A", vec![1, 1337]))
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // Unknown tournament
+    let response = client
+        .insert_group(tournament_id + 1, &group_payload("A", vec![1, 2]))
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = client
+        .insert_group(tournament_id, &group_payload("A", vec![1, 2, 3]))
+        .await;
+    assert!(response.status().is_success());
+
+    // Group names are unique within the class
+    let response = client
+        .insert_group(tournament_id, &group_payload("A", vec![2, 3]))
+        .await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
+
+#[actix_rt::test]
+async fn should_rank_group_standings() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament_and_players(&client, 3).await;
+    let response = client
+        .add_court_to_tournament(tournament_id, "Bana 1".to_string())
+        .await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .insert_group(tournament_id, &group_payload("A", vec![1, 2, 3]))
+        .await;
+    let group = response.json::<Group>().await.unwrap();
+    let response = client
+        .generate_group_matches(tournament_id, group.id, &matches_payload())
+        .await;
+    let matches = response.json::<Vec<Match>>().await.unwrap();
+    assert_eq!(matches.len(), 3);
+
+    // Nothing is played yet
+    let response = client.get_group_standings(tournament_id, group.id).await;
+    assert!(response.status().is_success());
+    let standings = response.json::<Vec<Standing>>().await.unwrap();
+    assert_eq!(standings.len(), 3);
+    assert!(standings.iter().all(|standing| standing.played == 0));
+
+    // Everyone wins one match and the sets are even,
+    // head to head is even between the three players so it's decided on games
+    play_group_match(&client, &matches, 1, 2, "6-0 6-0").await;
+    play_group_match(&client, &matches, 2, 3, "6-4 6-4").await;
+    play_group_match(&client, &matches, 3, 1, "6-3 6-3").await;
+
+    let response = client.get_group_standings(tournament_id, group.id).await;
+    let standings = response.json::<Vec<Standing>>().await.unwrap();
+    let order: Vec<i64> = standings
+        .iter()
+        .map(|standing| standing.player.id)
+        .collect();
+    assert_eq!(order, vec![1, 3, 2]);
+    assert_eq!(standings[0].rank, 1);
+    assert_eq!(standings[0].played, 2);
+    assert_eq!(standings[0].wins, 1);
+    assert_eq!(standings[0].sets_won, 2);
+    assert_eq!(standings[0].sets_lost, 2);
+    assert_eq!(standings[0].games_won, 18);
+    assert_eq!(standings[0].games_lost, 12);
+    assert_eq!(standings[2].rank, 3);
+    assert_eq!(standings[2].games_won, 12);
+    assert_eq!(standings[2].games_lost, 20);
+}
+
+#[actix_rt::test]
+async fn should_break_ties_on_head_to_head() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament_and_players(&client, 4).await;
+    let response = client
+        .add_court_to_tournament(tournament_id, "Bana 1".to_string())
+        .await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .insert_group(tournament_id, &group_payload("A", vec![1, 2, 3, 4]))
+        .await;
+    let group = response.json::<Group>().await.unwrap();
+    let response = client
+        .generate_group_matches(tournament_id, group.id, &matches_payload())
+        .await;
+    let matches = response.json::<Vec<Match>>().await.unwrap();
+
+    // Player 1 and 2 both win two matches but 2 has the better game difference,
+    // player 1 still ranks higher since they won their meeting. The same goes for 4 and 3
+    play_group_match(&client, &matches, 1, 2, "7-6 7-6").await;
+    play_group_match(&client, &matches, 3, 1, "6-0 6-0").await;
+    play_group_match(&client, &matches, 1, 4, "6-4 6-4").await;
+    play_group_match(&client, &matches, 2, 3, "6-0 6-0").await;
+    play_group_match(&client, &matches, 2, 4, "6-0 6-0").await;
+    play_group_match(&client, &matches, 4, 3, "6-4 6-4").await;
+
+    let response = client.get_group_standings(tournament_id, group.id).await;
+    let standings = response.json::<Vec<Standing>>().await.unwrap();
+    let order: Vec<i64> = standings
+        .iter()
+        .map(|standing| standing.player.id)
+        .collect();
+    assert_eq!(order, vec![1, 2, 4, 3]);
+}
+
+#[actix_rt::test]
+async fn should_break_three_way_ties_on_head_to_head() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament_and_players(&client, 5).await;
+    let response = client
+        .add_court_to_tournament(tournament_id, "Bana 1".to_string())
+        .await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .insert_group(tournament_id, &group_payload("A", vec![1, 2, 3, 4, 5]))
+        .await;
+    let group = response.json::<Group>().await.unwrap();
+    let response = client
+        .generate_group_matches(tournament_id, group.id, &matches_payload())
+        .await;
+    let matches = response.json::<Vec<Match>>().await.unwrap();
+
+    // Player 1, 2 and 3 all win two matches. Between the three of them 1 won both
+    // their matches and 2 beat 3, even though 3 has the best game difference
+    play_group_match(&client, &matches, 1, 2, "7-6 7-6").await;
+    play_group_match(&client, &matches, 1, 3, "7-6 7-6").await;
+    play_group_match(&client, &matches, 2, 3, "7-6 7-6").await;
+    play_group_match(&client, &matches, 4, 1, "6-0 6-0").await;
+    play_group_match(&client, &matches, 5, 1, "6-0 6-0").await;
+    play_group_match(&client, &matches, 2, 4, "6-4 6-4").await;
+    play_group_match(&client, &matches, 5, 2, "6-0 6-0").await;
+    play_group_match(&client, &matches, 3, 4, "6-0 6-0").await;
+    play_group_match(&client, &matches, 3, 5, "6-0 6-0").await;
+    play_group_match(&client, &matches, 5, 4, "6-4 6-4").await;
+
+    let response = client.get_group_standings(tournament_id, group.id).await;
+    let standings = response.json::<Vec<Standing>>().await.unwrap();
+    let order: Vec<i64> = standings
+        .iter()
+        .map(|standing| standing.player.id)
+        .collect();
+    assert_eq!(order, vec![5, 1, 2, 3, 4]);
+}
+
+#[actix_rt::test]
+async fn should_create_knockout_draw_from_groups() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament_and_players(&client, 4).await;
+    let response = client
+        .add_court_to_tournament(tournament_id, "Bana 1".to_string())
+        .await;
+    assert!(response.status().is_success());
+
+    let mut group_matches = Vec::new();
+    for (name, players) in [("A", vec![1, 2]), ("B", vec![3, 4])].iter() {
+        let response = client
+            .insert_group(tournament_id, &group_payload(name, players.clone()))
+            .await;
+        let group = response.json::<Group>().await.unwrap();
+        let response = client
+            .generate_group_matches(tournament_id, group.id, &matches_payload())
+            .await;
+        group_matches.extend(response.json::<Vec<Match>>().await.unwrap());
+    }
+
+    let knockout_payload = KnockoutPayload {
+        class: "p96".to_string(),
+        start_time: Local::now().naive_local() + Duration::hours(4),
+        players_per_group: 2,
+    };
+
+    play_group_match(&client, &group_matches, 1, 2, "6-3 6-3").await;
+    // Group B isn't finished yet
+    let response = client
+        .generate_knockout_draw(tournament_id, &knockout_payload)
+        .await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    play_group_match(&client, &group_matches, 4, 3, "6-3 6-3").await;
+    let response = client
+        .generate_knockout_draw(tournament_id, &knockout_payload)
+        .await;
+    assert!(response.status().is_success());
+    let draw = response.json::<Vec<BracketMatch>>().await.unwrap();
+
+    // A1 meets B2 and B1 meets A2
+    assert_eq!(draw.len(), 3);
+    assert_eq!(draw[0].player_one.as_ref().unwrap().id, 1);
+    assert_eq!(draw[0].player_two.as_ref().unwrap().id, 3);
+    assert_eq!(draw[1].player_one.as_ref().unwrap().id, 4);
+    assert_eq!(draw[1].player_two.as_ref().unwrap().id, 2);
+    assert_eq!(draw[2].player_one_from, Some(draw[0].id));
+    assert_eq!(draw[2].player_two_from, Some(draw[1].id));
+
+    // Only one knockout draw per class
+    let response = client
+        .generate_knockout_draw(tournament_id, &knockout_payload)
+        .await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
+
+#[actix_rt::test]
+async fn should_not_cross_over_three_groups() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament_and_players(&client, 6).await;
+    let response = client
+        .add_court_to_tournament(tournament_id, "Bana 1".to_string())
+        .await;
+    assert!(response.status().is_success());
+
+    let mut group_matches = Vec::new();
+    for (name, players) in [("A", vec![1, 2]), ("B", vec![3, 4]), ("C", vec![5, 6])].iter() {
+        let response = client
+            .insert_group(tournament_id, &group_payload(name, players.clone()))
+            .await;
+        let group = response.json::<Group>().await.unwrap();
+        let response = client
+            .generate_group_matches(tournament_id, group.id, &matches_payload())
+            .await;
+        group_matches.extend(response.json::<Vec<Match>>().await.unwrap());
+    }
+    play_group_match(&client, &group_matches, 1, 2, "6-3 6-3").await;
+    play_group_match(&client, &group_matches, 3, 4, "6-3 6-3").await;
+    play_group_match(&client, &group_matches, 5, 6, "6-3 6-3").await;
+
+    // Winners and runners up can't be kept apart with three groups
+    let mut knockout_payload = KnockoutPayload {
+        class: "p96".to_string(),
+        start_time: Local::now().naive_local() + Duration::hours(4),
+        players_per_group: 2,
+    };
+    let response = client
+        .generate_knockout_draw(tournament_id, &knockout_payload)
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Only the group winners advancing is fine, the top seed gets a bye
+    knockout_payload.players_per_group = 1;
+    let response = client
+        .generate_knockout_draw(tournament_id, &knockout_payload)
+        .await;
+    assert!(response.status().is_success());
+    let draw = response.json::<Vec<BracketMatch>>().await.unwrap();
+    assert_eq!(draw.len(), 2);
+    assert_eq!(draw[0].player_one.as_ref().unwrap().id, 3);
+    assert_eq!(draw[0].player_two.as_ref().unwrap().id, 5);
+    assert_eq!(draw[1].player_one.as_ref().unwrap().id, 1);
+}
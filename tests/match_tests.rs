@@ -2,13 +2,17 @@ use chrono::{Duration, Local};
 use common::{spawn_server_and_authenticate, AuthenticatedClient};
 use reqwest::{Response, StatusCode};
 use tournament_tracker_backend::match_operations::MatchInfo;
-use tournament_tracker_backend::stores::match_store::MatchResult;
+use tournament_tracker_backend::stores::match_store::{
+    MatchOutcome, MatchResult, MatchResultCorrection,
+};
 use tournament_tracker_backend::{
     endpoints::PlayerMatchRegistrationPayload,
     match_operations::TournamentMatchList,
     stores::{
-        match_store::Match, player_registration_store::PlayerMatchRegistration,
-        player_store::Player, tournament_store::Tournament,
+        match_store::Match,
+        player_registration_store::PlayerMatchRegistration,
+        player_store::Player,
+        tournament_store::{MatchFormat, Tournament},
     },
 };
 
@@ -58,8 +62,8 @@ async fn insert_match(
     // insert match
     let match_data = Match {
         id: 0, // not important
-        player_one,
-        player_two,
+        player_one: Some(player_one),
+        player_two: Some(player_two),
         tournament_id,
         class: "p96".to_string(),
         start_time: Local::now().naive_local() + Duration::hours(2),
@@ -71,10 +75,7 @@ async fn insert_match(
 }
 
 async fn register_player(client: &AuthenticatedClient, match_id: i64, player_id: i64) {
-    let player_registration = PlayerMatchRegistrationPayload {
-        player_id,
-        registered_by: "Svante".to_string(),
-    };
+    let player_registration = PlayerMatchRegistrationPayload { player_id };
 
     // register player 1
     let response = client.register_player(match_id, &player_registration).await;
@@ -83,7 +84,8 @@ async fn register_player(client: &AuthenticatedClient, match_id: i64, player_id:
 
     assert_eq!(player_id, actual.player_id);
     assert_eq!(match_id, actual.match_id);
-    assert_eq!("Svante".to_string(), actual.registerd_by);
+    assert_eq!("dummy@test.se".to_string(), actual.registerd_by);
+    assert!(actual.registerd_by_user.is_some());
 }
 
 #[actix_rt::test]
@@ -94,8 +96,8 @@ async fn should_fail_to_register_match_with_invalid_rooster() {
 
     let match_data = Match {
         id: 0, // not important
-        player_one,
-        player_two: player_one, // Can't play against yourself!
+        player_one: Some(player_one),
+        player_two: Some(player_one), // Can't play against yourself!
         tournament_id,
         class: "p96".to_string(),
         start_time: Local::now().naive_local() + Duration::hours(2),
@@ -113,8 +115,8 @@ async fn should_fail_to_register_match_with_invalid_start_date() {
 
     let match_data = Match {
         id: 0, // not important
-        player_one,
-        player_two,
+        player_one: Some(player_one),
+        player_two: Some(player_two),
         tournament_id,
         class: "p96".to_string(),
         start_time: Local::now().naive_local() - Duration::hours(2),
@@ -128,10 +130,7 @@ async fn should_fail_to_register_match_with_invalid_start_date() {
 async fn should_fail_to_register_to_missing_match() {
     let client = spawn_server_and_authenticate().await;
 
-    let player_registration = PlayerMatchRegistrationPayload {
-        player_id: 0,
-        registered_by: "Svante".to_string(),
-    };
+    let player_registration = PlayerMatchRegistrationPayload { player_id: 0 };
 
     let response = client.register_player(2, &player_registration).await;
     assert_eq!(response.status(), StatusCode::NOT_FOUND);
@@ -172,17 +171,13 @@ async fn should_not_register_invalid_player() {
     let match_id = insert_match(&client, tournament_id, player_one, player_two).await;
 
     // Try to register player not part of rooster
-    let player_registration = PlayerMatchRegistrationPayload {
-        player_id: 1337,
-        registered_by: "Svante".to_string(),
-    };
+    let player_registration = PlayerMatchRegistrationPayload { player_id: 1337 };
     let response = client.register_player(match_id, &player_registration).await;
     assert!(response.status().is_client_error());
 
     // Try to register player twice
     let player_registration = PlayerMatchRegistrationPayload {
         player_id: player_one,
-        registered_by: "Svante".to_string(),
     };
     let response = client.register_player(match_id, &player_registration).await;
     assert!(response.status().is_success());
@@ -190,7 +185,6 @@ async fn should_not_register_invalid_player() {
     // Second attempt should fail
     let player_registration = PlayerMatchRegistrationPayload {
         player_id: player_one,
-        registered_by: "Svante".to_string(),
     };
     let response = client.register_player(match_id, &player_registration).await;
     assert!(response.status().is_client_error())
@@ -295,14 +289,16 @@ async fn should_assign_court_when_match_is_finished() {
         .finish_match(
             match_id_1,
             &MatchResult {
-                result: "2-3(2) 4-4 3-3(2)".to_string(),
+                result: "6-2 6-7(4) 7-6(5)".to_string(),
                 winner: player_one,
+                outcome: MatchOutcome::Completed,
             },
         )
         .await;
     assert!(response.status().is_success());
     let match_info = response.json::<MatchInfo>().await.unwrap();
-    assert_eq!(match_info.result, Some("2-3(2) 4-4 3-3(2)".to_string()));
+    assert_eq!(match_info.result, Some("6-2 6-7(4) 7-6(5)".to_string()));
+    assert_eq!(match_info.score.as_ref().unwrap().sets.len(), 3);
     assert_eq!(match_info.winner, Some(player_one));
     assert_eq!(match_info.court, None);
     assert!(match_info.player_two_arrived);
@@ -351,6 +347,7 @@ async fn should_not_allow_invalid_result() {
         &MatchResult {
             result: "2-3-4-5 6-2(2)".to_string(),
             winner: 0, // player_one
+            outcome: MatchOutcome::Completed,
         },
     )
     .await;
@@ -363,10 +360,367 @@ async fn should_not_allow_invalid_winner() {
     let response = create_and_finish_match(
         &client,
         &MatchResult {
-            result: "2-3(2) 4-4 3-3(2)".to_string(),
+            result: "6-2 6-7(4) 7-6(5)".to_string(),
             winner: 10,
+            outcome: MatchOutcome::Completed,
+        },
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn should_not_allow_unfinished_score() {
+    let client = spawn_server_and_authenticate().await;
+    let response = create_and_finish_match(
+        &client,
+        &MatchResult {
+            result: "6-5 6-0".to_string(),
+            winner: 0, // player_one
+            outcome: MatchOutcome::Completed,
+        },
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn should_not_allow_winner_not_matching_score() {
+    let client = spawn_server_and_authenticate().await;
+    let response = create_and_finish_match(
+        &client,
+        &MatchResult {
+            result: "6-0 0-6 6-0".to_string(),
+            winner: 1, // player_two only won one set
+            outcome: MatchOutcome::Completed,
         },
     )
     .await;
     assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 }
+
+#[actix_rt::test]
+async fn should_validate_score_against_match_format() {
+    let client = spawn_server_and_authenticate().await;
+    let (tournament_id, player_one, player_two) = insert_tournament_and_players(&client).await;
+
+    let response = client.get_match_format(tournament_id).await;
+    assert!(response.status().is_success());
+    assert_eq!(
+        response.json::<MatchFormat>().await.unwrap(),
+        MatchFormat::default()
+    );
+
+    let invalid_format = MatchFormat {
+        best_of: 4,
+        match_tiebreak: false,
+    };
+    let response = client
+        .update_match_format(tournament_id, &invalid_format)
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let match_format = MatchFormat {
+        best_of: 3,
+        match_tiebreak: true,
+    };
+    let response = client
+        .update_match_format(tournament_id, &match_format)
+        .await;
+    assert!(response.status().is_success());
+
+    let match_id = insert_match(&client, tournament_id, player_one, player_two).await;
+    let response = client
+        .add_court_to_tournament(tournament_id, "Bana 1".to_string())
+        .await;
+    assert!(response.status().is_success());
+    register_player(&client, match_id, player_one).await;
+    register_player(&client, match_id, player_two).await;
+
+    // A full third set isn't played
+    let response = client
+        .finish_match(
+            match_id,
+            &MatchResult {
+                result: "6-3 3-6 6-4".to_string(),
+                winner: player_one,
+                outcome: MatchOutcome::Completed,
+            },
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = client
+        .finish_match(
+            match_id,
+            &MatchResult {
+                result: "6-3 3-6 10-8".to_string(),
+                winner: player_one,
+                outcome: MatchOutcome::Completed,
+            },
+        )
+        .await;
+    assert!(response.status().is_success());
+    let match_info = response.json::<MatchInfo>().await.unwrap();
+    assert_eq!(match_info.result, Some("6-3 3-6 10-8".to_string()));
+    let score = match_info.score.unwrap();
+    assert_eq!(score.sets.len(), 3);
+    assert_eq!(score.sets[2].player_one, 10);
+    assert_eq!(score.sets[2].player_two, 8);
+}
+
+#[actix_rt::test]
+async fn should_give_walkover_without_court() {
+    let client = spawn_server_and_authenticate().await;
+    let (tournament_id, player_one, player_two) = insert_tournament_and_players(&client).await;
+    let match_id = insert_match(&client, tournament_id, player_one, player_two).await;
+
+    // Walkovers don't have a score
+    let response = client
+        .finish_match(
+            match_id,
+            &MatchResult {
+                result: "6-0 6-0".to_string(),
+                winner: player_one,
+                outcome: MatchOutcome::Walkover,
+            },
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Player two never showed up
+    let response = client
+        .finish_match(
+            match_id,
+            &MatchResult {
+                result: "".to_string(),
+                winner: player_one,
+                outcome: MatchOutcome::Walkover,
+            },
+        )
+        .await;
+    assert!(response.status().is_success());
+    let match_info = response.json::<MatchInfo>().await.unwrap();
+    assert_eq!(match_info.winner, Some(player_one));
+    assert_eq!(match_info.outcome, Some(MatchOutcome::Walkover));
+    assert_eq!(match_info.result, None);
+    assert_eq!(match_info.score, None);
+    assert_eq!(match_info.court, None);
+
+    let response = client.get_tournaments_matches(tournament_id).await;
+    let match_list = response.json::<TournamentMatchList>().await.unwrap();
+    assert_eq!(match_list.finished.len(), 1);
+    assert_eq!(match_list.finished[0], match_info);
+
+    // The match can't be started afterwards
+    let player_registration = PlayerMatchRegistrationPayload {
+        player_id: player_two,
+    };
+    let response = client.register_player(match_id, &player_registration).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
+
+#[actix_rt::test]
+async fn should_only_finish_match_once() {
+    let client = spawn_server_and_authenticate().await;
+    let (tournament_id, player_one, player_two) = insert_tournament_and_players(&client).await;
+    let match_id = insert_match(&client, tournament_id, player_one, player_two).await;
+
+    // Both finish the match at the same time, only one of them succeeds
+    let walkover = |winner| MatchResult {
+        result: "".to_string(),
+        winner,
+        outcome: MatchOutcome::Walkover,
+    };
+    let (first, second) = futures::future::join(
+        client.finish_match(match_id, &walkover(player_one)),
+        client.finish_match(match_id, &walkover(player_two)),
+    )
+    .await;
+    let mut statuses = vec![first.status(), second.status()];
+    statuses.sort();
+    assert_eq!(statuses, vec![StatusCode::OK, StatusCode::CONFLICT]);
+}
+
+#[actix_rt::test]
+async fn should_remove_walkover_from_court_queue() {
+    let client = spawn_server_and_authenticate().await;
+    let (tournament_id, player_one, player_two) = insert_tournament_and_players(&client).await;
+    let response = client
+        .add_court_to_tournament(tournament_id, "Bana 1".to_string())
+        .await;
+    assert!(response.status().is_success());
+    for id in 2..=5 {
+        let player = Player {
+            id,
+            name: format!("Spelare {}", id),
+        };
+        let response = client.insert_player(&player).await;
+        assert!(response.status().is_success());
+    }
+    let playing_match = insert_match(&client, tournament_id, player_one, player_two).await;
+    let walkover_match = insert_match(&client, tournament_id, 2, 3).await;
+    let waiting_match = insert_match(&client, tournament_id, 4, 5).await;
+    for (match_id, players) in [
+        (playing_match, (player_one, player_two)),
+        (walkover_match, (2, 3)),
+        (waiting_match, (4, 5)),
+    ]
+    .iter()
+    {
+        register_player(&client, *match_id, players.0).await;
+        register_player(&client, *match_id, players.1).await;
+    }
+
+    // Player 3 left before the match got a court
+    let response = client
+        .finish_match(
+            walkover_match,
+            &MatchResult {
+                result: "".to_string(),
+                winner: 2,
+                outcome: MatchOutcome::Walkover,
+            },
+        )
+        .await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .finish_match(
+            playing_match,
+            &MatchResult {
+                result: "6-2 6-2".to_string(),
+                winner: player_one,
+                outcome: MatchOutcome::Completed,
+            },
+        )
+        .await;
+    assert!(response.status().is_success());
+
+    // The court goes to the match that was still waiting
+    let response = client.get_tournaments_matches(tournament_id).await;
+    let match_list = response.json::<TournamentMatchList>().await.unwrap();
+    assert_eq!(match_list.finished.len(), 2);
+    assert_eq!(match_list.playing.len(), 1);
+    assert_eq!(match_list.playing[0].id, waiting_match);
+    assert_eq!(match_list.playing[0].court, Some("Bana 1".to_string()));
+    assert!(match_list.scheduled.is_empty());
+}
+
+#[actix_rt::test]
+async fn should_record_retirement_with_partial_score() {
+    let client = spawn_server_and_authenticate().await;
+    let (tournament_id, player_one, player_two) = insert_tournament_and_players(&client).await;
+    let match_id = insert_match(&client, tournament_id, player_one, player_two).await;
+    let response = client
+        .add_court_to_tournament(tournament_id, "Bana 1".to_string())
+        .await;
+    assert!(response.status().is_success());
+
+    let retirement = MatchResult {
+        result: "6-2 3-1".to_string(),
+        winner: player_one,
+        outcome: MatchOutcome::Retired,
+    };
+    // Only started matches can end in a retirement
+    let response = client.finish_match(match_id, &retirement).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    register_player(&client, match_id, player_one).await;
+    register_player(&client, match_id, player_two).await;
+    let response = client.finish_match(match_id, &retirement).await;
+    assert!(response.status().is_success());
+    let match_info = response.json::<MatchInfo>().await.unwrap();
+    assert_eq!(match_info.outcome, Some(MatchOutcome::Retired));
+    assert_eq!(match_info.result, Some("6-2 3-1".to_string()));
+    assert_eq!(match_info.score.unwrap().sets.len(), 2);
+}
+
+#[actix_rt::test]
+async fn should_disqualify_player() {
+    let client = spawn_server_and_authenticate().await;
+    let response = create_and_finish_match(
+        &client,
+        &MatchResult {
+            result: "6-2 2-1".to_string(),
+            winner: 1, // player_one is disqualified
+            outcome: MatchOutcome::Disqualified,
+        },
+    )
+    .await;
+    assert!(response.status().is_success());
+    let match_info = response.json::<MatchInfo>().await.unwrap();
+    assert_eq!(match_info.outcome, Some(MatchOutcome::Disqualified));
+    assert_eq!(match_info.winner, Some(1));
+    assert_eq!(match_info.result, Some("6-2 2-1".to_string()));
+}
+
+#[actix_rt::test]
+async fn should_correct_finished_match_result() {
+    let client = spawn_server_and_authenticate().await;
+    let (tournament_id, player_one, player_two) = insert_tournament_and_players(&client).await;
+    let match_id = insert_match(&client, tournament_id, player_one, player_two).await;
+    let response = client
+        .add_court_to_tournament(tournament_id, "Bana 1".to_string())
+        .await;
+    assert!(response.status().is_success());
+
+    let corrected_result = MatchResult {
+        result: "2-6 6-3 4-6".to_string(),
+        winner: player_two,
+        outcome: MatchOutcome::Completed,
+    };
+    // Only finished matches can be corrected
+    let response = client
+        .correct_match_result(match_id, &corrected_result)
+        .await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    register_player(&client, match_id, player_one).await;
+    register_player(&client, match_id, player_two).await;
+    let response = client
+        .finish_match(
+            match_id,
+            &MatchResult {
+                result: "6-2 3-6 6-4".to_string(),
+                winner: player_one,
+                outcome: MatchOutcome::Completed,
+            },
+        )
+        .await;
+    assert!(response.status().is_success());
+
+    // The corrected result is validated just like a new one
+    let response = client
+        .correct_match_result(
+            match_id,
+            &MatchResult {
+                result: "2-6 6-3 4-6".to_string(),
+                winner: player_one,
+                outcome: MatchOutcome::Completed,
+            },
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = client
+        .correct_match_result(match_id, &corrected_result)
+        .await;
+    assert!(response.status().is_success());
+    let match_info = response.json::<MatchInfo>().await.unwrap();
+    assert_eq!(match_info.winner, Some(player_two));
+    assert_eq!(match_info.result, Some("2-6 6-3 4-6".to_string()));
+
+    let response = client.get_tournaments_matches(tournament_id).await;
+    let match_list = response.json::<TournamentMatchList>().await.unwrap();
+    assert_eq!(match_list.finished[0], match_info);
+
+    // The replaced result is kept
+    let response = client.get_match_result_corrections(match_id).await;
+    assert!(response.status().is_success());
+    let corrections = response.json::<Vec<MatchResultCorrection>>().await.unwrap();
+    assert_eq!(corrections.len(), 1);
+    assert_eq!(corrections[0].previous_result, "6-2 3-6 6-4");
+    assert_eq!(corrections[0].previous_winner, player_one);
+    assert!(corrections[0].corrected_by.is_some());
+}
@@ -0,0 +1,94 @@
+use common::spawn_server_and_authenticate;
+use reqwest::StatusCode;
+use tournament_tracker_backend::endpoints::CredentialsPayload;
+
+mod common;
+
+fn credentials(password: &str) -> CredentialsPayload {
+    CredentialsPayload {
+        email: "dummy@test.se".to_string(),
+        password: password.to_string(),
+    }
+}
+
+#[actix_rt::test]
+async fn should_change_password() {
+    let client = spawn_server_and_authenticate().await;
+
+    let response = client
+        .change_password("wrong-password", "new-secure-password")
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = client
+        .change_password("some-secure-password", "short")
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = client
+        .change_password("some-secure-password", "new-secure-password")
+        .await;
+    assert!(response.status().is_success());
+
+    let unauthenticated_client = &client.unauthenticated_client;
+    let response = unauthenticated_client
+        .login(&credentials("some-secure-password"))
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = unauthenticated_client
+        .login(&credentials("new-secure-password"))
+        .await;
+    assert!(response.status().is_success());
+}
+
+#[actix_rt::test]
+async fn should_reset_forgotten_password() {
+    let client = spawn_server_and_authenticate().await;
+    let unauthenticated_client = &client.unauthenticated_client;
+
+    // Doesn't reveal that the account doesn't exist
+    let response = unauthenticated_client
+        .forgot_password("unknown@test.se")
+        .await;
+    assert!(response.status().is_success());
+    // Only the verification email has been sent
+    assert_eq!(unauthenticated_client.mailer.sent_emails().len(), 1);
+
+    let response = unauthenticated_client
+        .forgot_password("dummy@test.se")
+        .await;
+    assert!(response.status().is_success());
+    let email = unauthenticated_client.last_email_to("dummy@test.se");
+    assert_eq!(email.subject, "Reset your password");
+    // The token is the last word of the second paragraph
+    let token = email
+        .body
+        .split("\n\n")
+        .nth(1)
+        .and_then(|line| line.split_whitespace().last())
+        .unwrap()
+        .to_string();
+
+    let response = unauthenticated_client
+        .reset_password("invalid-token", "new-secure-password")
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = unauthenticated_client
+        .reset_password(&token, "new-secure-password")
+        .await;
+    assert!(response.status().is_success());
+    // Single use
+    let response = unauthenticated_client
+        .reset_password(&token, "another-secure-password")
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // The old sessions are logged out
+    let response = client
+        .change_password("new-secure-password", "whatever-password")
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = unauthenticated_client
+        .login(&credentials("new-secure-password"))
+        .await;
+    assert!(response.status().is_success());
+}
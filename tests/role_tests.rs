@@ -0,0 +1,200 @@
+use chrono::{Duration, Local};
+use common::{spawn_server_and_authenticate, AuthenticatedClient};
+use reqwest::StatusCode;
+use tournament_tracker_backend::{
+    endpoints::PlayerMatchRegistrationPayload,
+    stores::{
+        match_store::{Match, MatchOutcome, MatchResult},
+        player_store::Player,
+        tournament_role_store::{TournamentRole, TournamentRoleGrant},
+        tournament_store::{MatchFormat, Tournament},
+    },
+    tournament_operations::RolePayload,
+};
+
+mod common;
+
+// Inserts a tournament owned by the client with a court and a match between player 0 and 1
+async fn insert_tournament_and_match(client: &AuthenticatedClient) -> (i32, i64) {
+    let start_date = Local::today().naive_local();
+    let tournament = Tournament {
+        id: 0, // doesn't matter
+        name: "Södertälje open".into(),
+        start_date,
+        end_date: start_date + Duration::days(1),
+    };
+    let response = client.insert_tournament(&tournament).await;
+    assert!(response.status().is_success());
+    let tournament_id = response.text().await.unwrap().parse::<i32>().unwrap();
+
+    let response = client
+        .add_court_to_tournament(tournament_id, "Bana 1".to_string())
+        .await;
+    assert!(response.status().is_success());
+
+    for id in 0..2 {
+        let player = Player {
+            id,
+            name: format!("Spelare {}", id),
+        };
+        let response = client.insert_player(&player).await;
+        assert!(response.status().is_success());
+    }
+    let match_data = Match {
+        id: 0, // not important
+        player_one: Some(0),
+        player_two: Some(1),
+        tournament_id,
+        class: "p96".to_string(),
+        start_time: Local::now().naive_local() + Duration::hours(2),
+    };
+    let response = client.insert_match(&match_data).await;
+    assert!(response.status().is_success());
+    let match_id = response.text().await.unwrap().parse::<i64>().unwrap();
+    (tournament_id, match_id)
+}
+
+fn role_payload(email: &str, role: TournamentRole) -> RolePayload {
+    RolePayload {
+        email: email.to_string(),
+        role,
+    }
+}
+
+async fn register_player(
+    client: &AuthenticatedClient,
+    match_id: i64,
+    player_id: i64,
+) -> StatusCode {
+    let player_registration = PlayerMatchRegistrationPayload { player_id };
+    client
+        .register_player(match_id, &player_registration)
+        .await
+        .status()
+}
+
+fn match_result() -> MatchResult {
+    MatchResult {
+        result: "6-2 6-2".to_string(),
+        winner: 0,
+        outcome: MatchOutcome::Completed,
+    }
+}
+
+#[actix_rt::test]
+async fn should_not_allow_changes_without_role() {
+    let owner = spawn_server_and_authenticate().await;
+    let client = owner.new_user("stranger@test.se").await;
+    let (tournament_id, match_id) = insert_tournament_and_match(&owner).await;
+
+    let response = client
+        .add_court_to_tournament(tournament_id, "Bana 2".to_string())
+        .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = client
+        .update_match_format(
+            tournament_id,
+            &MatchFormat {
+                best_of: 5,
+                match_tiebreak: false,
+            },
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    assert_eq!(
+        register_player(&client, match_id, 0).await,
+        StatusCode::FORBIDDEN
+    );
+    let response = client.finish_match(match_id, &match_result()).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // Only the owner gives out roles
+    let response = client
+        .grant_tournament_role(
+            tournament_id,
+            &role_payload("stranger@test.se", TournamentRole::Organizer),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_rt::test]
+async fn should_allow_actions_of_granted_roles() {
+    let owner = spawn_server_and_authenticate().await;
+    let desk = owner.new_user("desk@test.se").await;
+    let referee = owner.new_user("referee@test.se").await;
+    let (tournament_id, match_id) = insert_tournament_and_match(&owner).await;
+
+    let response = owner
+        .grant_tournament_role(
+            tournament_id,
+            &role_payload("desk@test.se", TournamentRole::Desk),
+        )
+        .await;
+    assert!(response.status().is_success());
+    let response = owner
+        .grant_tournament_role(
+            tournament_id,
+            &role_payload("referee@test.se", TournamentRole::Referee),
+        )
+        .await;
+    assert!(response.status().is_success());
+    let response = owner
+        .grant_tournament_role(
+            tournament_id,
+            &role_payload("unknown@test.se", TournamentRole::Desk),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // The desk checks in players but doesn't report results or change the setup
+    assert!(register_player(&desk, match_id, 0).await.is_success());
+    assert!(register_player(&desk, match_id, 1).await.is_success());
+    let response = desk.finish_match(match_id, &match_result()).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = desk
+        .add_court_to_tournament(tournament_id, "Bana 2".to_string())
+        .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = referee.finish_match(match_id, &match_result()).await;
+    assert!(response.status().is_success());
+    let response = referee
+        .add_court_to_tournament(tournament_id, "Bana 2".to_string())
+        .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // Organizers can change the setup
+    let response = owner
+        .grant_tournament_role(
+            tournament_id,
+            &role_payload("desk@test.se", TournamentRole::Organizer),
+        )
+        .await;
+    assert!(response.status().is_success());
+    let response = desk
+        .add_court_to_tournament(tournament_id, "Bana 2".to_string())
+        .await;
+    assert!(response.status().is_success());
+
+    let response = desk.get_tournament_roles(tournament_id).await;
+    assert!(response.status().is_success());
+    let roles = response.json::<Vec<TournamentRoleGrant>>().await.unwrap();
+    assert_eq!(roles.len(), 2);
+    assert_eq!(roles[0].email, "desk@test.se");
+    assert_eq!(roles[0].role, TournamentRole::Organizer);
+    assert_eq!(roles[1].email, "referee@test.se");
+    assert_eq!(roles[1].role, TournamentRole::Referee);
+
+    // Once revoked the role no longer gives access
+    let response = owner
+        .revoke_tournament_role(tournament_id, roles[0].user_id)
+        .await;
+    assert!(response.status().is_success());
+    let response = desk
+        .add_court_to_tournament(tournament_id, "Bana 3".to_string())
+        .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,96 @@
+use common::{spawn_server_and_authenticate, AuthenticatedClient};
+use jsonwebtoken::decode_header;
+use reqwest::StatusCode;
+use std::collections::HashMap;
+use tournament_tracker_backend::{
+    authentication::{set_signing_keys, AuthTokens, SigningKeyInfo},
+    configuration::{get_configuration, SigningKeySettings, DEFAULT_SIGNING_KEY_ID},
+    stores::player_store::Player,
+};
+
+mod common;
+
+// Returns a client using a newly issued access token
+async fn refresh(client: &AuthenticatedClient) -> AuthenticatedClient {
+    let response = client
+        .unauthenticated_client
+        .refresh(&client.refresh_token)
+        .await;
+    assert!(response.status().is_success());
+    let tokens = response.json::<AuthTokens>().await.unwrap();
+    client.with_token(&tokens.access_token)
+}
+
+// Any endpoint that requires authentication
+async fn insert_player(client: &AuthenticatedClient, id: i64) -> StatusCode {
+    let player = Player {
+        id,
+        name: format!("Spelare {}", id),
+    };
+    client.insert_player(&player).await.status()
+}
+
+// The signing keys are shared by every server in the process so this is the only test here
+#[actix_rt::test]
+async fn should_rotate_signing_keys() {
+    let client = spawn_server_and_authenticate().await;
+    let admin = client.new_admin("admin@test.se").await;
+    let header = decode_header(&client.token).unwrap();
+    assert_eq!(header.kid, Some(DEFAULT_SIGNING_KEY_ID.to_string()));
+
+    let config = get_configuration().unwrap();
+    let mut keys = config.signing_keys.keys.clone();
+    keys.insert(
+        "next".to_string(),
+        base64::encode("another-secret-key-for-testing-purposes-only-0987654321"),
+    );
+    let key_info = set_signing_keys(&SigningKeySettings {
+        active_key_id: "next".to_string(),
+        keys: keys.clone(),
+    })
+    .unwrap();
+    assert_eq!(
+        key_info,
+        SigningKeyInfo {
+            active_key_id: "next".to_string(),
+            key_ids: vec![DEFAULT_SIGNING_KEY_ID.to_string(), "next".to_string()],
+        }
+    );
+
+    // Tokens signed with the previous key still work while it's accepted
+    assert!(insert_player(&client, 1).await.is_success());
+    let rotated_client = refresh(&client).await;
+    let header = decode_header(&rotated_client.token).unwrap();
+    assert_eq!(header.kid, Some("next".to_string()));
+    let rotated_admin = refresh(&admin).await;
+
+    keys.remove(DEFAULT_SIGNING_KEY_ID);
+    set_signing_keys(&SigningKeySettings {
+        active_key_id: "next".to_string(),
+        keys,
+    })
+    .unwrap();
+    assert_eq!(insert_player(&client, 2).await, StatusCode::UNAUTHORIZED);
+    assert!(insert_player(&rotated_client, 3).await.is_success());
+
+    // The active key must be one of the keys
+    let result = set_signing_keys(&SigningKeySettings {
+        active_key_id: "missing".to_string(),
+        keys: HashMap::new(),
+    });
+    assert!(result.is_err());
+    assert!(insert_player(&rotated_client, 4).await.is_success());
+
+    // Reloading goes back to the keys in the configuration
+    let response = rotated_client.reload_signing_keys().await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = rotated_admin.reload_signing_keys().await;
+    assert!(response.status().is_success());
+    let key_info = response.json::<SigningKeyInfo>().await.unwrap();
+    assert_eq!(key_info.active_key_id, DEFAULT_SIGNING_KEY_ID);
+    assert_eq!(key_info.key_ids, vec![DEFAULT_SIGNING_KEY_ID.to_string()]);
+    assert_eq!(
+        insert_player(&rotated_client, 5).await,
+        StatusCode::UNAUTHORIZED
+    );
+}
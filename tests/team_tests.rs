@@ -0,0 +1,195 @@
+use chrono::{Duration, Local};
+use common::{spawn_server_and_authenticate, AuthenticatedClient};
+use reqwest::StatusCode;
+use tournament_tracker_backend::{
+    endpoints::PlayerMatchRegistrationPayload,
+    match_operations::TournamentMatchList,
+    stores::{
+        match_store::Match, player_store::Player, team_store::Team, tournament_store::Tournament,
+    },
+};
+
+mod common;
+
+// Inserts players 1-4 and the teams 10 (1 & 2) and 20 (3 & 4)
+async fn insert_tournament_and_teams(client: &AuthenticatedClient) -> i32 {
+    let start_date = Local::today().naive_local();
+    let tournament = Tournament {
+        id: 0, // doesn't matter
+        name: "Södertälje open".into(),
+        start_date,
+        end_date: start_date + Duration::days(1),
+    };
+
+    let response = client.insert_tournament(&tournament).await;
+    assert!(response.status().is_success());
+    let tournament_id = response.text().await.unwrap().parse::<i32>().unwrap();
+
+    for id in 1..=4 {
+        let player = Player {
+            id,
+            name: format!("Spelare {}", id),
+        };
+        let response = client.insert_player(&player).await;
+        assert!(response.status().is_success());
+    }
+    for (id, players) in [(10, (1, 2)), (20, (3, 4))].iter() {
+        let team = Team {
+            id: *id,
+            name: format!("Lag {}", id),
+            player_one: players.0,
+            player_two: players.1,
+        };
+        let response = client.insert_team(&team).await;
+        assert!(response.status().is_success());
+    }
+    tournament_id
+}
+
+fn doubles_match(tournament_id: i32, team_one: i64, team_two: i64) -> Match {
+    Match {
+        id: 0, // not important
+        player_one: Some(team_one),
+        player_two: Some(team_two),
+        tournament_id,
+        class: "d96".to_string(),
+        start_time: Local::now().naive_local() + Duration::hours(2),
+    }
+}
+
+async fn register_player(
+    client: &AuthenticatedClient,
+    match_id: i64,
+    player_id: i64,
+) -> StatusCode {
+    let player_registration = PlayerMatchRegistrationPayload { player_id };
+    client
+        .register_player(match_id, &player_registration)
+        .await
+        .status()
+}
+
+#[actix_rt::test]
+async fn should_insert_and_get_team() {
+    let client = spawn_server_and_authenticate().await;
+    insert_tournament_and_teams(&client).await;
+
+    let response = client.get_team(10).await;
+    assert!(response.status().is_success());
+    let team = response.json::<Team>().await.unwrap();
+    assert_eq!(team.name, "Lag 10");
+    assert_eq!(team.players(), [1, 2]);
+
+    // The team can take part in matches as a player
+    let response = client.get_player(10).await;
+    assert!(response.status().is_success());
+    assert_eq!(response.json::<Player>().await.unwrap().name, "Lag 10");
+
+    let response = client.get_team(1).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn should_not_insert_invalid_teams() {
+    let client = spawn_server_and_authenticate().await;
+    insert_tournament_and_teams(&client).await;
+
+    let team = |id, player_one, player_two| Team {
+        id,
+        name: "Lag".to_string(),
+        player_one,
+        player_two,
+    };
+    // Same player twice
+    let response = client.insert_team(&team(30, 1, 1)).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    // Unknown player
+    let response = client.insert_team(&team(30, 1, 1337)).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    // Teams can't be part of other teams
+    let response = client.insert_team(&team(30, 10, 3)).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn should_not_insert_invalid_doubles_matches() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament_and_teams(&client).await;
+
+    // A singles player can't play against a team
+    let response = client
+        .insert_match(&doubles_match(tournament_id, 10, 3))
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Player 1 can't play on both sides of the net
+    let team = Team {
+        id: 30,
+        name: "Lag 30".to_string(),
+        player_one: 1,
+        player_two: 3,
+    };
+    let response = client.insert_team(&team).await;
+    assert!(response.status().is_success());
+    let response = client
+        .insert_match(&doubles_match(tournament_id, 10, 30))
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn should_start_doubles_match_when_all_players_arrived() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament_and_teams(&client).await;
+    let response = client
+        .add_court_to_tournament(tournament_id, "Bana 1".to_string())
+        .await;
+    assert!(response.status().is_success());
+
+    let response = client
+        .insert_match(&doubles_match(tournament_id, 10, 20))
+        .await;
+    assert!(response.status().is_success());
+    let match_id = response.text().await.unwrap().parse::<i64>().unwrap();
+
+    // The players check in, not the teams
+    assert_eq!(
+        register_player(&client, match_id, 10).await,
+        StatusCode::BAD_REQUEST
+    );
+
+    for player_id in 1..=3 {
+        assert!(register_player(&client, match_id, player_id)
+            .await
+            .is_success());
+    }
+    assert_eq!(
+        register_player(&client, match_id, 3).await,
+        StatusCode::BAD_REQUEST
+    );
+
+    // Player 4 is still missing
+    let response = client.get_tournaments_matches(tournament_id).await;
+    let match_list = response.json::<TournamentMatchList>().await.unwrap();
+    assert!(match_list.playing.is_empty());
+
+    assert!(register_player(&client, match_id, 4).await.is_success());
+    let response = client.get_tournaments_matches(tournament_id).await;
+    let match_list = response.json::<TournamentMatchList>().await.unwrap();
+    assert_eq!(match_list.playing.len(), 1);
+    let match_info = &match_list.playing[0];
+    assert_eq!(match_info.court, Some("Bana 1".to_string()));
+    assert_eq!(match_info.player_one.id, 10);
+    assert!(match_info.player_one_arrived);
+    assert!(match_info.player_two_arrived);
+    let team_players: Vec<i64> = match_info
+        .player_two_team
+        .iter()
+        .map(|member| member.player.id)
+        .collect();
+    assert_eq!(team_players, vec![3, 4]);
+    assert!(match_info
+        .player_two_team
+        .iter()
+        .all(|member| member.arrived));
+}
@@ -1,7 +1,10 @@
 use chrono::{Duration, Local};
 use common::spawn_server_and_authenticate;
 use reqwest::StatusCode;
-use tournament_tracker_backend::stores::tournament_store::Tournament;
+use tournament_tracker_backend::{
+    stores::{match_store::Match, player_store::Player, tournament_store::Tournament},
+    tournament_operations::TournamentPatch,
+};
 
 mod common;
 
@@ -75,3 +78,185 @@ async fn get_tournament_test() {
 
     assert_eq!(tournament_list[0], tournament);
 }
+
+async fn insert_tournament(client: &common::AuthenticatedClient) -> (i32, Tournament) {
+    let start_date = Local::today().naive_local();
+    let tournament = Tournament {
+        id: 0, // doesn't matter
+        name: "Södertälje open".into(),
+        start_date,
+        end_date: start_date + Duration::days(2),
+    };
+    let response = client.insert_tournament(&tournament).await;
+    assert!(response.status().is_success());
+    let id = response.text().await.unwrap().parse().unwrap();
+    (id, Tournament { id, ..tournament })
+}
+
+#[actix_rt::test]
+async fn get_single_tournament_test() {
+    let client = spawn_server_and_authenticate().await;
+    let (tournament_id, tournament) = insert_tournament(&client).await;
+
+    let response = client.get_tournament(tournament_id).await;
+    assert!(response.status().is_success());
+    let fetched = response.json::<Tournament>().await.unwrap();
+    assert_eq!(fetched.name, tournament.name);
+    assert_eq!(fetched.start_date, tournament.start_date);
+    assert_eq!(fetched.end_date, tournament.end_date);
+
+    let response = client.get_tournament(tournament_id + 1).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn update_tournament_test() {
+    let client = spawn_server_and_authenticate().await;
+    let (tournament_id, tournament) = insert_tournament(&client).await;
+
+    let updated = Tournament {
+        name: "Södertälje open 2021".into(),
+        end_date: tournament.end_date + Duration::days(1),
+        ..tournament.clone()
+    };
+    let response = client.update_tournament(tournament_id, &updated).await;
+    assert!(response.status().is_success());
+
+    let response = client.get_tournament(tournament_id).await;
+    let fetched = response.json::<Tournament>().await.unwrap();
+    assert_eq!(fetched.name, "Södertälje open 2021");
+    assert_eq!(fetched.end_date, updated.end_date);
+
+    // Same date validation as when inserting
+    let invalid = Tournament {
+        end_date: tournament.start_date - Duration::days(1),
+        ..tournament.clone()
+    };
+    let response = client.update_tournament(tournament_id, &invalid).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = client.update_tournament(tournament_id + 1, &updated).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // Only the given fields are changed
+    let patch = TournamentPatch {
+        name: Some("Järna open".into()),
+        start_date: None,
+        end_date: None,
+    };
+    let response = client.patch_tournament(tournament_id, &patch).await;
+    assert!(response.status().is_success());
+    let fetched = response.json::<Tournament>().await.unwrap();
+    assert_eq!(fetched.name, "Järna open");
+    assert_eq!(fetched.end_date, updated.end_date);
+}
+
+#[actix_rt::test]
+async fn should_keep_matches_within_tournament_dates() {
+    let client = spawn_server_and_authenticate().await;
+    let (tournament_id, tournament) = insert_tournament(&client).await;
+
+    for id in 0..2 {
+        let player = Player {
+            id,
+            name: format!("Spelare {}", id),
+        };
+        let response = client.insert_player(&player).await;
+        assert!(response.status().is_success());
+    }
+    // The match is played on the last day
+    let match_data = Match {
+        id: 0, // not important
+        player_one: Some(0),
+        player_two: Some(1),
+        tournament_id,
+        class: "p96".to_string(),
+        start_time: tournament.end_date.and_hms(12, 0, 0),
+    };
+    let response = client.insert_match(&match_data).await;
+    assert!(response.status().is_success());
+
+    // Matches can't be scheduled after the tournament has ended
+    let late_match = Match {
+        start_time: (tournament.end_date + Duration::days(1)).and_hms(12, 0, 0),
+        ..match_data.clone()
+    };
+    let response = client.insert_match(&late_match).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let patch = TournamentPatch {
+        name: None,
+        start_date: None,
+        end_date: Some(tournament.end_date - Duration::days(1)),
+    };
+    let response = client.patch_tournament(tournament_id, &patch).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    let patch = TournamentPatch {
+        end_date: Some(tournament.end_date + Duration::days(1)),
+        ..patch
+    };
+    let response = client.patch_tournament(tournament_id, &patch).await;
+    assert!(response.status().is_success());
+}
+
+#[actix_rt::test]
+async fn should_move_end_date_of_ongoing_tournament() {
+    let client = spawn_server_and_authenticate().await;
+    let (tournament_id, tournament) = insert_tournament(&client).await;
+    // Tournaments can't be created in the past
+    let start_date = Local::today().naive_local() - Duration::days(1);
+    sqlx::query("UPDATE tournaments SET start_date = $1 WHERE id = $2")
+        .bind(start_date)
+        .bind(tournament_id)
+        .execute(&client.unauthenticated_client.db_pool)
+        .await
+        .unwrap();
+
+    let patch = TournamentPatch {
+        name: None,
+        start_date: None,
+        end_date: Some(tournament.end_date + Duration::days(1)),
+    };
+    let response = client.patch_tournament(tournament_id, &patch).await;
+    assert!(response.status().is_success());
+    let updated = response.json::<Tournament>().await.unwrap();
+    assert_eq!(updated.start_date, start_date);
+    assert_eq!(updated.end_date, tournament.end_date + Duration::days(1));
+
+    // The end date still can't be before the start date
+    let patch = TournamentPatch {
+        end_date: Some(start_date - Duration::days(1)),
+        ..patch
+    };
+    let response = client.patch_tournament(tournament_id, &patch).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Moving the start date to another day in the past isn't allowed
+    let patch = TournamentPatch {
+        start_date: Some(start_date - Duration::days(1)),
+        end_date: None,
+        ..patch
+    };
+    let response = client.patch_tournament(tournament_id, &patch).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn delete_tournament_test() {
+    let client = spawn_server_and_authenticate().await;
+    let (tournament_id, _) = insert_tournament(&client).await;
+
+    // Only the owner can delete the tournament
+    let other_client = client.new_user("other@test.se").await;
+    let response = other_client.delete_tournament(tournament_id).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = client.delete_tournament(tournament_id).await;
+    assert!(response.status().is_success());
+
+    let response = client.get_tournament(tournament_id).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = client.delete_tournament(tournament_id).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
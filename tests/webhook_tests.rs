@@ -0,0 +1,305 @@
+use chrono::{Duration, Local};
+use common::{spawn_server_and_authenticate, spawn_webhook_receiver, AuthenticatedClient};
+use reqwest::StatusCode;
+use ring::hmac;
+use tournament_tracker_backend::{
+    endpoints::PlayerMatchRegistrationPayload,
+    events::{TournamentEvent, TournamentEventType},
+    stores::{
+        match_store::{Match, MatchOutcome, MatchResult},
+        player_store::Player,
+        tournament_store::Tournament,
+        webhook_store::{WebhookDelivery, WebhookDeliveryStatus},
+    },
+    webhooks::{NewWebhook, WebhookEvent, WebhookPayload},
+};
+use uuid::Uuid;
+
+mod common;
+
+async fn insert_tournament(client: &AuthenticatedClient) -> i32 {
+    let start_date = Local::today().naive_local();
+    let tournament = Tournament {
+        id: 0, // doesn't matter
+        name: "Södertälje open".into(),
+        start_date,
+        end_date: start_date + Duration::days(1),
+    };
+    let response = client.insert_tournament(&tournament).await;
+    assert!(response.status().is_success());
+    let tournament_id = response.text().await.unwrap().parse::<i32>().unwrap();
+
+    let response = client
+        .add_court_to_tournament(tournament_id, "Bana 1".to_string())
+        .await;
+    assert!(response.status().is_success());
+    for id in 0..2 {
+        let player = Player {
+            id,
+            name: format!("Spelare {}", id),
+        };
+        let response = client.insert_player(&player).await;
+        assert!(response.status().is_success());
+    }
+    tournament_id
+}
+
+async fn insert_match(client: &AuthenticatedClient, tournament_id: i32) -> i64 {
+    let match_data = Match {
+        id: 0, // not important
+        player_one: Some(0),
+        player_two: Some(1),
+        tournament_id,
+        class: "p96".to_string(),
+        start_time: Local::now().naive_local() + Duration::hours(2),
+    };
+    let response = client.insert_match(&match_data).await;
+    assert!(response.status().is_success());
+    response.text().await.unwrap().parse::<i64>().unwrap()
+}
+
+// Waits until every delivery of the webhook is no longer pending
+async fn wait_for_deliveries(
+    client: &AuthenticatedClient,
+    tournament_id: i32,
+    webhook_id: Uuid,
+    count: usize,
+) -> Vec<WebhookDelivery> {
+    for _ in 0..100 {
+        let response = client
+            .get_webhook_deliveries(tournament_id, webhook_id)
+            .await;
+        assert!(response.status().is_success());
+        let deliveries: Vec<WebhookDelivery> = response.json().await.unwrap();
+        if deliveries.len() >= count
+            && deliveries
+                .iter()
+                .all(|delivery| delivery.status != WebhookDeliveryStatus::Pending)
+        {
+            return deliveries;
+        }
+        actix_rt::time::delay_for(std::time::Duration::from_millis(100)).await;
+    }
+    panic!("Timed out waiting for webhook deliveries");
+}
+
+fn expected_signature(secret: &str, body: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let signature = hmac::sign(&key, body.as_bytes());
+    let hex: Vec<String> = signature
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", hex.concat())
+}
+
+#[actix_rt::test]
+async fn should_deliver_signed_events_to_webhooks() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament(&client).await;
+    let receiver = spawn_webhook_receiver(0);
+
+    let invalid_payloads = [
+        WebhookPayload {
+            url: "not a url".to_string(),
+            event_types: vec![TournamentEventType::MatchStarted],
+        },
+        WebhookPayload {
+            url: "ftp://example.com".to_string(),
+            event_types: vec![TournamentEventType::MatchStarted],
+        },
+        // Only public addresses are allowed
+        WebhookPayload {
+            url: "http://169.254.169.254/latest/meta-data".to_string(),
+            event_types: vec![TournamentEventType::MatchStarted],
+        },
+        WebhookPayload {
+            url: "http://10.0.0.1/".to_string(),
+            event_types: vec![TournamentEventType::MatchStarted],
+        },
+        WebhookPayload {
+            url: "http://[::1]/".to_string(),
+            event_types: vec![TournamentEventType::MatchStarted],
+        },
+        WebhookPayload {
+            url: "http://localhost/".to_string(),
+            event_types: vec![TournamentEventType::MatchStarted],
+        },
+        WebhookPayload {
+            url: receiver.url.clone(),
+            event_types: Vec::new(),
+        },
+    ];
+    for payload in invalid_payloads.iter() {
+        let response = client.create_webhook(tournament_id, payload).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    let payload = WebhookPayload {
+        url: receiver.url.clone(),
+        event_types: vec![
+            TournamentEventType::MatchStarted,
+            TournamentEventType::MatchFinished,
+        ],
+    };
+    let response = client.create_webhook(tournament_id, &payload).await;
+    assert!(response.status().is_success());
+    let new_webhook: NewWebhook = response.json().await.unwrap();
+    assert_eq!(new_webhook.webhook.url, receiver.url);
+    assert_eq!(new_webhook.webhook.event_types, payload.event_types);
+
+    let response = client.get_webhooks(tournament_id).await;
+    assert!(response.status().is_success());
+    let body = response.text().await.unwrap();
+    assert!(body.contains(&new_webhook.webhook.id.to_string()));
+    assert!(!body.contains(&new_webhook.secret));
+
+    // Others can't manage the webhooks
+    let other_client = client.new_user("other@test.se").await;
+    let response = other_client.get_webhooks(tournament_id).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = other_client.create_webhook(tournament_id, &payload).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // Creating the match and checking in the players isn't sent to the webhook
+    let match_id = insert_match(&client, tournament_id).await;
+    for player_id in 0..2 {
+        let response = client
+            .register_player(match_id, &PlayerMatchRegistrationPayload { player_id })
+            .await;
+        assert!(response.status().is_success());
+    }
+    let result = MatchResult {
+        result: "6-0 6-0".to_string(),
+        winner: 0,
+        outcome: MatchOutcome::Completed,
+    };
+    let response = client.finish_match(match_id, &result).await;
+    assert!(response.status().is_success());
+
+    let mut received = receiver.wait_for(2).await;
+    // Deliveries may be sent concurrently
+    received.sort_by_key(|webhook| webhook.delivery.parse::<i64>().unwrap());
+    for webhook in received.iter() {
+        assert_eq!(
+            webhook.signature,
+            expected_signature(&new_webhook.secret, &webhook.body)
+        );
+    }
+    assert_eq!(received[0].event, "match_started");
+    let event: WebhookEvent = serde_json::from_str(&received[0].body).unwrap();
+    assert_eq!(
+        event,
+        WebhookEvent {
+            tournament_id,
+            event: TournamentEvent::MatchStarted {
+                match_id,
+                court: "Bana 1".to_string()
+            }
+        }
+    );
+    assert_eq!(received[1].event, "match_finished");
+    let event: WebhookEvent = serde_json::from_str(&received[1].body).unwrap();
+    match event.event {
+        TournamentEvent::MatchFinished { match_info } => {
+            assert_eq!(match_info.id, match_id);
+            assert_eq!(match_info.winner, Some(0));
+        }
+        event => panic!("Unexpected event: {:?}", event),
+    }
+
+    let deliveries = wait_for_deliveries(&client, tournament_id, new_webhook.webhook.id, 2).await;
+    assert_eq!(deliveries.len(), 2);
+    // Newest first
+    assert_eq!(deliveries[0].event_type, "match_finished");
+    assert_eq!(deliveries[1].event_type, "match_started");
+    for delivery in deliveries.iter() {
+        assert_eq!(delivery.status, WebhookDeliveryStatus::Delivered);
+        assert_eq!(delivery.status_code, Some(200));
+        assert_eq!(delivery.attempts, 1);
+    }
+}
+
+#[actix_rt::test]
+async fn should_retry_failed_webhook_deliveries() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament(&client).await;
+    let receiver = spawn_webhook_receiver(1);
+
+    let payload = WebhookPayload {
+        url: receiver.url.clone(),
+        event_types: vec![TournamentEventType::MatchCreated],
+    };
+    let response = client.create_webhook(tournament_id, &payload).await;
+    assert!(response.status().is_success());
+    let new_webhook: NewWebhook = response.json().await.unwrap();
+
+    insert_match(&client, tournament_id).await;
+    // The first attempt fails and it's sent again with the same delivery id and signature
+    let received = receiver.wait_for(2).await;
+    assert_eq!(received[0].delivery, received[1].delivery);
+    assert_eq!(received[0].body, received[1].body);
+    assert_eq!(received[0].signature, received[1].signature);
+
+    let deliveries = wait_for_deliveries(&client, tournament_id, new_webhook.webhook.id, 1).await;
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0].status, WebhookDeliveryStatus::Delivered);
+    assert_eq!(deliveries[0].status_code, Some(200));
+    assert_eq!(deliveries[0].attempts, 2);
+
+    let response = client
+        .delete_webhook(tournament_id, new_webhook.webhook.id)
+        .await;
+    assert!(response.status().is_success());
+    let response = client
+        .get_webhook_deliveries(tournament_id, new_webhook.webhook.id)
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = client
+        .delete_webhook(tournament_id, new_webhook.webhook.id)
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn should_not_deliver_webhooks_to_private_addresses() {
+    let client = spawn_server_and_authenticate().await;
+    let tournament_id = insert_tournament(&client).await;
+    let receiver = spawn_webhook_receiver(0);
+
+    let payload = WebhookPayload {
+        url: receiver.url.clone(),
+        event_types: vec![TournamentEventType::MatchCreated],
+    };
+    let response = client.create_webhook(tournament_id, &payload).await;
+    assert!(response.status().is_success());
+    let new_webhook: NewWebhook = response.json().await.unwrap();
+
+    // Same as the host resolving to a private address after the webhook was created
+    let private_url = receiver.url.replace("127.0.0.1", "localhost");
+    sqlx::query("UPDATE webhooks SET url = $1 WHERE id = $2")
+        .bind(private_url)
+        .bind(new_webhook.webhook.id)
+        .execute(&client.unauthenticated_client.db_pool)
+        .await
+        .unwrap();
+
+    insert_match(&client, tournament_id).await;
+    let mut deliveries: Vec<WebhookDelivery> = Vec::new();
+    for _ in 0..100 {
+        let response = client
+            .get_webhook_deliveries(tournament_id, new_webhook.webhook.id)
+            .await;
+        deliveries = response.json().await.unwrap();
+        if deliveries.iter().any(|delivery| delivery.attempts > 0) {
+            break;
+        }
+        actix_rt::time::delay_for(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0].attempts, 1);
+    assert_eq!(deliveries[0].status_code, None);
+    assert!(deliveries[0].last_error.is_some());
+    assert!(receiver.received().is_empty());
+}